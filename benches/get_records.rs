@@ -0,0 +1,160 @@
+//! Compares `SqliteStore::get_tweets` against the former per-row lookups on a generated 
+//! database of 100k tweets. 
+//! 
+//! Run with `cargo bench --bench get_records`. 
+
+use std::time::{Duration, Instant};
+
+use rusqlite::{Connection, named_params};
+use sui_twitter_db::db;
+use sui_twitter_db::query_result::{BasicTweet, BasicUserDetail, FetchedTweet, TweetType};
+use sui_twitter_db::store::{SqliteStore, Store};
+
+const TWEET_NUM: usize = 100_000;
+const USER_NUM: usize = 500;
+const AUTHOR_ID: &str = "0";
+
+fn generate_db(conn: &Connection) {
+    db::init_db(conn).expect("should initialize database");
+    let tx = conn.unchecked_transaction().expect("should open transaction");
+    {
+        let mut user_stmt = tx.prepare("INSERT INTO user_dict (user_id, username, name) VALUES (?, ?, ?)").unwrap();
+        for user_idx in 0..USER_NUM {
+            user_stmt.execute([user_idx.to_string(), format!("user_{user_idx}"), format!("User {user_idx}")]).unwrap();
+        }
+
+        let mut ref_tweet_stmt = tx.prepare("INSERT INTO tweet_dict (tweet_id, author_id, text) VALUES (?, ?, ?)").unwrap();
+        let mut tweet_stmt = tx.prepare(
+            "INSERT INTO user_tweet (tweet_id, tweet_text, time, author_id, tweet_type, ref_tweet_id) 
+            VALUES (:tweet_id, :tweet_text, :time, :author_id, :tweet_type, :ref_tweet_id)"
+        ).unwrap();
+        let mut hashtag_stmt = tx.prepare("INSERT INTO hashtag_dict (hashtag, tweet_id) VALUES (?, ?)").unwrap();
+        let mut mention_stmt = tx.prepare("INSERT INTO mention_dict (ref_user_id, tweet_id) VALUES (?, ?)").unwrap();
+
+        for tweet_idx in 0..TWEET_NUM {
+            let tweet_id = format!("t{tweet_idx}");
+            let (tweet_type, ref_tweet_id) = match tweet_idx % 3 {
+                0 => ("tweet", None), 
+                1 => ("reply", Some(format!("r{tweet_idx}"))), 
+                _ => ("retweet", Some(format!("r{tweet_idx}")))
+            };
+            if let Some(ref_tweet_id) = &ref_tweet_id {
+                let ref_author = (tweet_idx % USER_NUM).to_string();
+                ref_tweet_stmt.execute([ref_tweet_id, &ref_author, &format!("referenced text {tweet_idx}")]).unwrap();
+                hashtag_stmt.execute([&format!("ref_tag_{}", tweet_idx % 17), ref_tweet_id]).unwrap();
+            }
+            tweet_stmt.execute(named_params! {
+                ":tweet_id": &tweet_id, 
+                ":tweet_text": format!("generated tweet {tweet_idx}"), 
+                ":time": "2023-01-01T00:00:00.000Z", 
+                ":author_id": AUTHOR_ID, 
+                ":tweet_type": tweet_type, 
+                ":ref_tweet_id": ref_tweet_id
+            }).unwrap();
+            if tweet_idx % 2 == 0 {
+                hashtag_stmt.execute([&format!("tag_{}", tweet_idx % 31), &tweet_id]).unwrap();
+            }
+            if tweet_idx % 5 == 0 {
+                mention_stmt.execute([&((tweet_idx + 1) % USER_NUM).to_string(), &tweet_id]).unwrap();
+            }
+        }
+    }
+    tx.commit().expect("should commit generated rows");
+}
+
+/// The former implementation: one query for the page, then hashtag, mention, user and 
+/// `tweet_dict` lookups for every row. 
+fn get_records_per_row(conn: &Connection, author_id: &str, max_results: Option<u16>) -> Vec<FetchedTweet> {
+    let mut user_tweet_stmt = conn.prepare(
+        "SELECT * FROM user_tweet WHERE author_id = :author_id ORDER BY id DESC LIMIT :limit"
+    ).unwrap();
+    let rows: Vec<(FetchedTweet, String, Option<String>)> = user_tweet_stmt.query_map(
+        named_params! {":author_id": author_id, ":limit": max_results.map(i64::from).unwrap_or(-1)}, 
+        |row| {
+            let mut tweet = FetchedTweet::new();
+            tweet.id = row.get(1)?;
+            tweet.text = row.get(2)?;
+            tweet.created_at = row.get(3)?;
+            tweet.author_id = row.get(4)?;
+            tweet.conversation_id = row.get(7)?;
+            tweet.in_reply_to_user_id = row.get(8)?;
+            tweet.replied_to_id = row.get(9)?;
+            Ok((tweet, row.get(5)?, row.get(6)?))
+        }
+    ).unwrap().map(|row| row.unwrap()).collect();
+
+    let mut fetched_tweet_list = Vec::new();
+    for (mut tweet, tweet_type_str, ref_tweet_id) in rows {
+        let mut hashtag_query = conn.prepare("SELECT * FROM hashtag_dict WHERE tweet_id = ?").unwrap();
+        let mut mention_query = conn.prepare("SELECT * FROM mention_dict WHERE tweet_id = ?").unwrap();
+        let mut user_query = conn.prepare("SELECT * FROM user_dict WHERE user_id = ?").unwrap();
+
+        let hashtags: Vec<String> = hashtag_query.query_map([&tweet.id], |row| row.get(1)).unwrap().map(|tag| tag.unwrap()).collect();
+        if !hashtags.is_empty() {
+            tweet.hashtags = Some(hashtags);
+        }
+        let mentioned_ids: Vec<String> = mention_query.query_map([&tweet.id], |row| row.get(1)).unwrap().map(|id| id.unwrap()).collect();
+        let mut user_of = |user_id: &str| -> BasicUserDetail {
+            user_query.query_row([user_id], |row| Ok(BasicUserDetail {
+                id: row.get(1)?, 
+                username: row.get(2)?, 
+                name: row.get(3)?
+            })).unwrap()
+        };
+        if !mentioned_ids.is_empty() {
+            tweet.mentions = Some(mentioned_ids.iter().map(|id| user_of(id)).collect());
+        }
+
+        if let Some(ref_tweet_id) = ref_tweet_id {
+            let mut ref_tweet: BasicTweet = conn.query_row("SELECT * FROM tweet_dict WHERE tweet_id = ?", [&ref_tweet_id], |row| {
+                Ok(BasicTweet { id: row.get(1)?, author_id: row.get(2)?, text: row.get(3)?, hashtags: None, urls: None, conversation_id: row.get(4)?, replied_to_id: row.get(5)? })
+            }).unwrap();
+            let ref_hashtags: Vec<String> = hashtag_query.query_map([&ref_tweet.id], |row| row.get(1)).unwrap().map(|tag| tag.unwrap()).collect();
+            if !ref_hashtags.is_empty() {
+                ref_tweet.hashtags = Some(ref_hashtags);
+            }
+            let ref_user = user_of(&ref_tweet.author_id);
+            tweet.tweet_type = if tweet_type_str == "retweet" {
+                TweetType::Retweet { tweet: ref_tweet, author: ref_user }
+            } else {
+                TweetType::Reply { tweet: ref_tweet, author: ref_user }
+            };
+        }
+        fetched_tweet_list.push(tweet);
+    }
+    fetched_tweet_list
+}
+
+fn time_it<T>(label: &str, rounds: u32, mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut result = f();
+    let started = Instant::now();
+    for _ in 0..rounds {
+        result = f();
+    }
+    let per_round = started.elapsed() / rounds;
+    println!("{label:<40} {per_round:>12.2?}");
+    (per_round, result)
+}
+
+fn main() {
+    let conn = Connection::open_in_memory().expect("should open in-memory database");
+    let started = Instant::now();
+    generate_db(&conn);
+    println!("generated {TWEET_NUM} tweets in {:.2?}", started.elapsed());
+
+    for (max_results, rounds) in [(Some(100), 50), (Some(5000), 5), (None, 1)] {
+        let label = match max_results {
+            Some(max_val) => format!("page of {max_val}"), 
+            None => "full timeline".to_string()
+        };
+        println!("--- {label}");
+        let (per_row, per_row_tweets) = time_it("per-row lookups", rounds, || {
+            get_records_per_row(&conn, AUTHOR_ID, max_results)
+        });
+        let (batched, batched_tweets) = time_it("SqliteStore::get_tweets", rounds, || {
+            SqliteStore::new(&conn).get_tweets(AUTHOR_ID, max_results, 0).expect("should read tweets")
+        });
+        assert_eq!(per_row_tweets, batched_tweets);
+        println!("speed-up: {:.1}x", per_row.as_secs_f64() / batched.as_secs_f64());
+    }
+}
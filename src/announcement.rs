@@ -0,0 +1,382 @@
+use std::error::Error;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, TimeZone};
+use rusqlite::{Connection, named_params};
+
+use crate::query_result::FetchedTweet;
+use crate::store::{SqliteStore, Store};
+
+/// Offset schedule times in tweets are read in; the monitored accounts announce in JST
+const ANNOUNCEMENT_UTC_OFFSET_SECS: i32 = 9 * 3600;
+
+/// Text hinting that a stream has ended and its recording is linked. Only phrases about the
+/// recording or about having watched count, since thanks and greetings appear in any tweet.
+const ARCHIVE_CUES: [&str; 6] = ["アーカイブ", "見てくれて", "archive", "thanks for watching", "thank you for watching", "vod"];
+/// Text hinting that a stream is live right now
+const LIVE_CUES: [&str; 9] = ["配信中", "始まった", "はじまった", "始まりました", "はじまりました", "開始しました", "🔴", "live now", "now live"];
+/// Words moving the stated schedule time to the next day
+const TOMORROW_CUES: [&str; 2] = ["明日", "tomorrow"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamPlatform {
+    YouTube,
+    Twitch
+}
+
+impl StreamPlatform {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StreamPlatform::YouTube => "youtube",
+            StreamPlatform::Twitch => "twitch"
+        }
+    }
+
+    fn from_str(platform_str: &str) -> StreamPlatform {
+        match platform_str {
+            "youtube" => StreamPlatform::YouTube,
+            "twitch" => StreamPlatform::Twitch,
+            _ => panic!("Unacceptable platform string in table stream_announcement!")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnouncementKind {
+    /// A stream scheduled for later, e.g. a YouTube waiting room
+    Announcement,
+    LiveNow,
+    /// The recording of a finished stream
+    Archive
+}
+
+impl AnnouncementKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AnnouncementKind::Announcement => "announcement",
+            AnnouncementKind::LiveNow => "live",
+            AnnouncementKind::Archive => "archive"
+        }
+    }
+
+    fn from_str(kind_str: &str) -> AnnouncementKind {
+        match kind_str {
+            "announcement" => AnnouncementKind::Announcement,
+            "live" => AnnouncementKind::LiveNow,
+            "archive" => AnnouncementKind::Archive,
+            _ => panic!("Unacceptable announcement kind string in table stream_announcement!")
+        }
+    }
+
+    /// Classifies tweet text by its wording; text without any cue is taken as an announcement
+    fn classify(text: &str) -> AnnouncementKind {
+        let lowered = text.to_lowercase();
+        if ARCHIVE_CUES.iter().any(|cue| lowered.contains(cue)) {
+            AnnouncementKind::Archive
+        } else if LIVE_CUES.iter().any(|cue| lowered.contains(cue)) {
+            AnnouncementKind::LiveNow
+        } else {
+            AnnouncementKind::Announcement
+        }
+    }
+}
+
+/// A stream link found in a stored tweet
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamAnnouncement {
+    pub tweet_id: String,
+    pub author_id: String,
+    pub platform: StreamPlatform,
+    /// YouTube video id, Twitch channel name or `videos/<id>` for Twitch recordings
+    pub stream_id: String,
+    pub url: String,
+    pub kind: AnnouncementKind,
+    pub tweet_time: String,
+    /// Start time stated in an announcement, as UTC
+    pub scheduled_at: Option<String>
+}
+
+impl StreamAnnouncement {
+    /// Finds the YouTube and Twitch links of `tweet` and classifies them by the tweet text
+    pub fn extract(tweet: &FetchedTweet) -> Vec<StreamAnnouncement> {
+        let mut announcements: Vec<StreamAnnouncement> = Vec::new();
+        let url_vec = match &tweet.urls {
+            Some(url_vec) => url_vec,
+            None => { return announcements; }
+        };
+
+        for url_entity in url_vec {
+            let linked_url = match url_entity.unwound_url.as_ref().or(url_entity.expanded_url.as_ref()) {
+                Some(linked_url) => linked_url,
+                None => { continue; }
+            };
+            let (platform, stream_id) = match parse_stream_url(linked_url) {
+                Some(stream) => stream,
+                None => { continue; }
+            };
+            if announcements.iter().any(|announcement| announcement.platform == platform && announcement.stream_id == stream_id) {
+                continue;
+            }
+
+            let mut kind = AnnouncementKind::classify(&tweet.text);
+            if platform == StreamPlatform::Twitch && stream_id.starts_with("videos/") {
+                kind = AnnouncementKind::Archive;
+            }
+            let scheduled_at = match kind {
+                AnnouncementKind::Announcement => scheduled_time(&tweet.text, &tweet.created_at),
+                _ => None
+            };
+
+            announcements.push(StreamAnnouncement {
+                tweet_id: tweet.id.clone(),
+                author_id: tweet.author_id.clone(),
+                platform,
+                stream_id,
+                url: linked_url.clone(),
+                kind,
+                tweet_time: tweet.created_at.clone(),
+                scheduled_at
+            });
+        }
+        announcements
+    }
+
+    /// Stores the announcement; a stream already recorded for the same tweet is kept as is
+    pub fn write_to_db(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        let mut announcement_stmt = conn.prepare(
+            "INSERT OR IGNORE INTO stream_announcement
+            (tweet_id, author_id, platform, stream_id, url, kind, tweet_time, scheduled_at)
+            VALUES (:tweet_id, :author_id, :platform, :stream_id, :url, :kind, :tweet_time, :scheduled_at)"
+        )?;
+        announcement_stmt.execute(named_params! {
+            ":tweet_id": &self.tweet_id,
+            ":author_id": &self.author_id,
+            ":platform": self.platform.as_str(),
+            ":stream_id": &self.stream_id,
+            ":url": &self.url,
+            ":kind": self.kind.as_str(),
+            ":tweet_time": &self.tweet_time,
+            ":scheduled_at": &self.scheduled_at
+        })?;
+        Ok(())
+    }
+
+    /// Extracts and stores the announcements of every stored tweet of `author_id`, e.g. after the
+    /// extraction rules changed. Returns the number of announcements found.
+    pub fn extract_stored(conn: &Connection, author_id: &str) -> Result<usize, Box<dyn Error>> {
+        let mut announcement_num = 0;
+        for tweet in SqliteStore::new(conn).get_tweets(author_id, None, 0)? {
+            for announcement in StreamAnnouncement::extract(&tweet) {
+                announcement.write_to_db(conn)?;
+                announcement_num += 1;
+            }
+        }
+        Ok(announcement_num)
+    }
+
+    /// Reads the announcements of `author_id` whose scheduled time (or tweet time, when no time
+    /// was stated) lies within `[since, until)`, in schedule order.
+    pub fn get_records(conn: &Connection, author_id: &str, since: Option<&str>, until: Option<&str>) -> Result<Vec<StreamAnnouncement>, Box<dyn Error>> {
+        let mut announcement_stmt = conn.prepare(
+            "SELECT tweet_id, author_id, platform, stream_id, url, kind, tweet_time, scheduled_at
+            FROM stream_announcement
+            WHERE author_id = :author_id
+                AND (:since IS NULL OR COALESCE(scheduled_at, tweet_time) >= :since)
+                AND (:until IS NULL OR COALESCE(scheduled_at, tweet_time) < :until)
+            ORDER BY COALESCE(scheduled_at, tweet_time), id"
+        )?;
+        let query_results = announcement_stmt.query_map(
+            named_params! {":author_id": author_id, ":since": since, ":until": until},
+            |row| Ok(StreamAnnouncement {
+                tweet_id: row.get(0)?,
+                author_id: row.get(1)?,
+                platform: StreamPlatform::from_str(&row.get::<_, String>(2)?),
+                stream_id: row.get(3)?,
+                url: row.get(4)?,
+                kind: AnnouncementKind::from_str(&row.get::<_, String>(5)?),
+                tweet_time: row.get(6)?,
+                scheduled_at: row.get(7)?
+            })
+        )?;
+
+        let mut announcements: Vec<StreamAnnouncement> = Vec::new();
+        for query_result in query_results {
+            announcements.push(query_result?);
+        }
+        Ok(announcements)
+    }
+}
+
+/// Recognises YouTube video and Twitch channel/recording links
+fn parse_stream_url(linked_url: &str) -> Option<(StreamPlatform, String)> {
+    let (_, rest) = linked_url.split_once("://")?;
+    let (host, path_and_query) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").or_else(|| host.strip_prefix("m.")).unwrap_or(&host);
+    let (path, query) = path_and_query.split_once('?').unwrap_or((path_and_query, ""));
+    let segments: Vec<&str> = path.split(['/', '#']).filter(|segment| !segment.is_empty()).collect();
+
+    match host {
+        "youtu.be" => segments.first().map(|video_id| (StreamPlatform::YouTube, video_id.to_string())),
+        "youtube.com" => match segments.as_slice() {
+            ["watch"] => query.split('&')
+                .find_map(|param| param.strip_prefix("v="))
+                .map(|video_id| (StreamPlatform::YouTube, video_id.to_string())),
+            ["live", video_id, ..] => Some((StreamPlatform::YouTube, video_id.to_string())),
+            _ => None
+        },
+        "twitch.tv" => match segments.as_slice() {
+            ["videos", video_id, ..] => Some((StreamPlatform::Twitch, format!("videos/{video_id}"))),
+            [channel] => Some((StreamPlatform::Twitch, channel.to_lowercase())),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Reads the first time of day stated in `text` (`21時`, `21時半`, `21:30`, `25時`, with full-width
+/// digits too) as the next such time after `tweet_time` in JST, returned as UTC.
+fn scheduled_time(text: &str, tweet_time: &str) -> Option<String> {
+    let (hour, minute) = stated_time_of_day(text)?;
+    let jst = FixedOffset::east_opt(ANNOUNCEMENT_UTC_OFFSET_SECS)?;
+    let tweeted_at = DateTime::parse_from_rfc3339(tweet_time).ok()?.with_timezone(&jst);
+
+    let mut date = tweeted_at.date_naive() + Duration::days(i64::from(hour / 24));
+    let lowered = text.to_lowercase();
+    if TOMORROW_CUES.iter().any(|cue| lowered.contains(cue)) {
+        date += Duration::days(1);
+    }
+    let time = NaiveTime::from_hms_opt(hour % 24, minute, 0)?;
+    let mut scheduled = jst.from_local_datetime(&date.and_time(time)).single()?;
+    if scheduled < tweeted_at {
+        scheduled += Duration::days(1);
+    }
+    Some(scheduled.naive_utc().format("%Y-%m-%dT%H:%M:%S.000Z").to_string())
+}
+
+fn stated_time_of_day(text: &str) -> Option<(u32, u32)> {
+    let chars: Vec<char> = text.chars().map(|c| match c {
+        '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+        '：' => ':',
+        _ => c
+    }).collect();
+
+    let read_number = |start: usize| -> (Option<u32>, usize) {
+        let mut end = start;
+        while end < chars.len() && end - start < 2 && chars[end].is_ascii_digit() {
+            end += 1;
+        }
+        let number: String = chars[start..end].iter().collect();
+        (number.parse().ok(), end)
+    };
+
+    let mut idx = 0;
+    while idx < chars.len() {
+        let preceded_by_digit = idx > 0 && chars[idx - 1].is_ascii_digit();
+        if !chars[idx].is_ascii_digit() || preceded_by_digit {
+            idx += 1;
+            continue;
+        }
+        let (hour, hour_end) = read_number(idx);
+        let hour = hour?;
+        if hour < 30 && hour_end < chars.len() {
+            match chars[hour_end] {
+                '時' => {
+                    if chars.get(hour_end + 1) == Some(&'半') {
+                        return Some((hour, 30));
+                    }
+                    let (minute, minute_end) = read_number(hour_end + 1);
+                    return match (minute, chars.get(minute_end)) {
+                        (Some(minute), Some('分')) if minute < 60 => Some((hour, minute)),
+                        _ => Some((hour, 0))
+                    };
+                }
+                ':' => {
+                    let (minute, minute_end) = read_number(hour_end + 1);
+                    if let Some(minute) = minute {
+                        if minute_end - hour_end == 3 && minute < 60 {
+                            return Some((hour, minute));
+                        }
+                    }
+                }
+                _ => ()
+            }
+        }
+        idx = hour_end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::query_result::UrlEntity;
+
+    use super::*;
+
+    fn linking_tweet(id: &str, text: &str, created_at: &str, linked_url: &str) -> FetchedTweet {
+        let mut tweet = FetchedTweet::new();
+        tweet.id = id.to_string();
+        tweet.author_id = "0".to_string();
+        tweet.text = text.to_string();
+        tweet.created_at = created_at.to_string();
+        tweet.urls = Some(vec![UrlEntity {
+            url: "https://t.co/x".to_string(),
+            expanded_url: Some(linked_url.to_string()),
+            display_url: None,
+            unwound_url: None,
+            domain: UrlEntity::domain_of(linked_url),
+            title: None
+        }]);
+        tweet
+    }
+
+    #[test]
+    fn test_parse_stream_url() {
+        assert_eq!(parse_stream_url("https://www.youtube.com/watch?v=abc123&t=10"), Some((StreamPlatform::YouTube, "abc123".to_string())));
+        assert_eq!(parse_stream_url("https://youtu.be/abc123"), Some((StreamPlatform::YouTube, "abc123".to_string())));
+        assert_eq!(parse_stream_url("https://youtube.com/live/abc123?feature=share"), Some((StreamPlatform::YouTube, "abc123".to_string())));
+        assert_eq!(parse_stream_url("https://www.twitch.tv/Suisei"), Some((StreamPlatform::Twitch, "suisei".to_string())));
+        assert_eq!(parse_stream_url("https://www.twitch.tv/videos/42"), Some((StreamPlatform::Twitch, "videos/42".to_string())));
+        assert_eq!(parse_stream_url("https://www.youtube.com/@suisei"), None);
+        assert_eq!(parse_stream_url("https://booth.pm/ja/items/1"), None);
+    }
+
+    #[test]
+    fn test_scheduled_time() {
+        // tweeted at 12:00 JST
+        let tweet_time = "2023-01-01T03:00:00.000Z";
+        assert_eq!(scheduled_time("今日は２１時から歌枠！", tweet_time), Some("2023-01-01T12:00:00.000Z".to_string()));
+        assert_eq!(scheduled_time("21:30~ Minecraft", tweet_time), Some("2023-01-01T12:30:00.000Z".to_string()));
+        assert_eq!(scheduled_time("明日20時半から", tweet_time), Some("2023-01-02T11:30:00.000Z".to_string()));
+        assert_eq!(scheduled_time("25時からゲリラ", tweet_time), Some("2023-01-01T16:00:00.000Z".to_string()));
+        assert_eq!(scheduled_time("9時に起きた", tweet_time), Some("2023-01-02T00:00:00.000Z".to_string()));
+        assert_eq!(scheduled_time("100万人ありがとう", tweet_time), None);
+    }
+
+    #[test]
+    fn test_extract_and_schedule() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let waiting_room = linking_tweet("1", "今日は21時から歌枠！ https://t.co/x", "2023-01-01T03:00:00.000Z", "https://youtu.be/a");
+        let live = linking_tweet("2", "配信中！来てくれてありがとう、おつかれさま https://t.co/x", "2023-01-01T12:01:00.000Z", "https://youtu.be/a");
+        let archive = linking_tweet("3", "見てくれてありがとう！アーカイブ https://t.co/x", "2023-01-01T14:00:00.000Z", "https://youtu.be/a");
+
+        let extracted = StreamAnnouncement::extract(&waiting_room);
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].kind, AnnouncementKind::Announcement);
+        assert_eq!(extracted[0].scheduled_at, Some("2023-01-01T12:00:00.000Z".to_string()));
+        assert_eq!(StreamAnnouncement::extract(&live)[0].kind, AnnouncementKind::LiveNow);
+        assert_eq!(StreamAnnouncement::extract(&archive)[0].kind, AnnouncementKind::Archive);
+
+        for tweet in [&waiting_room, &live, &archive] {
+            SqliteStore::new(&conn).write_tweet(tweet).unwrap();
+        }
+        assert_eq!(StreamAnnouncement::extract_stored(&conn, "0").unwrap(), 3);
+        assert_eq!(StreamAnnouncement::extract_stored(&conn, "0").unwrap(), 3);
+
+        let schedule = StreamAnnouncement::get_records(&conn, "0", Some("2023-01-01T11:00:00.000Z"), None).unwrap();
+        let scheduled_tweets: Vec<&str> = schedule.iter().map(|announcement| announcement.tweet_id.as_str()).collect();
+        assert_eq!(scheduled_tweets, vec!["1", "2", "3"]);
+    }
+}
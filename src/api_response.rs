@@ -0,0 +1,544 @@
+use std::error::Error;
+
+use serde::Deserialize;
+
+use crate::errors::InvalidTweetField;
+use crate::query_result::{self, BasicTweet, BasicUserDetail, EditControls, FetchedTweet, Media, TweetType, TweetVersion, UrlEntity, UserDetail};
+use crate::request_builder::LookupError;
+
+/// A v2 response; `data` is a list for timelines and lookups, a single object for stream lines
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Response<T> {
+    pub data: Option<T>,
+    #[serde(default)]
+    pub includes: Includes,
+    #[serde(default)]
+    pub meta: Meta,
+    #[serde(default)]
+    pub errors: Vec<ApiError>
+}
+
+/// Objects expanded from `data`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Includes {
+    #[serde(default)]
+    pub users: Vec<User>,
+    #[serde(default)]
+    pub tweets: Vec<Tweet>,
+    #[serde(default)]
+    pub media: Vec<MediaObject>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Meta {
+    pub result_count: Option<i64>,
+    pub next_token: Option<String>,
+    pub newest_id: Option<String>,
+    pub oldest_id: Option<String>
+}
+
+/// A partial error for one requested resource, or an error of the whole request
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ApiError {
+    pub resource_id: Option<String>,
+    pub title: Option<String>,
+    pub detail: Option<String>,
+    #[serde(rename = "type")]
+    pub error_type: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub profile_image_url: Option<String>,
+    pub profile_banner_url: Option<String>,
+    pub pinned_tweet_id: Option<String>,
+    pub protected: Option<bool>,
+    pub verified: Option<bool>
+}
+
+/// A tweet; which optional fields are present depends on the requested `tweet.fields`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Tweet {
+    pub id: String,
+    pub text: String,
+    pub author_id: Option<String>,
+    pub created_at: Option<String>,
+    pub conversation_id: Option<String>,
+    pub in_reply_to_user_id: Option<String>,
+    #[serde(default)]
+    pub referenced_tweets: Vec<ReferencedTweet>,
+    pub entities: Option<Entities>,
+    pub note_tweet: Option<NoteTweet>,
+    pub attachments: Option<Attachments>,
+    #[serde(default)]
+    pub edit_history_tweet_ids: Vec<String>,
+    pub edit_controls: Option<EditControlsObject>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ReferencedTweet {
+    /// `replied_to`, `quoted` or `retweeted`
+    #[serde(rename = "type")]
+    pub ref_type: String,
+    pub id: String
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Entities {
+    #[serde(default)]
+    pub hashtags: Vec<HashtagEntity>,
+    #[serde(default)]
+    pub mentions: Vec<MentionEntity>,
+    #[serde(default)]
+    pub urls: Vec<UrlObject>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HashtagEntity {
+    pub tag: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MentionEntity {
+    pub username: Option<String>,
+    pub id: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UrlObject {
+    pub url: Option<String>,
+    pub expanded_url: Option<String>,
+    pub display_url: Option<String>,
+    pub unwound_url: Option<String>,
+    pub title: Option<String>
+}
+
+/// The full body of a long-form tweet, whose `text` is truncated
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct NoteTweet {
+    pub text: String,
+    pub entities: Option<Entities>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Attachments {
+    #[serde(default)]
+    pub media_keys: Vec<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EditControlsObject {
+    pub edits_remaining: i64,
+    pub is_edit_eligible: bool,
+    pub editable_until: String
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MediaObject {
+    pub media_key: String,
+    #[serde(rename = "type")]
+    pub media_type: String,
+    pub url: Option<String>,
+    pub preview_image_url: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub alt_text: Option<String>,
+    pub duration_ms: Option<i64>,
+    #[serde(default)]
+    pub variants: Vec<MediaVariant>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MediaVariant {
+    pub content_type: Option<String>,
+    pub url: String,
+    pub bit_rate: Option<i64>
+}
+
+impl<T> Response<T> {
+    /// Whether this is the empty page the API answers with instead of `data` when nothing matches
+    pub fn is_empty_page(&self) -> bool {
+        self.meta.result_count == Some(0)
+    }
+
+    /// Per-id errors; errors without a `resource_id` concern the whole request and are left out
+    pub fn lookup_errors(&self) -> Vec<LookupError> {
+        self.errors.iter().filter_map(|api_error| api_error.lookup_error()).collect()
+    }
+}
+
+impl Includes {
+    pub fn basic_users(&self) -> Vec<BasicUserDetail> {
+        self.users.iter().map(|user| user.to_basic_user()).collect()
+    }
+
+    pub fn basic_tweets(&self) -> Result<Vec<BasicTweet>, Box<dyn Error>> {
+        self.tweets.iter().map(|tweet| tweet.to_basic_tweet()).collect()
+    }
+
+    pub fn media(&self) -> Vec<Media> {
+        self.media.iter().map(|media| media.to_media()).collect()
+    }
+}
+
+impl ApiError {
+    pub fn lookup_error(&self) -> Option<LookupError> {
+        Some(LookupError {
+            resource_id: self.resource_id.clone()?,
+            title: self.title.clone().unwrap_or_else(|| String::from("Unknown Error"))
+        })
+    }
+
+    pub fn message(&self) -> String {
+        self.detail.clone().or_else(|| self.title.clone()).unwrap_or_else(|| String::from("unknown error"))
+    }
+}
+
+impl User {
+    pub fn to_basic_user(&self) -> BasicUserDetail {
+        BasicUserDetail {
+            id: self.id.clone(),
+            username: self.username.clone(),
+            name: self.name.clone()
+        }
+    }
+
+    pub fn to_user_detail(&self) -> UserDetail {
+        UserDetail {
+            id: self.id.clone(),
+            username: self.username.clone(),
+            name: self.name.clone(),
+            location: self.location.clone(),
+            description: self.description.clone(),
+            // an empty url means the profile has none
+            url: self.url.clone().filter(|url| !url.is_empty()),
+            profile_image_url: self.profile_image_url.clone(),
+            profile_banner_url: self.profile_banner_url.clone(),
+            pinned_tweet_id: self.pinned_tweet_id.clone(),
+            protected: self.protected,
+            verified: self.verified
+        }
+    }
+}
+
+impl Tweet {
+    pub fn author_id(&self) -> Result<String, Box<dyn Error>> {
+        self.author_id.clone().ok_or_else(|| InvalidTweetField::new("author_id").into())
+    }
+
+    pub fn created_at(&self) -> Result<String, Box<dyn Error>> {
+        self.created_at.clone().ok_or_else(|| InvalidTweetField::new("created_at").into())
+    }
+
+    /// `text` of a long-form tweet is truncated, the note has the full body and its entities
+    fn full_text(&self) -> (&str, Option<&Entities>) {
+        match &self.note_tweet {
+            Some(note_tweet) => (&note_tweet.text, note_tweet.entities.as_ref()),
+            None => (&self.text, self.entities.as_ref())
+        }
+    }
+
+    pub fn replied_to_id(&self) -> Option<String> {
+        self.referenced_tweets.iter()
+            .find(|reference| reference.ref_type == "replied_to")
+            .map(|reference| reference.id.clone())
+    }
+
+    /// The reference a tweet is classified by when it has several, e.g. a reply quoting another
+    /// tweet: a reply first, then a retweet, then a quote.
+    fn primary_reference(&self) -> Option<&ReferencedTweet> {
+        ["replied_to", "retweeted", "quoted"].iter()
+            .find_map(|ref_type| self.referenced_tweets.iter().find(|reference| reference.ref_type == *ref_type))
+            .or(self.referenced_tweets.first())
+    }
+
+    /// The tweet as stored in `tweet_dict`
+    pub fn to_basic_tweet(&self) -> Result<BasicTweet, Box<dyn Error>> {
+        let (text, entities) = self.full_text();
+        Ok(BasicTweet {
+            id: self.id.clone(),
+            author_id: self.author_id()?,
+            text: text.to_string(),
+            hashtags: entities.and_then(|entities| entities.hashtags()),
+            urls: entities.and_then(|entities| entities.url_entities()),
+            conversation_id: self.conversation_id.clone(),
+            replied_to_id: self.replied_to_id()
+        })
+    }
+
+    /// The tweet of a monitored user as stored in `user_tweet`. Tweets and users it references
+    /// but the includes lack are added to the related lists as placeholders.
+    pub fn to_fetched_tweet(
+        &self,
+        related_tweets: &mut Vec<BasicTweet>,
+        related_users: &mut Vec<BasicUserDetail>,
+        related_media: &[Media]
+    ) -> Result<FetchedTweet, Box<dyn Error>> {
+        let mut tweet_item = FetchedTweet::new();
+        let (text, entities) = self.full_text();
+        tweet_item.id = self.id.clone();
+        tweet_item.text = text.to_string();
+        tweet_item.is_note_tweet = self.note_tweet.is_some();
+        tweet_item.created_at = self.created_at()?;
+        tweet_item.author_id = self.author_id()?;
+        tweet_item.conversation_id = self.conversation_id.clone();
+        tweet_item.in_reply_to_user_id = self.in_reply_to_user_id.clone();
+        tweet_item.replied_to_id = self.replied_to_id();
+        tweet_item.edit_controls = self.edit_controls.as_ref().map(|edit_controls| EditControls {
+            edits_remaining: edit_controls.edits_remaining,
+            is_edit_eligible: edit_controls.is_edit_eligible,
+            editable_until: edit_controls.editable_until.clone()
+        });
+        // edited tweets are stored under the id of their first version
+        if self.edit_history_tweet_ids.len() > 1 {
+            tweet_item.versions = Some(self.edit_history_tweet_ids.iter().map(|version_id| TweetVersion {
+                tweet_id: version_id.clone(),
+                text: (*version_id == self.id).then(|| tweet_item.text.clone())
+            }).collect());
+            tweet_item.id = self.edit_history_tweet_ids[0].clone();
+        }
+
+        tweet_item.tweet_type = self.tweet_type(related_tweets, related_users)?;
+
+        if let Some(entities) = entities {
+            tweet_item.hashtags = entities.hashtags();
+            tweet_item.urls = entities.url_entities();
+            for mention_entity in entities.mentions.iter() {
+                let mentioned_username = match &mention_entity.username {
+                    Some(mentioned_username) => mentioned_username,
+                    None => { continue; }
+                };
+                let mentioned_id = match &mention_entity.id {
+                    Some(mentioned_id) => mentioned_id,
+                    None => { return Err(Box::new(InvalidTweetField::new("entities.mentions.id"))); }
+                };
+                let name = match query_result::find_by_id(mentioned_id, related_users) {
+                    Some(mentioned_user) => mentioned_user.name.clone(),
+                    None => String::from("Unavailable account")
+                };
+                tweet_item.mentions.get_or_insert_with(Vec::new).push(BasicUserDetail {
+                    id: mentioned_id.clone(),
+                    username: mentioned_username.clone(),
+                    name
+                });
+            }
+        }
+
+        if let Some(attachments) = &self.attachments {
+            for media_key in attachments.media_keys.iter() {
+                if let Some(media) = query_result::find_by_id(media_key, related_media) {
+                    tweet_item.media.get_or_insert_with(Vec::new).push(media.clone());
+                }
+            }
+        }
+
+        Ok(tweet_item)
+    }
+
+    /// Classifies the tweet by its primary reference, taking the referenced tweet and its author
+    /// from the related lists. References left out of `includes` are added to the lists as
+    /// placeholders to be looked up again later.
+    pub fn tweet_type(
+        &self,
+        related_tweets: &mut Vec<BasicTweet>,
+        related_users: &mut Vec<BasicUserDetail>
+    ) -> Result<TweetType, Box<dyn Error>> {
+        let primary_reference = match self.primary_reference() {
+            Some(primary_reference) => primary_reference,
+            None => { return Ok(TweetType::Tweet); }
+        };
+
+        let related_tweet_detail = match query_result::find_by_id(&primary_reference.id, related_tweets) {
+            Some(related_tweet) => related_tweet.clone(),
+            None => {
+                let placeholder = BasicTweet::placeholder(&primary_reference.id);
+                related_tweets.push(placeholder.clone());
+                placeholder
+            }
+        };
+
+        let related_user_detail = match query_result::find_by_id(&related_tweet_detail.author_id, related_users) {
+            Some(related_user) => related_user.clone(),
+            None => {
+                let placeholder = BasicUserDetail::placeholder(&related_tweet_detail.author_id);
+                if !related_tweet_detail.is_placeholder() {
+                    related_users.push(placeholder.clone());
+                }
+                placeholder
+            }
+        };
+
+        match primary_reference.ref_type.as_str() {
+            "replied_to" => Ok(TweetType::Reply {
+                tweet: related_tweet_detail,
+                author: related_user_detail
+            }),
+            "quoted" | "retweeted" => Ok(TweetType::Retweet {
+                tweet: related_tweet_detail,
+                author: related_user_detail
+            }),
+            _ => Err(Box::new(InvalidTweetField::new("referenced_tweets.type")))
+        }
+    }
+}
+
+impl Entities {
+    pub fn hashtags(&self) -> Option<Vec<String>> {
+        let hashtags: Vec<String> = self.hashtags.iter().filter_map(|hashtag| hashtag.tag.clone()).collect();
+        (!hashtags.is_empty()).then_some(hashtags)
+    }
+
+    pub fn url_entities(&self) -> Option<Vec<UrlEntity>> {
+        let url_entities: Vec<UrlEntity> = self.urls.iter().filter_map(|url_object| {
+            let domain = url_object.unwound_url.as_ref().or(url_object.expanded_url.as_ref()).and_then(|url| UrlEntity::domain_of(url));
+            Some(UrlEntity {
+                url: url_object.url.clone()?,
+                expanded_url: url_object.expanded_url.clone(),
+                display_url: url_object.display_url.clone(),
+                unwound_url: url_object.unwound_url.clone(),
+                domain,
+                title: url_object.title.clone()
+            })
+        }).collect();
+        (!url_entities.is_empty()).then_some(url_entities)
+    }
+}
+
+impl MediaObject {
+    pub fn to_media(&self) -> Media {
+        // videos and GIFs have no `url`, only mp4/m3u8 variants at several bit rates
+        let url = self.url.clone().or_else(|| {
+            self.variants.iter()
+                .filter(|variant| variant.content_type.as_deref() == Some("video/mp4"))
+                .fold(None, |best_variant: Option<&MediaVariant>, variant| match best_variant {
+                    Some(best) if best.bit_rate.unwrap_or(0) >= variant.bit_rate.unwrap_or(0) => Some(best),
+                    _ => Some(variant)
+                })
+                .map(|variant| variant.url.clone())
+        });
+
+        Media {
+            media_key: self.media_key.clone(),
+            media_type: self.media_type.clone(),
+            url,
+            preview_image_url: self.preview_image_url.clone(),
+            width: self.width,
+            height: self.height,
+            alt_text: self.alt_text.clone(),
+            duration_ms: self.duration_ms,
+            content_hash: None,
+            local_path: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture<T: serde::de::DeserializeOwned>(fixture_json: &str) -> Response<T> {
+        serde_json::from_str(fixture_json).unwrap()
+    }
+
+    #[test]
+    fn test_user() {
+        let response: Response<Vec<User>> = fixture(include_str!("../tests/fixtures/users_by.json"));
+        let user_detail = response.data.unwrap()[0].to_user_detail();
+        assert_eq!(user_detail.id, "975275878673408001");
+        assert_eq!(user_detail.username, "suisei_hosimati");
+        assert_eq!(user_detail.location, Some(String::new()));
+        assert_eq!(user_detail.url, None);
+        assert_eq!(user_detail.pinned_tweet_id, Some("1630000000000000000".to_string()));
+        assert_eq!(user_detail.protected, Some(false));
+
+        let response: Response<Vec<User>> = fixture(include_str!("../tests/fixtures/liking_users.json"));
+        assert_eq!(response.data.unwrap().len(), 2);
+        assert!(response.meta.next_token.is_some());
+    }
+
+    #[test]
+    fn test_tweets() {
+        let response: Response<Vec<Tweet>> = fixture(include_str!("../tests/fixtures/user_tweets.json"));
+        let tweet_list = response.data.as_ref().unwrap();
+        let mut related_users = response.includes.basic_users();
+        let mut related_tweets = response.includes.basic_tweets().unwrap();
+        let related_media = response.includes.media();
+        let towa = related_users[1].clone();
+        let collab = related_tweets[0].clone();
+
+        let fetched: Vec<FetchedTweet> = tweet_list.iter()
+            .map(|tweet| tweet.to_fetched_tweet(&mut related_tweets, &mut related_users, &related_media).unwrap())
+            .collect();
+
+        assert_eq!(fetched[0].tweet_type, TweetType::Retweet { tweet: collab.clone(), author: towa.clone() });
+        assert_eq!(fetched[0].mentions, Some(vec![towa.clone()]));
+
+        // a reply quoting another tweet is classified as a reply
+        assert_eq!(fetched[1].tweet_type, TweetType::Reply { tweet: collab.clone(), author: towa.clone() });
+        assert_eq!(fetched[1].replied_to_id, Some("1620000000000000001".to_string()));
+        assert_eq!(fetched[1].in_reply_to_user_id, Some("1200357161747939328".to_string()));
+        let quote_url = &fetched[1].urls.as_ref().unwrap()[0];
+        assert_eq!(quote_url.url, "https://t.co/quote");
+        assert_eq!(quote_url.domain, Some("twitter.com".to_string()));
+
+        assert!(fetched[2].is_note_tweet);
+        assert_eq!(fetched[2].text, "長文のお知らせです。全文はこちら #ほしまちすたじお");
+        assert_eq!(fetched[2].hashtags, Some(vec!["ほしまちすたじお".to_string()]));
+
+        assert_eq!(fetched[3].id, "1630000000000000001");
+        assert_eq!(fetched[3].versions, Some(vec![
+            TweetVersion { tweet_id: "1630000000000000001".to_string(), text: None },
+            TweetVersion { tweet_id: "1630000000000000002".to_string(), text: Some("MV公開！ #Suisei https://t.co/video".to_string()) }
+        ]));
+        assert_eq!(fetched[3].edit_controls, Some(EditControls {
+            edits_remaining: 4,
+            is_edit_eligible: true,
+            editable_until: "2023-02-27T12:30:01.000Z".to_string()
+        }));
+        let video = &fetched[3].media.as_ref().unwrap()[0];
+        assert_eq!(video.media_type, "video");
+        assert_eq!(video.url, Some("https://video.twimg.com/ext_tw_video/1/pu/vid/1280x720/high.mp4".to_string()));
+        assert_eq!(video.duration_ms, Some(215000));
+
+        // the unavailable quoted tweet is a partial error, not a page failure
+        assert_eq!(response.lookup_errors(), vec![LookupError {
+            resource_id: "1610000000000000009".to_string(),
+            title: "Not Found Error".to_string()
+        }]);
+        assert_eq!(response.meta.next_token, Some("7140dibdnow9c7btw3w29grvxfcgvpb9n9coehpk7xz5i".to_string()));
+        assert!(!response.is_empty_page());
+    }
+
+    #[test]
+    fn test_empty_page() {
+        let response: Response<Vec<Tweet>> = fixture(include_str!("../tests/fixtures/empty_page.json"));
+        assert!(response.data.is_none());
+        assert!(response.is_empty_page());
+        assert!(response.lookup_errors().is_empty());
+    }
+
+    #[test]
+    fn test_stream_lines() {
+        let response: Response<Tweet> = fixture(include_str!("../tests/fixtures/stream_tweet.json"));
+        assert_eq!(response.data.unwrap().to_basic_tweet().unwrap().id, "1630000000000000020");
+
+        let response: Response<Tweet> = fixture(include_str!("../tests/fixtures/stream_disconnect.json"));
+        assert!(response.data.is_none());
+        assert_eq!(response.errors[0].message(), "This stream has been disconnected upstream for operational reasons.");
+        // errors about the connection are not tied to a resource
+        assert!(response.lookup_errors().is_empty());
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let tweet: Tweet = serde_json::from_str(r#"{ "id": "1", "text": "no fields requested" }"#).unwrap();
+        assert_eq!(tweet.to_basic_tweet().unwrap_err().to_string(), InvalidTweetField::new("author_id").to_string());
+        assert_eq!(tweet.created_at().unwrap_err().to_string(), InvalidTweetField::new("created_at").to_string());
+    }
+}
@@ -0,0 +1,252 @@
+use std::error::Error;
+
+use rusqlite::{Connection, named_params, OptionalExtension};
+
+use crate::configuration;
+use crate::paginator::Fetcher;
+use crate::query_result;
+use crate::worker::StopSignal;
+use crate::writer::DbWriter;
+
+/// How far the initialization paged through an endpoint of a monitored user
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchCheckpoint {
+    pub user_id: String,
+    /// e.g. `tweets` or `following`
+    pub endpoint: String,
+    /// Token of the next page to request; `None` before the first page and once completed
+    pub page_token: Option<String>,
+    pub pages: u32,
+    pub records: u32,
+    pub completed: bool,
+    pub updated_at: String
+}
+
+impl FetchCheckpoint {
+    pub fn new(user_id: &str, endpoint: &str) -> FetchCheckpoint {
+        FetchCheckpoint {
+            user_id: user_id.to_string(),
+            endpoint: endpoint.to_string(),
+            page_token: None,
+            pages: 0,
+            records: 0,
+            completed: false,
+            updated_at: query_result::now()
+        }
+    }
+
+    /// The stored checkpoint of `endpoint`, or a new one when paging has not started
+    pub fn get_record(conn: &Connection, user_id: &str, endpoint: &str) -> Result<FetchCheckpoint, Box<dyn Error>> {
+        let checkpoint = conn.query_row(
+            "SELECT page_token, pages, records, completed, updated_at FROM fetch_checkpoint
+            WHERE user_id = :user_id AND endpoint = :endpoint",
+            named_params! { ":user_id": user_id, ":endpoint": endpoint },
+            |row| Ok(FetchCheckpoint {
+                user_id: user_id.to_string(),
+                endpoint: endpoint.to_string(),
+                page_token: row.get(0)?,
+                pages: row.get(1)?,
+                records: row.get(2)?,
+                completed: row.get(3)?,
+                updated_at: row.get(4)?
+            })
+        ).optional()?;
+        Ok(checkpoint.unwrap_or_else(|| FetchCheckpoint::new(user_id, endpoint)))
+    }
+
+    pub fn write_to_db(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        conn.execute(
+            "INSERT INTO fetch_checkpoint (user_id, endpoint, page_token, pages, records, completed, updated_at)
+            VALUES (:user_id, :endpoint, :page_token, :pages, :records, :completed, :updated_at)
+            ON CONFLICT (user_id, endpoint) DO UPDATE SET
+                page_token = excluded.page_token, pages = excluded.pages, records = excluded.records,
+                completed = excluded.completed, updated_at = excluded.updated_at",
+            named_params! {
+                ":user_id": &self.user_id,
+                ":endpoint": &self.endpoint,
+                ":page_token": &self.page_token,
+                ":pages": self.pages,
+                ":records": self.records,
+                ":completed": self.completed,
+                ":updated_at": &self.updated_at
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Records a written page; the endpoint is completed once there is no next page
+    pub fn advance(&mut self, next_token: Option<String>, records: usize) {
+        self.completed = next_token.is_none();
+        self.page_token = next_token;
+        self.pages += 1;
+        self.records += records as u32;
+        self.updated_at = query_result::now();
+    }
+
+    /// Whether an initialization of `usernames` was interrupted before completing every endpoint,
+    /// including before the first checkpoint of one of them was written. Databases initialized
+    /// before checkpoints existed never were.
+    pub fn interrupted(conn: &Connection, usernames: &[String]) -> Result<bool, Box<dyn Error>> {
+        let has_table: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'fetch_checkpoint')",
+            [],
+            |row| row.get(0)
+        )?;
+        if !has_table {
+            return Ok(false);
+        }
+        let interrupted: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM fetch_checkpoint WHERE completed = 0)",
+            [],
+            |row| row.get(0)
+        )?;
+        if interrupted {
+            return Ok(true);
+        }
+        // the profile is written with the first checkpoint of its user
+        for username in usernames.iter() {
+            let started: bool = conn.query_row(
+                "SELECT EXISTS (
+                    SELECT 1 FROM user_profile
+                    JOIN fetch_checkpoint ON fetch_checkpoint.user_id = user_profile.user_id
+                    WHERE user_profile.username = :username COLLATE NOCASE
+                )",
+                named_params! { ":username": username },
+                |row| row.get(0)
+            )?;
+            if !started {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// The write of one page's records, returning the number of records written
+pub type PageWrite = Box<dyn FnOnce(&Connection) -> Result<usize, Box<dyn Error>> + Send>;
+
+/// Pages through `fetcher` from the stored `checkpoint` on. `write_page` turns every page into
+/// its write, downloading what it needs first, and `writer` runs the write in one transaction
+/// with the advanced checkpoint, so an interrupted run resumes at the first page not written,
+/// also once `stop` is given.
+pub fn fetch_resumable<F: Fetcher>(
+    conf: &configuration::Config,
+    writer: &DbWriter,
+    stop: &StopSignal,
+    mut checkpoint: FetchCheckpoint,
+    fetcher: &F,
+    mut write_page: impl FnMut(F::Page) -> Result<PageWrite, Box<dyn Error>>
+) -> Result<FetchCheckpoint, Box<dyn Error>> {
+    if checkpoint.completed {
+        return Ok(checkpoint);
+    }
+
+    for page in fetcher.pages_from(conf, checkpoint.page_token.clone(), stop) {
+        let page = page?;
+        let page_write = write_page(page.records)?;
+        let mut advanced = checkpoint.clone();
+        checkpoint = writer.write(move |conn| {
+            let records = page_write(conn)?;
+            advanced.advance(page.next_token, records);
+            advanced.write_to_db(conn)?;
+            Ok(advanced)
+        })?;
+        log::info!(
+            "{}: {} page {} written, {} records so far",
+            &checkpoint.user_id, &checkpoint.endpoint, checkpoint.pages, checkpoint.records
+        );
+    }
+    Ok(checkpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use reqwest::blocking::Client;
+
+    use crate::api_response::{Response, User};
+    use crate::configuration::TaskType;
+    use crate::db;
+    use crate::paginator::{PageIter, Paginator};
+    use crate::query_result::{BasicUserDetail, FetchedUser};
+    use crate::store::{SqliteStore, Store};
+    use crate::test_support::{self, serve_pages, FIRST_PAGE, LAST_PAGE};
+
+    use super::*;
+
+    struct TestFetcher {
+        base_url: String
+    }
+
+    impl Fetcher for TestFetcher {
+        type Page = Vec<BasicUserDetail>;
+
+        fn pages_from<'a>(&'a self, _conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, Self::Page> {
+            let request = Client::new().get(format!("{}/2/users/1/following", &self.base_url));
+            Box::new(Paginator::new(request, |page: &Response<Vec<User>>| {
+                Ok(ControlFlow::Continue(page.data.iter().flatten().map(|user| user.to_basic_user()).collect()))
+            }).start_at(page_token).stop_on(stop))
+        }
+
+        fn parse_archived(&self, _conf: &configuration::Config, body: &str, _fetched_at: &str) -> Result<Self::Page, Box<dyn Error>> {
+            let page: Response<Vec<User>> = serde_json::from_str(body)?;
+            Ok(page.data.iter().flatten().map(|user| user.to_basic_user()).collect())
+        }
+    }
+
+    #[test]
+    fn test_fetch_resumable() {
+        let (db_path, writer, writer_handle) = test_support::writer_db("checkpoint");
+        let conn = db::open(&db_path).unwrap();
+        let conf = test_support::config(TaskType::Initializing);
+        assert!(!FetchCheckpoint::interrupted(&conn, &[]).unwrap());
+
+        let write_users = |users: Vec<BasicUserDetail>| -> Result<PageWrite, Box<dyn Error>> {
+            Ok(Box::new(move |conn: &Connection| {
+                let store = SqliteStore::new(conn);
+                for user in users.iter() {
+                    store.write_user(user)?;
+                }
+                Ok(users.len())
+            }))
+        };
+
+        // the connection is refused after the first page
+        let (base_url, server) = serve_pages(&[FIRST_PAGE]);
+        let fetcher = TestFetcher { base_url };
+        assert!(fetch_resumable(&conf, &writer, &StopSignal::new(), FetchCheckpoint::get_record(&conn, "1", "following").unwrap(), &fetcher, write_users).is_err());
+        server.join().unwrap();
+        assert!(FetchCheckpoint::interrupted(&conn, &[]).unwrap());
+        let checkpoint = FetchCheckpoint::get_record(&conn, "1", "following").unwrap();
+        assert_eq!((checkpoint.page_token.as_deref(), checkpoint.pages, checkpoint.records, checkpoint.completed), (Some("page2"), 1, 2, false));
+        assert!(SqliteStore::new(&conn).get_user("2").unwrap().is_some());
+
+        let (base_url, server) = serve_pages(&[LAST_PAGE]);
+        let fetcher = TestFetcher { base_url };
+        let checkpoint = fetch_resumable(&conf, &writer, &StopSignal::new(), FetchCheckpoint::get_record(&conn, "1", "following").unwrap(), &fetcher, write_users).unwrap();
+        assert_eq!(server.join().unwrap(), vec!["GET /2/users/1/following?pagination_token=page2 HTTP/1.1"]);
+        assert_eq!((checkpoint.page_token.as_deref(), checkpoint.pages, checkpoint.records, checkpoint.completed), (None, 2, 3, true));
+        assert_eq!(FetchCheckpoint::get_record(&conn, "1", "following").unwrap(), checkpoint);
+        assert!(!FetchCheckpoint::interrupted(&conn, &[]).unwrap());
+
+        // a run killed before the first page of another user was written
+        let usernames = vec![String::from("suisei")];
+        assert!(FetchCheckpoint::interrupted(&conn, &usernames).unwrap());
+        let mut profile = FetchedUser::record(&TaskType::Initializing);
+        profile.user.id = String::from("1");
+        profile.user.username = String::from("Suisei");
+        SqliteStore::new(&conn).write_profile(&profile, &TaskType::Initializing).unwrap();
+        assert!(!FetchCheckpoint::interrupted(&conn, &usernames).unwrap());
+        assert!(FetchCheckpoint::interrupted(&conn, &[String::from("suisei"), String::from("towa")]).unwrap());
+
+        // completed endpoints are not requested again
+        let fetcher = TestFetcher { base_url: "http://127.0.0.1:1".to_string() };
+        assert!(fetch_resumable(&conf, &writer, &StopSignal::new(), FetchCheckpoint::get_record(&conn, "1", "following").unwrap(), &fetcher, write_users).unwrap().completed);
+
+        drop(writer);
+        writer_handle.join().unwrap();
+        drop(conn);
+        test_support::remove_db(&db_path);
+    }
+}
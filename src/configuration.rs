@@ -1,11 +1,17 @@
-use std::{fs, error::Error};
+use std::{fs, error::Error, collections::HashMap, time::Duration};
 use clap::{Parser, ValueEnum};
 use serde::{Serialize, Deserialize};
 
+use crate::errors::InvalidConfigOption;
+use crate::transport::Transport;
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 pub enum TaskType {
     Initializing, 
-    Monitoring
+    Monitoring, 
+    /// Rebuild the database from the raw response archive
+    #[value(alias = "reprocess")]
+    Reprocessing
 }
 
 /// `FileConfig` structure saving configurations from config file
@@ -14,6 +20,231 @@ struct FileConfig {
     bearer_token: String,
     monitoring_username: Vec<String>, 
     db_path: String,
+    #[serde(default)]
+    media_archive_dir: Option<String>,
+    #[serde(default)]
+    raw_archive_dir: Option<String>,
+    #[serde(default)]
+    saved_searches: Vec<SavedSearch>,
+    #[serde(default)]
+    ingestion: IngestionMode,
+    #[serde(default)]
+    cassette: Option<CassetteConfig>,
+    #[serde(default)]
+    polling: FilePolling,
+}
+
+/// `polling` section: overrides of the built-in intervals for every account, then for single
+/// accounts
+#[derive(Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilePolling {
+    #[serde(default)]
+    defaults: PollingOverrides,
+    /// Keyed by monitored username
+    #[serde(default)]
+    accounts: HashMap<String, PollingOverrides>,
+    /// Seconds between placeholder repairs
+    #[serde(default)]
+    repair: Option<u64>,
+    /// Seconds between saved search captures
+    #[serde(default)]
+    search: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PollingOverrides {
+    #[serde(default)]
+    profile: Option<StreamOverride>,
+    #[serde(default)]
+    tweets: Option<StreamOverride>,
+    #[serde(default)]
+    likes: Option<StreamOverride>,
+    #[serde(default)]
+    mentions: Option<StreamOverride>,
+    #[serde(default)]
+    following: Option<StreamOverride>,
+    #[serde(default)]
+    engagement: Option<StreamOverride>,
+}
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StreamOverride {
+    /// Seconds between two polls
+    #[serde(default)]
+    interval: Option<u64>,
+    /// Seconds the interval may shrink to while the account is active
+    #[serde(default)]
+    min_interval: Option<u64>,
+    /// Seconds the interval may grow to while the account is idle
+    #[serde(default)]
+    max_interval: Option<u64>,
+    #[serde(default)]
+    enabled: Option<bool>,
+    /// Newest tweets of the account one engagement poll covers
+    #[serde(default)]
+    recent_tweets: Option<u16>,
+    /// Requests one engagement poll may send to each endpoint
+    #[serde(default)]
+    request_budget: Option<u16>,
+}
+
+/// How one stream of an account is polled
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StreamPolling {
+    /// Neither fetched when initializing nor polled when monitoring when `false`, e.g. likes of
+    /// an account with hidden likes. The profile is still fetched once when initializing.
+    pub enabled: bool,
+    /// Interval at the account's average activity
+    pub interval: Duration,
+    /// Bounds the interval adapts to the account's activity within; it stays fixed without them
+    pub min_interval: Option<Duration>,
+    pub max_interval: Option<Duration>,
+    /// Newest tweets of the account one poll covers, 0 for streams not polled per tweet
+    pub recent_tweets: u16,
+    /// Requests one poll may send to each endpoint, 0 for streams not polled per tweet
+    pub request_budget: u16
+}
+
+impl StreamPolling {
+    fn every(secs: u64) -> StreamPolling {
+        StreamPolling {
+            enabled: true,
+            interval: Duration::from_secs(secs),
+            min_interval: None,
+            max_interval: None,
+            recent_tweets: 0,
+            request_budget: 0
+        }
+    }
+
+    fn apply(&mut self, stream_override: Option<StreamOverride>) {
+        if let Some(stream_override) = stream_override {
+            self.enabled = stream_override.enabled.unwrap_or(self.enabled);
+            self.interval = stream_override.interval.map(Duration::from_secs).unwrap_or(self.interval);
+            self.min_interval = stream_override.min_interval.map(Duration::from_secs).or(self.min_interval);
+            self.max_interval = stream_override.max_interval.map(Duration::from_secs).or(self.max_interval);
+            self.recent_tweets = stream_override.recent_tweets.unwrap_or(self.recent_tweets);
+            self.request_budget = stream_override.request_budget.unwrap_or(self.request_budget);
+        }
+    }
+
+    /// Shortest and longest interval, the interval itself for missing bounds
+    pub fn bounds(&self) -> (Duration, Duration) {
+        (self.min_interval.unwrap_or(self.interval), self.max_interval.unwrap_or(self.interval))
+    }
+}
+
+/// How every stream of one monitored account is polled
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AccountPolling {
+    pub profile: StreamPolling,
+    pub tweets: StreamPolling,
+    pub likes: StreamPolling,
+    pub mentions: StreamPolling,
+    pub following: StreamPolling,
+    pub engagement: StreamPolling
+}
+
+impl Default for AccountPolling {
+    fn default() -> Self {
+        AccountPolling {
+            profile: StreamPolling::every(120),
+            tweets: StreamPolling::every(60),
+            likes: StreamPolling::every(60),
+            mentions: StreamPolling::every(60),
+            following: StreamPolling::every(180),
+            // 60 of each endpoint's 75 requests per 15 minutes
+            engagement: StreamPolling { recent_tweets: 50, request_budget: 60, ..StreamPolling::every(900) }
+        }
+    }
+}
+
+impl AccountPolling {
+    fn streams(&self) -> [(&'static str, StreamPolling); 6] {
+        [
+            ("profile", self.profile), ("tweets", self.tweets), ("likes", self.likes),
+            ("mentions", self.mentions), ("following", self.following), ("engagement", self.engagement)
+        ]
+    }
+
+    fn apply(mut self, overrides: &PollingOverrides) -> AccountPolling {
+        self.profile.apply(overrides.profile);
+        self.tweets.apply(overrides.tweets);
+        self.likes.apply(overrides.likes);
+        self.mentions.apply(overrides.mentions);
+        self.following.apply(overrides.following);
+        self.engagement.apply(overrides.engagement);
+        self
+    }
+}
+
+/// Polling intervals of every monitored account and of the tasks shared by all of them
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Polling {
+    /// Polling of accounts without overrides of their own
+    pub defaults: AccountPolling,
+    /// Polling of accounts with overrides, keyed by username as listed in `monitoring_username`
+    pub accounts: HashMap<String, AccountPolling>,
+    pub repair: Duration,
+    pub search: Duration
+}
+
+impl Default for Polling {
+    fn default() -> Self {
+        Polling {
+            defaults: AccountPolling::default(),
+            accounts: HashMap::new(),
+            repair: Duration::from_secs(900),
+            search: Duration::from_secs(60)
+        }
+    }
+}
+
+impl Polling {
+    /// How `username` is polled
+    pub fn account(&self, username: &str) -> &AccountPolling {
+        self.accounts.get(username).unwrap_or(&self.defaults)
+    }
+}
+
+/// How new tweets of the monitored users are ingested
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestionMode {
+    /// Fetch the timeline at the tweets polling interval
+    #[default]
+    Polling,
+    /// Follow the filtered stream, polling only to catch up after reconnecting or while the
+    /// stream is unavailable
+    Stream
+}
+
+/// A recent search query followed alongside the monitored users, e.g. a fan-art hashtag
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    /// Unique name the hits are stored under
+    pub name: String,
+    /// Query in the search endpoint's syntax, e.g. `#suiseiart -is:retweet`
+    pub query: String
+}
+
+/// A cassette the API requests are recorded to or replayed from
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CassetteConfig {
+    pub path: String,
+    pub mode: CassetteMode
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    /// Send requests over HTTP and record them with their responses
+    Record,
+    /// Serve recorded responses without network access
+    Replay
 }
 
 /// `Config` structure saving configurations required for running
@@ -23,6 +254,17 @@ pub struct Config {
     pub bearer_token: String,
     pub monitoring_username: Vec<String>, 
     pub db_path: String,
+    /// Directory tweet media and changed profile images are downloaded to; nothing is archived
+    /// when absent
+    pub media_archive_dir: Option<String>,
+    /// Directory every raw API response is kept in, compressed, for reprocessing; nothing is
+    /// kept when absent
+    pub raw_archive_dir: Option<String>,
+    pub saved_searches: Vec<SavedSearch>,
+    pub ingestion: IngestionMode,
+    /// How the fetchers send their requests, over HTTP unless a cassette is configured
+    pub transport: Transport,
+    pub polling: Polling,
     pub verbose: bool, 
     pub task_type: TaskType
 }
@@ -37,25 +279,206 @@ impl Config {
     /// # Errors
     /// * [`std::io::Error`]
     /// * `serde_yaml::Error`
-    /// * `sui_twitter_monitor::OptionError`: invalid option entry(ies)
+    /// * [`InvalidConfigOption`]: invalid option entry(ies)
     pub fn configure(
         conf_path: &str, 
         verbose: bool, 
         task_type: &TaskType,
+    ) -> Result<Config, Box<dyn Error>> {
+        Config::read(conf_path, verbose, task_type, None)
+    }
+
+    /// Reads the config file again, e.g. after it was edited while monitoring. The database and
+    /// the transport stay the ones the process started with, changing them takes a restart.
+    pub fn reload(&self) -> Result<Config, Box<dyn Error>> {
+        let mut reloaded = Config::read(&self.conf_path, self.verbose, &self.task_type, Some(&self.transport))?;
+        if reloaded.db_path != self.db_path {
+            log::warn!("db_path changed to {}, which takes a restart; keeping {}", &reloaded.db_path, &self.db_path);
+            reloaded.db_path = self.db_path.clone();
+        }
+        Ok(reloaded)
+    }
+
+    /// What `reloaded` changes for the running monitor. Only the workers using a changed option
+    /// restart.
+    pub fn diff(&self, reloaded: &Config) -> ConfigChange {
+        let added: Vec<String> = reloaded.monitoring_username.iter()
+            .filter(|username| !self.monitoring_username.contains(username))
+            .cloned()
+            .collect();
+        let removed: Vec<String> = self.monitoring_username.iter()
+            .filter(|username| !reloaded.monitoring_username.contains(username))
+            .cloned()
+            .collect();
+
+        // every fetching worker sends the token and archives the responses
+        let fetching_changed = self.bearer_token != reloaded.bearer_token || self.raw_archive_dir != reloaded.raw_archive_dir;
+        // tweets and profile images are archived, and tweets polled unless they are streamed
+        let archiving_changed = fetching_changed || self.media_archive_dir != reloaded.media_archive_dir;
+        let accounts_changed = archiving_changed
+            || self.ingestion != reloaded.ingestion
+            // the accounts share the rate limits
+            || self.monitoring_username.len() != reloaded.monitoring_username.len();
+        let restarted = self.monitoring_username.iter()
+            .filter(|username| reloaded.monitoring_username.contains(username))
+            .filter(|username| accounts_changed || self.polling.account(username) != reloaded.polling.account(username))
+            .cloned()
+            .collect();
+
+        let mut shared_restarted: Vec<SharedWorker> = Vec::new();
+        if fetching_changed || self.polling.repair != reloaded.polling.repair {
+            shared_restarted.push(SharedWorker::Repair);
+        }
+        if fetching_changed || self.saved_searches != reloaded.saved_searches || self.polling.search != reloaded.polling.search {
+            shared_restarted.push(SharedWorker::Search);
+        }
+        // the filtered stream follows every account with tweets enabled and polls them meanwhile
+        let streamed = |conf: &Config| -> Vec<(String, StreamPolling)> {
+            conf.monitoring_username.iter().map(|username| (username.clone(), conf.polling.account(username).tweets)).collect()
+        };
+        if self.ingestion != reloaded.ingestion
+            || (reloaded.ingestion == IngestionMode::Stream && (archiving_changed || streamed(self) != streamed(reloaded))) {
+            shared_restarted.push(SharedWorker::Stream);
+        }
+
+        ConfigChange {
+            added,
+            removed,
+            restarted,
+            shared_restarted
+        }
+    }
+
+    fn read(
+        conf_path: &str, 
+        verbose: bool, 
+        task_type: &TaskType,
+        transport: Option<&Transport>,
     ) -> Result<Config, Box<dyn Error>> {
         let conf_yaml_str = fs::read_to_string(conf_path)?;
         let conf_file_options: FileConfig = serde_yaml::from_str(&conf_yaml_str)?;
+        validate_saved_searches(&conf_file_options.saved_searches)?;
+        let polling = resolve_polling(&conf_file_options.polling, &conf_file_options.monitoring_username)?;
+        let transport = match (transport, &conf_file_options.cassette) {
+            (Some(transport), _) => transport.clone(),
+            (None, None) => Transport::Http,
+            (None, Some(CassetteConfig { path, mode: CassetteMode::Record })) => Transport::record(path)?,
+            (None, Some(CassetteConfig { path, mode: CassetteMode::Replay })) => Transport::replay(path)?
+        };
         Ok(Config {
             conf_path: String::from(conf_path), 
             bearer_token: conf_file_options.bearer_token,
             monitoring_username: conf_file_options.monitoring_username, 
             db_path: conf_file_options.db_path,
-            verbose: verbose,
-            task_type: task_type.clone()
+            media_archive_dir: conf_file_options.media_archive_dir,
+            raw_archive_dir: conf_file_options.raw_archive_dir,
+            saved_searches: conf_file_options.saved_searches,
+            ingestion: conf_file_options.ingestion,
+            transport,
+            polling,
+            verbose,
+            task_type: *task_type
         })
     }
 }
 
+/// Workers to stop and start after the config file was reloaded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// Accounts to start monitoring
+    pub added: Vec<String>,
+    /// Accounts to stop monitoring
+    pub removed: Vec<String>,
+    /// Accounts still monitored whose workers restart with the reloaded config
+    pub restarted: Vec<String>,
+    /// Workers shared by every account that restart with the reloaded config
+    pub shared_restarted: Vec<SharedWorker>
+}
+
+impl ConfigChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.restarted.is_empty() && self.shared_restarted.is_empty()
+    }
+}
+
+/// Workers shared by every monitored account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SharedWorker {
+    /// Looks placeholder tweets and users up again
+    Repair,
+    /// Captures the saved searches
+    Search,
+    /// Follows the filtered stream when tweets are streamed
+    Stream
+}
+
+impl SharedWorker {
+    pub const ALL: [SharedWorker; 3] = [SharedWorker::Repair, SharedWorker::Search, SharedWorker::Stream];
+
+    /// Name of the worker's thread
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SharedWorker::Repair => "repair",
+            SharedWorker::Search => "search",
+            SharedWorker::Stream => "stream"
+        }
+    }
+}
+
+/// Saved searches need a query and a name no other search has
+fn validate_saved_searches(saved_searches: &[SavedSearch]) -> Result<(), InvalidConfigOption> {
+    for (search_index, saved_search) in saved_searches.iter().enumerate() {
+        if saved_search.name.trim().is_empty()
+            || saved_searches[..search_index].iter().any(|other_search| other_search.name == saved_search.name) {
+            return Err(InvalidConfigOption::new("saved_searches.name"));
+        }
+        if saved_search.query.trim().is_empty() {
+            return Err(InvalidConfigOption::new("saved_searches.query"));
+        }
+    }
+    Ok(())
+}
+
+/// Applies the polling overrides to the built-in intervals. Overrides must be for monitored
+/// accounts, intervals at least a second long and within their bounds.
+fn resolve_polling(file_polling: &FilePolling, monitoring_username: &[String]) -> Result<Polling, InvalidConfigOption> {
+    if file_polling.repair == Some(0) {
+        return Err(InvalidConfigOption::new("polling.repair"));
+    }
+    if file_polling.search == Some(0) {
+        return Err(InvalidConfigOption::new("polling.search"));
+    }
+
+    let mut polling = Polling::default();
+    polling.defaults = polling.defaults.apply(&file_polling.defaults);
+    for (username, overrides) in file_polling.accounts.iter() {
+        if !monitoring_username.contains(username) {
+            return Err(InvalidConfigOption::new(&format!("polling.accounts.{username}")));
+        }
+        polling.accounts.insert(username.clone(), polling.defaults.apply(overrides));
+    }
+    for (option, account_polling) in std::iter::once(("defaults", &polling.defaults))
+        .chain(polling.accounts.iter().map(|(username, account_polling)| (username.as_str(), account_polling))) {
+        for (stream, stream_polling) in account_polling.streams() {
+            let (min_interval, max_interval) = stream_polling.bounds();
+            if min_interval.is_zero() || min_interval > stream_polling.interval || stream_polling.interval > max_interval {
+                return Err(InvalidConfigOption::new(&format!("polling.{option}.{stream}.interval")));
+            }
+            // only engagement is polled per tweet
+            let per_tweet = stream == "engagement";
+            if (stream_polling.recent_tweets == 0) == per_tweet {
+                return Err(InvalidConfigOption::new(&format!("polling.{option}.{stream}.recent_tweets")));
+            }
+            if (stream_polling.request_budget == 0) == per_tweet {
+                return Err(InvalidConfigOption::new(&format!("polling.{option}.{stream}.request_budget")));
+            }
+        }
+    }
+    polling.repair = file_polling.repair.map(Duration::from_secs).unwrap_or(polling.repair);
+    polling.search = file_polling.search.map(Duration::from_secs).unwrap_or(polling.search);
+    Ok(polling)
+}
+
 /// Following a twitter user's activities
 #[derive(Debug, Parser)]
 pub struct Args {
@@ -86,11 +509,133 @@ mod tests {
             conf_path: String::from(conf_path), 
             bearer_token: String::from("aaaabbbb"),
             db_path: String::from("./sui.db"), 
+            media_archive_dir: None,
+            raw_archive_dir: None,
+            saved_searches: vec![SavedSearch { name: String::from("art"), query: String::from("#suiseiart -is:retweet") }],
+            ingestion: IngestionMode::Stream,
+            transport: Transport::Http,
+            polling: Polling {
+                accounts: HashMap::from([(String::from("@miko"), AccountPolling {
+                    likes: StreamPolling { enabled: false, ..StreamPolling::every(90) },
+                    tweets: StreamPolling::every(30),
+                    ..AccountPolling::default()
+                })]),
+                defaults: AccountPolling { likes: StreamPolling::every(90), ..AccountPolling::default() },
+                search: Duration::from_secs(300),
+                ..Polling::default()
+            },
             monitoring_username: vec![String::from("@suisei"), String::from("@miko")],
-            verbose: verbose,
+            verbose,
             task_type: TaskType::Monitoring
         };
 
         assert_eq!(target_conf, Config::configure("test_conf.yaml", verbose, &TaskType::Monitoring).unwrap());
     }
+
+    #[test]
+    fn test_validate_saved_searches() {
+        let saved_search = |name: &str, query: &str| SavedSearch { name: name.to_string(), query: query.to_string() };
+
+        assert!(validate_saved_searches(&[saved_search("art", "#suiseiart"), saved_search("clips", "#suiseiclips")]).is_ok());
+        assert!(validate_saved_searches(&[saved_search("art", "#suiseiart"), saved_search("art", "#mikoart")]).is_err());
+        assert!(validate_saved_searches(&[saved_search("", "#suiseiart")]).is_err());
+        assert!(validate_saved_searches(&[saved_search("art", " ")]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_polling() {
+        let monitoring_username = vec![String::from("@suisei"), String::from("@miko")];
+        let file_polling = |yaml: &str| serde_yaml::from_str::<FilePolling>(yaml).unwrap();
+
+        let polling = resolve_polling(&FilePolling::default(), &monitoring_username).unwrap();
+        assert_eq!(polling, Polling::default());
+        assert_eq!(polling.account("@miko").following.interval, Duration::from_secs(180));
+
+        // account overrides apply over the defaults
+        let polling = resolve_polling(&file_polling("
+            defaults: { profile: { interval: 600 } }
+            accounts: { '@miko': { likes: { enabled: false } } }
+        "), &monitoring_username).unwrap();
+        assert_eq!(polling.account("@suisei").profile, StreamPolling::every(600));
+        assert!(polling.account("@suisei").likes.enabled);
+        assert_eq!(polling.account("@miko").profile, StreamPolling::every(600));
+        assert_eq!(polling.account("@miko").likes, StreamPolling { enabled: false, ..StreamPolling::every(60) });
+
+        assert!(resolve_polling(&file_polling("accounts: { '@pekora': { likes: { enabled: false } } }"), &monitoring_username).is_err());
+        assert!(resolve_polling(&file_polling("defaults: { tweets: { interval: 0 } }"), &monitoring_username).is_err());
+        assert!(resolve_polling(&file_polling("accounts: { '@miko': { tweets: { interval: 0 } } }"), &monitoring_username).is_err());
+        assert!(resolve_polling(&file_polling("search: 0"), &monitoring_username).is_err());
+
+        let polling = resolve_polling(&file_polling("defaults: { tweets: { min_interval: 15, max_interval: 600 } }"), &monitoring_username).unwrap();
+        assert_eq!(polling.account("@miko").tweets.bounds(), (Duration::from_secs(15), Duration::from_secs(600)));
+        assert_eq!(polling.account("@miko").likes.bounds(), (Duration::from_secs(60), Duration::from_secs(60)));
+        assert!(resolve_polling(&file_polling("defaults: { tweets: { min_interval: 90 } }"), &monitoring_username).is_err());
+        assert!(resolve_polling(&file_polling("accounts: { '@miko': { tweets: { max_interval: 30 } } }"), &monitoring_username).is_err());
+        assert!(resolve_polling(&file_polling("defaults: { tweets: { min_interval: 0 } }"), &monitoring_username).is_err());
+        assert!(serde_yaml::from_str::<FilePolling>("defaults: { tweet: { interval: 30 } }").is_err());
+
+        let polling = resolve_polling(&file_polling("accounts: { '@miko': { engagement: { recent_tweets: 20, request_budget: 30 } } }"), &monitoring_username).unwrap();
+        assert_eq!((polling.account("@miko").engagement.recent_tweets, polling.account("@miko").engagement.request_budget), (20, 30));
+        assert_eq!((polling.account("@suisei").engagement.recent_tweets, polling.account("@suisei").engagement.request_budget), (50, 60));
+        assert!(resolve_polling(&file_polling("defaults: { engagement: { request_budget: 0 } }"), &monitoring_username).is_err());
+        assert!(resolve_polling(&file_polling("defaults: { likes: { recent_tweets: 20 } }"), &monitoring_username).is_err());
+    }
+
+    #[test]
+    fn test_reload_diff() {
+        let conf_path = std::env::temp_dir().join(format!("sui_reload_{}.yaml", std::process::id()));
+        let conf_path = conf_path.to_str().unwrap();
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']").unwrap();
+        let config = Config::configure(conf_path, false, &TaskType::Monitoring).unwrap();
+        assert!(config.diff(&config.reload().unwrap()).is_empty());
+
+        // an account swapped for another, the database kept
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./other.db\nmonitoring_username: ['@suisei', '@pekora']").unwrap();
+        let reloaded = config.reload().unwrap();
+        assert_eq!(reloaded.db_path, "./sui.db");
+        assert_eq!(config.diff(&reloaded), ConfigChange {
+            added: vec![String::from("@pekora")],
+            removed: vec![String::from("@miko")],
+            restarted: Vec::new(),
+            shared_restarted: Vec::new()
+        });
+
+        // polling of one account
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']\npolling: { accounts: { '@miko': { likes: { enabled: false } } } }").unwrap();
+        assert_eq!(config.diff(&config.reload().unwrap()), ConfigChange { restarted: vec![String::from("@miko")], ..ConfigChange::default() });
+
+        // a third account shrinks the rate limit budget of the others
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko', '@pekora']").unwrap();
+        let change = config.diff(&config.reload().unwrap());
+        assert_eq!(change.added, vec![String::from("@pekora")]);
+        assert_eq!(change.restarted, vec![String::from("@suisei"), String::from("@miko")]);
+
+        // every fetching worker uses the token, the stream only runs when tweets are streamed
+        fs::write(conf_path, "bearer_token: ccccdddd\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']").unwrap();
+        let change = config.diff(&config.reload().unwrap());
+        assert_eq!(change.restarted.len(), 2);
+        assert_eq!(change.shared_restarted, vec![SharedWorker::Repair, SharedWorker::Search]);
+
+        // options of a single shared worker
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']\nsaved_searches: [{ name: towa, query: 'from:tokoyami_towa' }]").unwrap();
+        assert_eq!(config.diff(&config.reload().unwrap()), ConfigChange { shared_restarted: vec![SharedWorker::Search], ..ConfigChange::default() });
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']\npolling: { repair: 600 }").unwrap();
+        assert_eq!(config.diff(&config.reload().unwrap()), ConfigChange { shared_restarted: vec![SharedWorker::Repair], ..ConfigChange::default() });
+
+        // streaming moves the tweets of every account to the stream
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']\ningestion: stream").unwrap();
+        let streaming = config.reload().unwrap();
+        let change = config.diff(&streaming);
+        assert_eq!((change.restarted.len(), change.shared_restarted), (2, vec![SharedWorker::Stream]));
+        fs::write(conf_path, "bearer_token: aaaabbbb\ndb_path: ./sui.db\nmonitoring_username: ['@suisei', '@miko']\ningestion: stream\npolling: { accounts: { '@miko': { tweets: { enabled: false } } } }").unwrap();
+        assert_eq!(streaming.diff(&streaming.reload().unwrap()), ConfigChange {
+            restarted: vec![String::from("@miko")],
+            shared_restarted: vec![SharedWorker::Stream],
+            ..ConfigChange::default()
+        });
+
+        fs::write(conf_path, "bearer_token: [").unwrap();
+        assert!(config.reload().is_err());
+        fs::remove_file(conf_path).unwrap();
+    }
 }
\ No newline at end of file
@@ -1,17 +1,139 @@
-use rusqlite::Connection;
+use std::time::Duration;
+
+use rusqlite::{named_params, Connection, Transaction, TransactionBehavior};
+
+/// Version of the schema [`init_db`] creates, kept in `PRAGMA user_version` so [`migrate`] knows
+/// which columns a database initialized by an older version lacks
+pub const SCHEMA_VERSION: i32 = 4;
+
+/// How long a connection waits for the lock held by another one before failing with
+/// `SQLITE_BUSY`
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Opens the database at `db_path` in WAL mode, where readers and the writer do not block each
+/// other, waiting up to [`BUSY_TIMEOUT`] for locks
+pub fn open(db_path: &str) -> Result<Connection, rusqlite::Error> {
+    let conn = Connection::open(db_path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    let journal_mode: String = conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        // in-memory databases have no write-ahead log
+        log::debug!("{} stays in {} journal mode", db_path, journal_mode);
+    }
+    // WAL commits stay durable across application crashes without syncing every transaction
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// Brings a database initialized by an older version up to [`SCHEMA_VERSION`] in one transaction,
+/// adding the columns of every later version it lacks. Uninitialized databases are left to
+/// [`init_db`].
+pub fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
+    // taking the write lock first keeps connections opened at the same time from migrating twice
+    let transaction = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let version: i32 = transaction.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version >= SCHEMA_VERSION || !has_table(&transaction, "user_tweet")? {
+        return Ok(());
+    }
+    if version < 1 {
+        // conversations
+        add_column(&transaction, "user_tweet", "conversation_id", "TEXT")?;
+        add_column(&transaction, "user_tweet", "in_reply_to_user_id", "TEXT")?;
+        add_column(&transaction, "user_tweet", "replied_to_id", "TEXT")?;
+        add_column(&transaction, "tweet_dict", "conversation_id", "TEXT")?;
+        add_column(&transaction, "tweet_dict", "replied_to_id", "TEXT")?;
+        transaction.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_user_tweet_conversation_id ON user_tweet (conversation_id);
+            CREATE INDEX IF NOT EXISTS idx_tweet_dict_conversation_id ON tweet_dict (conversation_id);"
+        )?;
+    }
+    if version < 2 {
+        // placeholder availability
+        for table in ["user_dict", "tweet_dict"] {
+            add_column(&transaction, table, "availability", "TEXT NOT NULL DEFAULT 'available'")?;
+            add_column(&transaction, table, "unavailable_reason", "TEXT")?;
+            add_column(&transaction, table, "checked_at", "TEXT")?;
+        }
+        transaction.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_tweet_dict_availability ON tweet_dict (availability);
+            CREATE INDEX IF NOT EXISTS idx_user_dict_availability ON user_dict (availability);"
+        )?;
+    }
+    if version < 3 {
+        // likes and follows were ordered by insertion before they had a fetch order
+        for table in ["user_liked", "user_following", "user_current_following"] {
+            if add_column(&transaction, table, "fetch_order", "INTEGER NOT NULL DEFAULT 0")? {
+                transaction.execute_batch(&format!(
+                    "UPDATE {table} SET fetch_order = id;
+                    DROP INDEX IF EXISTS idx_{table}_user_id;
+                    CREATE INDEX idx_{table}_user_id ON {table} (user_id, fetch_order);"
+                ))?;
+            }
+        }
+    }
+    if version < 4 && has_table(&transaction, "search_hit")? {
+        // saved search hits were named like the full-text search results
+        transaction.execute_batch("ALTER TABLE search_hit RENAME TO saved_search_hit;")?;
+    }
+    transaction.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    transaction.commit()
+}
+
+fn has_table(conn: &Connection, table: &str) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = :table",
+        named_params! { ":table": table },
+        |row| row.get(0)
+    )
+}
+
+/// Adds `column` to `table` unless a version in between already added it, returning whether it
+/// was added
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool, rusqlite::Error> {
+    let has_column: bool = conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{table}') WHERE name = :column"),
+        named_params! { ":column": column },
+        |row| row.get(0)
+    )?;
+    if !has_column {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition};"))?;
+    }
+    Ok(!has_column)
+}
 
 pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
+    init_tables(conn)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+}
+
+fn init_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
     conn.execute_batch(
         "BEGIN;
         DROP TABLE IF EXISTS user_profile;
+        DROP TABLE IF EXISTS profile_change;
         DROP TABLE IF EXISTS user_dict;
         DROP TABLE IF EXISTS tweet_dict;
         DROP TABLE IF EXISTS user_tweet;
         DROP TABLE IF EXISTS user_liked;
+        DROP TABLE IF EXISTS user_mentioned;
+        DROP TABLE IF EXISTS tweet_engagement;
+        DROP TABLE IF EXISTS engagement_poll;
+        DROP TABLE IF EXISTS search_hit;
+        DROP TABLE IF EXISTS saved_search_hit;
+        DROP TABLE IF EXISTS fetch_checkpoint;
+        DROP TABLE IF EXISTS monitor_session;
         DROP TABLE IF EXISTS user_following;
+        DROP TABLE IF EXISTS user_current_following;
         DROP TABLE IF EXISTS user_unfollowed;
         DROP TABLE IF EXISTS hashtag_dict;
         DROP TABLE IF EXISTS mention_dict;
+        DROP TABLE IF EXISTS media_dict;
+        DROP TABLE IF EXISTS url_dict;
+        DROP TABLE IF EXISTS stream_announcement;
+        DROP TABLE IF EXISTS tweet_version;
+        DROP TABLE IF EXISTS user_tweet_fts;
+        DROP TABLE IF EXISTS tweet_dict_fts;
         
         CREATE TABLE user_profile (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
@@ -20,7 +142,26 @@ pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
             username TEXT NOT NULL, 
             name TEXT NOT NULL, 
             location TEXT, 
-            description TEXT
+            description TEXT, 
+            url TEXT, 
+            profile_image_url TEXT, 
+            pinned_tweet_id TEXT, 
+            protected INTEGER, 
+            verified INTEGER, 
+            profile_banner_url TEXT
+        );
+
+        CREATE TABLE profile_change (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            time TEXT, 
+            user_id TEXT NOT NULL, 
+            field TEXT NOT NULL, 
+            old_value TEXT, 
+            new_value TEXT, 
+            old_content_hash TEXT, 
+            old_local_path TEXT, 
+            new_content_hash TEXT, 
+            new_local_path TEXT
         );
 
         CREATE TABLE user_tweet (
@@ -30,7 +171,73 @@ pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
             time TEXT NOT NULL,
             author_id TEXT NOT NULL, 
             tweet_type TEXT NOT NULL, 
-            ref_tweet_id TEXT
+            ref_tweet_id TEXT, 
+            conversation_id TEXT, 
+            in_reply_to_user_id TEXT, 
+            replied_to_id TEXT, 
+            is_note_tweet INTEGER NOT NULL DEFAULT 0, 
+            edits_remaining INTEGER, 
+            is_edit_eligible INTEGER, 
+            editable_until TEXT
+        );
+
+        CREATE TABLE tweet_version (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            original_tweet_id TEXT NOT NULL, 
+            tweet_id TEXT NOT NULL UNIQUE, 
+            text TEXT
+        );
+
+        CREATE TABLE user_mentioned (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            user_id TEXT NOT NULL, 
+            tweet_id TEXT NOT NULL, 
+            time TEXT NOT NULL, 
+            author_id TEXT NOT NULL, 
+            tweet_type TEXT NOT NULL, 
+            ref_tweet_id TEXT, 
+            UNIQUE(user_id, tweet_id)
+        );
+
+        CREATE TABLE tweet_engagement (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            tweet_id TEXT NOT NULL, 
+            user_id TEXT NOT NULL, 
+            kind TEXT NOT NULL, 
+            quote_tweet_id TEXT, 
+            time TEXT NOT NULL
+        );
+
+        CREATE TABLE engagement_poll (
+            tweet_id TEXT NOT NULL PRIMARY KEY, 
+            polled_at TEXT NOT NULL
+        );
+
+        CREATE TABLE saved_search_hit (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            search_name TEXT NOT NULL, 
+            tweet_id TEXT NOT NULL, 
+            time TEXT NOT NULL, 
+            author_id TEXT NOT NULL, 
+            UNIQUE(search_name, tweet_id)
+        );
+
+        CREATE TABLE fetch_checkpoint (
+            user_id TEXT NOT NULL, 
+            endpoint TEXT NOT NULL, 
+            page_token TEXT, 
+            pages INTEGER NOT NULL DEFAULT 0, 
+            records INTEGER NOT NULL DEFAULT 0, 
+            completed INTEGER NOT NULL DEFAULT 0, 
+            updated_at TEXT NOT NULL, 
+            PRIMARY KEY (user_id, endpoint)
+        );
+
+        CREATE TABLE monitor_session (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            started_at TEXT NOT NULL, 
+            stopped_at TEXT, 
+            clean_shutdown INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE user_liked (
@@ -38,7 +245,8 @@ pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
             time TEXT, 
             user_id TEXT NOT NULL,
             author_id TEXT NOT NULL,
-            ref_tweet_id TEXT NOT NULL
+            ref_tweet_id TEXT NOT NULL,
+            fetch_order INTEGER NOT NULL
         ); 
 
         CREATE TABLE user_following (
@@ -46,7 +254,8 @@ pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
             time TEXT, 
             user_id TEXT NOT NULL, 
             following_user_id TEXT NOT NULL, 
-            action TEXT NOT NULL
+            action TEXT NOT NULL, 
+            fetch_order INTEGER NOT NULL
         ); 
 
         CREATE TABLE user_current_following (
@@ -54,7 +263,8 @@ pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
             time TEXT, 
             user_id TEXT NOT NULL, 
             following_user_id TEXT NOT NULL, 
-            action TEXT NOT NULL
+            action TEXT NOT NULL, 
+            fetch_order INTEGER NOT NULL
         );
 
         CREATE TABLE hashtag_dict (
@@ -69,27 +279,135 @@ pub fn init_db(conn: &Connection) -> Result<(), rusqlite::Error> {
             tweet_id TEXT NOT NULL
         );
 
+        CREATE TABLE media_dict (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            media_key TEXT NOT NULL, 
+            tweet_id TEXT NOT NULL, 
+            media_type TEXT NOT NULL, 
+            url TEXT, 
+            preview_image_url TEXT, 
+            width INTEGER, 
+            height INTEGER, 
+            alt_text TEXT, 
+            duration_ms INTEGER, 
+            content_hash TEXT, 
+            local_path TEXT
+        );
+
+        CREATE TABLE url_dict (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            tweet_id TEXT NOT NULL, 
+            url TEXT NOT NULL, 
+            expanded_url TEXT, 
+            display_url TEXT, 
+            unwound_url TEXT, 
+            domain TEXT, 
+            title TEXT
+        );
+
+        CREATE TABLE stream_announcement (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+            tweet_id TEXT NOT NULL, 
+            author_id TEXT NOT NULL, 
+            platform TEXT NOT NULL, 
+            stream_id TEXT NOT NULL, 
+            url TEXT NOT NULL, 
+            kind TEXT NOT NULL, 
+            tweet_time TEXT NOT NULL, 
+            scheduled_at TEXT, 
+            UNIQUE (tweet_id, platform, stream_id)
+        );
+
         CREATE TABLE user_dict (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
             user_id TEXT NOT NULL UNIQUE, 
             username TEXT NOT NULL, 
-            name TEXT NOT NULL
+            name TEXT NOT NULL, 
+            availability TEXT NOT NULL DEFAULT 'available', 
+            unavailable_reason TEXT, 
+            checked_at TEXT
         ); 
 
         CREATE TABLE tweet_dict (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
             tweet_id TEXT NOT NULL UNIQUE, 
             author_id TEXT NOT NULL, 
-            text TEXT NOT NULL
+            text TEXT NOT NULL, 
+            conversation_id TEXT, 
+            replied_to_id TEXT, 
+            availability TEXT NOT NULL DEFAULT 'available', 
+            unavailable_reason TEXT, 
+            checked_at TEXT
+        );
+
+        CREATE INDEX idx_user_profile_username ON user_profile (username);
+        CREATE INDEX idx_user_profile_user_id ON user_profile (user_id);
+        CREATE INDEX idx_profile_change_user_id ON profile_change (user_id, time);
+        CREATE INDEX idx_user_tweet_author_id ON user_tweet (author_id);
+        CREATE INDEX idx_user_tweet_conversation_id ON user_tweet (conversation_id);
+        CREATE INDEX idx_tweet_version_original_tweet_id ON tweet_version (original_tweet_id);
+        CREATE INDEX idx_tweet_dict_conversation_id ON tweet_dict (conversation_id);
+        CREATE INDEX idx_tweet_dict_availability ON tweet_dict (availability);
+        CREATE INDEX idx_user_dict_availability ON user_dict (availability);
+        CREATE INDEX idx_user_liked_user_id ON user_liked (user_id, fetch_order);
+        CREATE INDEX idx_user_mentioned_author_id ON user_mentioned (author_id);
+        CREATE UNIQUE INDEX idx_tweet_engagement_unique ON tweet_engagement (tweet_id, kind, user_id, COALESCE(quote_tweet_id, ''));
+        CREATE INDEX idx_tweet_engagement_user_id ON tweet_engagement (user_id);
+        CREATE INDEX idx_user_following_user_id ON user_following (user_id, fetch_order);
+        CREATE INDEX idx_user_current_following_user_id ON user_current_following (user_id, fetch_order);
+        CREATE INDEX idx_hashtag_dict_tweet_id ON hashtag_dict (tweet_id);
+        CREATE INDEX idx_mention_dict_tweet_id ON mention_dict (tweet_id);
+        CREATE INDEX idx_media_dict_tweet_id ON media_dict (tweet_id);
+        CREATE INDEX idx_media_dict_media_key ON media_dict (media_key);
+        CREATE INDEX idx_url_dict_tweet_id ON url_dict (tweet_id);
+        CREATE INDEX idx_url_dict_domain ON url_dict (domain);
+        CREATE INDEX idx_stream_announcement_author_id ON stream_announcement (author_id);
+
+        CREATE VIRTUAL TABLE user_tweet_fts USING fts5(
+            tweet_text, 
+            content = 'user_tweet', 
+            content_rowid = 'id', 
+            tokenize = 'trigram'
+        );
+
+        CREATE VIRTUAL TABLE tweet_dict_fts USING fts5(
+            text, 
+            content = 'tweet_dict', 
+            content_rowid = 'id', 
+            tokenize = 'trigram'
         );
         
         COMMIT;"
     )
 }
 
+/// Creates the full-text indexes if missing and rebuilds them from `user_tweet` and `tweet_dict`, 
+/// e.g. for a database initialized before the indexes existed. 
+pub fn rebuild_search_index(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "BEGIN;
+        CREATE VIRTUAL TABLE IF NOT EXISTS user_tweet_fts USING fts5(
+            tweet_text, 
+            content = 'user_tweet', 
+            content_rowid = 'id', 
+            tokenize = 'trigram'
+        );
+        CREATE VIRTUAL TABLE IF NOT EXISTS tweet_dict_fts USING fts5(
+            text, 
+            content = 'tweet_dict', 
+            content_rowid = 'id', 
+            tokenize = 'trigram'
+        );
+        INSERT INTO user_tweet_fts (user_tweet_fts) VALUES ('rebuild');
+        INSERT INTO tweet_dict_fts (tweet_dict_fts) VALUES ('rebuild');
+        COMMIT;"
+    )
+}
+
 #[cfg(test)]
 mod test {
     use crate::{query_result::{FetchedUser, UserDetail}, configuration::TaskType};
+    use crate::store::{SqliteStore, Store};
 
     use super::*;
 
@@ -106,10 +424,16 @@ mod test {
                 name: "zyxwvu".to_string(), 
                 description: None, 
                 location: None,
+                url: None, 
+                profile_image_url: None, 
+                profile_banner_url: None, 
+                pinned_tweet_id: None, 
+                protected: None, 
+                verified: None,
             }, 
         };
 
-        test_fetched_profile.write_to_db(&conn, &TaskType::Initializing).unwrap();
+        SqliteStore::new(&conn).write_profile(&test_fetched_profile, &TaskType::Initializing).unwrap();
 
         let test_fetched_profile_2 = FetchedUser {
             recorded_time: Some("1970-01-01T00:00.000Z".to_string()),
@@ -119,9 +443,118 @@ mod test {
             }
         };
 
-        test_fetched_profile_2.write_to_db(&conn, &TaskType::Initializing).unwrap();
+        SqliteStore::new(&conn).write_profile(&test_fetched_profile_2, &TaskType::Initializing).unwrap();
 
-        test_fetched_profile_2.write_to_db(&conn, &TaskType::Monitoring).unwrap();
+        SqliteStore::new(&conn).write_profile(&test_fetched_profile_2, &TaskType::Monitoring).unwrap();
         
     }
+
+    /// Columns of `table`
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(&format!("SELECT name FROM pragma_table_info('{table}')")).unwrap();
+        let columns = stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<Vec<String>, _>>().unwrap();
+        columns
+    }
+
+    #[test]
+    fn test_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        // uninitialized databases are left to init_db
+        migrate(&conn).unwrap();
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, 0);
+
+        // tables as older versions created them
+        conn.execute_batch(
+            "CREATE TABLE user_tweet (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                tweet_id TEXT NOT NULL UNIQUE, 
+                tweet_text TEXT NOT NULL,
+                time TEXT NOT NULL,
+                author_id TEXT NOT NULL, 
+                tweet_type TEXT NOT NULL, 
+                ref_tweet_id TEXT
+            );
+            CREATE TABLE user_liked (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                time TEXT, 
+                user_id TEXT NOT NULL,
+                author_id TEXT NOT NULL,
+                ref_tweet_id TEXT NOT NULL
+            );
+            CREATE TABLE user_following (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                time TEXT, 
+                user_id TEXT NOT NULL, 
+                following_user_id TEXT NOT NULL, 
+                action TEXT NOT NULL
+            );
+            CREATE TABLE user_current_following (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                time TEXT, 
+                user_id TEXT NOT NULL, 
+                following_user_id TEXT NOT NULL, 
+                action TEXT NOT NULL
+            );
+            CREATE TABLE user_dict (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                user_id TEXT NOT NULL UNIQUE, 
+                username TEXT NOT NULL, 
+                name TEXT NOT NULL
+            );
+            CREATE TABLE tweet_dict (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                tweet_id TEXT NOT NULL UNIQUE, 
+                author_id TEXT NOT NULL, 
+                text TEXT NOT NULL
+            );
+            CREATE TABLE search_hit (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, 
+                search_name TEXT NOT NULL, 
+                tweet_id TEXT NOT NULL, 
+                time TEXT NOT NULL, 
+                author_id TEXT NOT NULL, 
+                UNIQUE(search_name, tweet_id)
+            );
+            INSERT INTO user_liked (user_id, author_id, ref_tweet_id) VALUES ('1', '2', '10'), ('1', '2', '20');"
+        ).unwrap();
+        migrate(&conn).unwrap();
+        // running again is a no-op
+        migrate(&conn).unwrap();
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        let availability: String = conn.query_row(
+            "INSERT INTO user_dict (user_id, username, name) VALUES ('1', 'suisei', 'Suisei') RETURNING availability", 
+            [], 
+            |row| row.get(0)
+        ).unwrap();
+        assert_eq!(availability, "available");
+        let newest_like: String = conn.query_row(
+            "SELECT ref_tweet_id FROM user_liked WHERE user_id = '1' ORDER BY fetch_order DESC LIMIT 1", 
+            [], 
+            |row| row.get(0)
+        ).unwrap();
+        assert_eq!(newest_like, "20");
+        assert!(has_table(&conn, "saved_search_hit").unwrap() && !has_table(&conn, "search_hit").unwrap());
+
+        let added_columns = [
+            ("user_tweet", "conversation_id"), 
+            ("user_tweet", "in_reply_to_user_id"), 
+            ("user_tweet", "replied_to_id"), 
+            ("tweet_dict", "conversation_id"), 
+            ("tweet_dict", "replied_to_id"), 
+            ("user_dict", "availability"), 
+            ("user_dict", "unavailable_reason"), 
+            ("user_dict", "checked_at"), 
+            ("tweet_dict", "availability"), 
+            ("tweet_dict", "unavailable_reason"), 
+            ("tweet_dict", "checked_at"), 
+            ("user_liked", "fetch_order"), 
+            ("user_following", "fetch_order"), 
+            ("user_current_following", "fetch_order")
+        ];
+        for (table, column) in added_columns {
+            assert!(columns(&conn, table).contains(&column.to_string()), "{table} lacks {column}");
+        }
+    }
 }
\ No newline at end of file
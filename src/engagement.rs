@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::time;
+
+use rusqlite::{Connection, named_params};
+
+use crate::configuration;
+use crate::query_result::{now, BasicUserDetail};
+use crate::request_builder::EngagementFetcher;
+use crate::store::{SqliteStore, Store};
+use crate::worker::StopSignal;
+use crate::writer::DbWriter;
+
+/// How another account engaged with a tweet; each kind is fetched from its own endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EngagementKind {
+    Like,
+    Retweet,
+    Quote
+}
+
+impl EngagementKind {
+    pub const ALL: [EngagementKind; 3] = [EngagementKind::Like, EngagementKind::Retweet, EngagementKind::Quote];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngagementKind::Like => "like",
+            EngagementKind::Retweet => "retweet",
+            EngagementKind::Quote => "quote"
+        }
+    }
+
+    fn from_str(kind: &str) -> Option<EngagementKind> {
+        EngagementKind::ALL.into_iter().find(|engagement_kind| engagement_kind.as_str() == kind)
+    }
+}
+
+/// An account liking, retweeting or quoting a tweet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Engagement {
+    pub tweet_id: String,
+    pub kind: EngagementKind,
+    pub user: BasicUserDetail,
+    /// The quoting tweet, stored in `tweet_dict`
+    pub quote_tweet_id: Option<String>,
+    /// Posting time of quotes; likes and retweets come without one, so it is when they were
+    /// first fetched
+    pub time: String
+}
+
+impl Engagement {
+    /// Stores the engagement unless already known; the engager goes to `user_dict` with the rest
+    /// of the fetch result
+    pub fn write_to_db(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        conn.execute(
+            "INSERT OR IGNORE INTO tweet_engagement (tweet_id, user_id, kind, quote_tweet_id, time)
+            VALUES (:tweet_id, :user_id, :kind, :quote_tweet_id, :time)",
+            named_params! {
+                ":tweet_id": &self.tweet_id,
+                ":user_id": &self.user.id,
+                ":kind": self.kind.as_str(),
+                ":quote_tweet_id": &self.quote_tweet_id,
+                ":time": &self.time
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Ids identifying the stored engagements of `kind` with `tweet_id`: engager ids for likes
+    /// and retweets, quoting tweet ids for quotes
+    pub fn known_ids(conn: &Connection, tweet_id: &str, kind: EngagementKind) -> Result<HashSet<String>, Box<dyn Error>> {
+        let mut known_stmt = conn.prepare(
+            "SELECT COALESCE(quote_tweet_id, user_id) FROM tweet_engagement WHERE tweet_id = :tweet_id AND kind = :kind"
+        )?;
+        let query_results = known_stmt.query_map(
+            named_params! { ":tweet_id": tweet_id, ":kind": kind.as_str() },
+            |row| row.get(0)
+        )?;
+
+        let mut known_ids: HashSet<String> = HashSet::new();
+        for query_result in query_results {
+            known_ids.insert(query_result?);
+        }
+        Ok(known_ids)
+    }
+
+    /// Reads the engagements with `tweet_id` in the order they were stored
+    pub fn get_records(conn: &Connection, tweet_id: &str) -> Result<Vec<Engagement>, Box<dyn Error>> {
+        let mut engagement_stmt = conn.prepare(
+            "SELECT tweet_engagement.kind, tweet_engagement.user_id, user_dict.username, user_dict.name,
+                tweet_engagement.quote_tweet_id, tweet_engagement.time
+            FROM tweet_engagement
+            LEFT JOIN user_dict ON user_dict.user_id = tweet_engagement.user_id
+            WHERE tweet_engagement.tweet_id = :tweet_id
+            ORDER BY tweet_engagement.id"
+        )?;
+        let query_results = engagement_stmt.query_map(named_params! { ":tweet_id": tweet_id }, |row| {
+            let user_id: String = row.get(1)?;
+            let user = match (row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?) {
+                (Some(username), Some(name)) => BasicUserDetail { id: user_id, username, name },
+                _ => BasicUserDetail::placeholder(&user_id)
+            };
+            Ok((row.get::<_, String>(0)?, user, row.get(4)?, row.get(5)?))
+        })?;
+
+        let mut engagements: Vec<Engagement> = Vec::new();
+        for query_result in query_results {
+            let (kind, user, quote_tweet_id, time) = query_result?;
+            let kind = match EngagementKind::from_str(&kind) {
+                Some(kind) => kind,
+                None => { return Err(Box::new(rusqlite::Error::InvalidColumnType(0, kind, rusqlite::types::Type::Text))); }
+            };
+            engagements.push(Engagement {
+                tweet_id: tweet_id.to_string(),
+                kind,
+                user,
+                quote_tweet_id,
+                time
+            });
+        }
+        Ok(engagements)
+    }
+}
+
+/// An account engaging with a monitored user's tweets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interactor {
+    pub user: BasicUserDetail,
+    /// Number of distinct tweets engaged with
+    pub engaged_tweets: usize,
+    pub likes: usize,
+    pub retweets: usize,
+    pub quotes: usize
+}
+
+/// Accounts engaging with the most of `user_id`'s stored tweets, then with the most engagements;
+/// the user's own engagements are left out
+pub fn loyal_interactors(conn: &Connection, user_id: &str, max_results: u16) -> Result<Vec<Interactor>, Box<dyn Error>> {
+    let mut interactor_stmt = conn.prepare(
+        "SELECT tweet_engagement.user_id, user_dict.username, user_dict.name,
+            COUNT(DISTINCT tweet_engagement.tweet_id),
+            SUM(tweet_engagement.kind = 'like'),
+            SUM(tweet_engagement.kind = 'retweet'),
+            SUM(tweet_engagement.kind = 'quote')
+        FROM tweet_engagement
+        JOIN user_tweet ON user_tweet.tweet_id = tweet_engagement.tweet_id
+        LEFT JOIN user_dict ON user_dict.user_id = tweet_engagement.user_id
+        WHERE user_tweet.author_id = :user_id AND tweet_engagement.user_id != :user_id
+        GROUP BY tweet_engagement.user_id
+        ORDER BY COUNT(DISTINCT tweet_engagement.tweet_id) DESC, COUNT(*) DESC, tweet_engagement.user_id
+        LIMIT :limit"
+    )?;
+    let query_results = interactor_stmt.query_map(
+        named_params! { ":user_id": user_id, ":limit": max_results },
+        |row| {
+            let engager_id: String = row.get(0)?;
+            let user = match (row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?) {
+                (Some(username), Some(name)) => BasicUserDetail { id: engager_id, username, name },
+                _ => BasicUserDetail::placeholder(&engager_id)
+            };
+            Ok(Interactor {
+                user,
+                engaged_tweets: row.get(3)?,
+                likes: row.get(4)?,
+                retweets: row.get(5)?,
+                quotes: row.get(6)?
+            })
+        }
+    )?;
+
+    let mut interactors: Vec<Interactor> = Vec::new();
+    for query_result in query_results {
+        interactors.push(query_result?);
+    }
+    Ok(interactors)
+}
+
+/// What one engagement poll did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EngagementReport {
+    pub polled_tweets: usize,
+    pub new_engagements: usize,
+    /// Requests sent, over all three endpoints
+    pub requests: usize
+}
+
+/// Polls the engagement with `user_id`'s `recent_tweets` newest tweets, least recently polled
+/// first, sending at most `request_budget` requests to each endpoint. Requests to an endpoint are
+/// spaced 12 seconds apart, which stays within its limit of 75 requests per 15 minutes. Stored
+/// engagements are read on `conn`, the fetched ones are written through `writer`. Once `stop` is
+/// given the poll ends with what it wrote so far.
+pub fn poll_engagement(
+    conf: &configuration::Config,
+    conn: &Connection,
+    writer: &DbWriter,
+    stop: &StopSignal,
+    user_id: &str,
+    recent_tweets: u16,
+    request_budget: u16
+) -> Result<EngagementReport, Box<dyn Error>> {
+    let mut report = EngagementReport::default();
+    let mut remaining: HashMap<EngagementKind, u16> = EngagementKind::ALL.into_iter()
+        .map(|kind| (kind, request_budget))
+        .collect();
+
+    for (tweet_index, tweet_id) in tweets_to_poll(conn, user_id, recent_tweets)?.iter().enumerate() {
+        if remaining.values().any(|requests| *requests == 0) {
+            break;
+        }
+        let delay = match tweet_index {
+            0 => time::Duration::ZERO,
+            _ => time::Duration::from_secs(12)
+        };
+        if stop.wait(delay) {
+            break;
+        }
+
+        for kind in EngagementKind::ALL {
+            let known_ids = Engagement::known_ids(conn, tweet_id, kind)?;
+            let fetch_result = EngagementFetcher::new(tweet_id, kind, known_ids).fetch(conf, remaining[&kind], stop);
+            if stop.is_stopped() {
+                return Ok(report);
+            }
+            let (engagements, quote_tweets, engagers, requests) = fetch_result?;
+            report.new_engagements += engagements.len();
+            report.requests += requests as usize;
+            *remaining.get_mut(&kind).expect("every kind has a budget") -= requests;
+            writer.write(move |conn| {
+                let store = SqliteStore::new(conn);
+                for engager in engagers.iter() {
+                    store.write_user(engager)?;
+                }
+                for quote_tweet in quote_tweets.iter() {
+                    store.write_dict_tweet(quote_tweet)?;
+                }
+                for engagement in engagements.iter() {
+                    engagement.write_to_db(conn)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        let polled_tweet_id = tweet_id.clone();
+        writer.write(move |conn| {
+            conn.execute(
+                "INSERT INTO engagement_poll (tweet_id, polled_at) VALUES (:tweet_id, :polled_at)
+                ON CONFLICT (tweet_id) DO UPDATE SET polled_at = excluded.polled_at",
+                named_params! { ":tweet_id": polled_tweet_id, ":polled_at": now() }
+            )?;
+            Ok(())
+        })?;
+        report.polled_tweets += 1;
+    }
+
+    Ok(report)
+}
+
+/// Ids among `user_id`'s `recent_tweets` newest tweets, never polled ones first, then the least
+/// recently polled. Plain retweets are skipped, their engagement belongs to the retweeted tweet.
+fn tweets_to_poll(conn: &Connection, user_id: &str, recent_tweets: u16) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut poll_stmt = conn.prepare(
+        "WITH recent AS (
+            SELECT tweet_id, id FROM user_tweet
+            WHERE author_id = :user_id AND NOT (tweet_type = 'retweet' AND tweet_text LIKE 'RT @%')
+            ORDER BY length(tweet_id) DESC, tweet_id DESC LIMIT :limit
+        )
+        SELECT recent.tweet_id FROM recent
+        LEFT JOIN engagement_poll ON engagement_poll.tweet_id = recent.tweet_id
+        ORDER BY engagement_poll.polled_at IS NOT NULL, engagement_poll.polled_at,
+            length(recent.tweet_id) DESC, recent.tweet_id DESC"
+    )?;
+    let query_results = poll_stmt.query_map(
+        named_params! { ":user_id": user_id, ":limit": recent_tweets },
+        |row| row.get(0)
+    )?;
+
+    let mut tweet_ids: Vec<String> = Vec::new();
+    for query_result in query_results {
+        tweet_ids.push(query_result?);
+    }
+    Ok(tweet_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::query_result::FetchedTweet;
+
+    use super::*;
+
+    fn own_tweet(conn: &Connection, id: &str, text: &str) {
+        let mut tweet = FetchedTweet::new();
+        tweet.id = id.to_string();
+        tweet.author_id = "0".to_string();
+        tweet.created_at = "2023-01-01T00:00:00.000Z".to_string();
+        tweet.text = text.to_string();
+        SqliteStore::new(conn).write_tweet(&tweet).unwrap();
+    }
+
+    fn engagement(tweet_id: &str, kind: EngagementKind, user: &BasicUserDetail, quote_tweet_id: Option<&str>) -> Engagement {
+        Engagement {
+            tweet_id: tweet_id.to_string(),
+            kind,
+            user: user.clone(),
+            quote_tweet_id: quote_tweet_id.map(|id| id.to_string()),
+            time: "2023-01-02T00:00:00.000Z".to_string()
+        }
+    }
+
+    #[test]
+    fn test_engagement() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let suisei = BasicUserDetail { id: "0".to_string(), username: "suisei_hosimati".to_string(), name: "Suisei".to_string() };
+        let towa = BasicUserDetail { id: "2".to_string(), username: "tokoyami_towa".to_string(), name: "Towa".to_string() };
+        let miko = BasicUserDetail { id: "3".to_string(), username: "sakuramiko35".to_string(), name: "Miko".to_string() };
+        for user in [&suisei, &towa, &miko] {
+            SqliteStore::new(&conn).write_user(user).unwrap();
+        }
+        own_tweet(&conn, "10", "Stellar Stellar");
+        own_tweet(&conn, "11", "comet");
+
+        let towa_likes = engagement("10", EngagementKind::Like, &towa, None);
+        let towa_quote = engagement("11", EngagementKind::Quote, &towa, Some("20"));
+        for stored_engagement in [
+            &towa_likes,
+            &towa_likes,
+            &engagement("11", EngagementKind::Like, &towa, None),
+            &towa_quote,
+            &engagement("11", EngagementKind::Like, &miko, None),
+            &engagement("11", EngagementKind::Retweet, &miko, None),
+            &engagement("10", EngagementKind::Like, &suisei, None)
+        ] {
+            stored_engagement.write_to_db(&conn).unwrap();
+        }
+
+        assert_eq!(Engagement::get_records(&conn, "10").unwrap(), vec![towa_likes, engagement("10", EngagementKind::Like, &suisei, None)]);
+        assert_eq!(Engagement::known_ids(&conn, "11", EngagementKind::Quote).unwrap(), HashSet::from(["20".to_string()]));
+        assert_eq!(Engagement::known_ids(&conn, "11", EngagementKind::Like).unwrap().len(), 2);
+
+        assert_eq!(loyal_interactors(&conn, "0", 10).unwrap(), vec![
+            Interactor { user: towa, engaged_tweets: 2, likes: 2, retweets: 0, quotes: 1 },
+            Interactor { user: miko, engaged_tweets: 1, likes: 1, retweets: 1, quotes: 0 }
+        ]);
+    }
+
+    #[test]
+    fn test_tweets_to_poll() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        own_tweet(&conn, "9", "first");
+        own_tweet(&conn, "10", "second");
+        own_tweet(&conn, "11", "third");
+
+        conn.execute("INSERT INTO engagement_poll (tweet_id, polled_at) VALUES ('11', '2023-01-01T00:00:00.000Z')", []).unwrap();
+        assert_eq!(tweets_to_poll(&conn, "0", 2).unwrap(), vec!["10".to_string(), "11".to_string()]);
+        assert_eq!(tweets_to_poll(&conn, "0", 10).unwrap(), vec!["10".to_string(), "9".to_string(), "11".to_string()]);
+    }
+}
@@ -52,8 +52,121 @@ impl fmt::Display for InvalidUserList {
     }
 }
 
+impl Default for InvalidUserList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InvalidUserList {
     pub fn new() -> InvalidUserList {
         InvalidUserList
     }
+}
+
+#[derive(Debug)]
+pub struct InvalidConfigOption {
+    option: String
+}
+
+impl Error for InvalidConfigOption {}
+
+impl fmt::Display for InvalidConfigOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Config: option {} is invalid!", &self.option)
+    }
+}
+
+impl InvalidConfigOption {
+    pub fn new(option: &str) -> InvalidConfigOption {
+        InvalidConfigOption {
+            option: option.to_string()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StreamError {
+    reason: String
+}
+
+impl Error for StreamError {}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Filtered stream: {}", &self.reason)
+    }
+}
+
+impl StreamError {
+    pub fn new(reason: &str) -> StreamError {
+        StreamError {
+            reason: reason.to_string()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CassetteError {
+    reason: String
+}
+
+impl Error for CassetteError {}
+
+impl fmt::Display for CassetteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cassette: {}", &self.reason)
+    }
+}
+
+impl CassetteError {
+    pub fn new(reason: &str) -> CassetteError {
+        CassetteError {
+            reason: reason.to_string()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WriterError {
+    reason: String
+}
+
+impl Error for WriterError {}
+
+impl fmt::Display for WriterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Database writer: {}", &self.reason)
+    }
+}
+
+impl WriterError {
+    pub fn new(reason: &str) -> WriterError {
+        WriterError {
+            reason: reason.to_string()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PagingStopped;
+
+impl Error for PagingStopped {}
+
+impl fmt::Display for PagingStopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Paging: stopped before the last page!")
+    }
+}
+
+impl Default for PagingStopped {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PagingStopped {
+    pub fn new() -> PagingStopped {
+        PagingStopped
+    }
 }
\ No newline at end of file
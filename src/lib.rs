@@ -2,4 +2,28 @@ pub mod configuration;
 pub mod request_builder;
 pub mod query_result;
 pub mod errors;
-pub mod db;
\ No newline at end of file
+pub mod db;
+pub mod search;
+pub mod media;
+pub mod stats;
+pub mod announcement;
+pub mod thread;
+pub mod repair;
+pub mod profile;
+pub mod mention;
+pub mod engagement;
+pub mod saved_search_hit;
+pub mod stream;
+pub mod api_response;
+pub mod paginator;
+pub mod checkpoint;
+pub mod raw_archive;
+pub mod reprocess;
+pub mod transport;
+pub mod store;
+pub mod writer;
+pub mod schedule;
+pub mod worker;
+pub mod session;
+#[cfg(test)]
+mod test_support;
\ No newline at end of file
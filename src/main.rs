@@ -1,7 +1,19 @@
-use std::{process, thread, time, sync::Arc};
+use std::{error::Error, fs, mem, process, thread, time::{self, SystemTime}, collections::HashMap, sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, TryRecvError}}};
+use chrono::Utc;
 use clap::Parser;
 use rusqlite::Connection;
-use sui_twitter_db::{configuration::{Config, Args, TaskType}, request_builder::{UserInfoFetcher, TweetFetcher, LikeFetcher, FollowingFetcher}, db, query_result::{FetchedUser, FetchedTweet, LikedTweet, FollowingUser}};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use sui_twitter_db::{configuration::{Config, Args, TaskType, IngestionMode, SharedWorker, StreamPolling}, request_builder::{UserInfoFetcher, TweetFetcher, TweetFetchResult, LikeFetcher, MentionsFetcher, SearchFetcher, FollowingFetcher}, db, query_result::{FetchedTweet, Placement}, media::{ArchivedFile, MediaArchiver}, profile::ProfileChange, announcement::StreamAnnouncement, mention::Mention, saved_search_hit::SavedSearchHit, stream::{self, Backoff, FilteredStream}, engagement, repair, checkpoint::{self, FetchCheckpoint}, raw_archive::RawArchive, reprocess, store::{SqliteStore, Store}, writer::DbWriter, schedule::{self, ActivityProfile, AdaptiveInterval}, worker::{self, StopSignal, Worker}, session::MonitorSession};
+
+/// How often the monitor checks for signals and changes of the config file
+const CHECK_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// The monitor stopped on SIGINT or SIGTERM after every pending write was flushed
+const EXIT_CLEAN: i32 = 0;
+/// The monitor stopped, but pending writes or its shutdown could not be written
+const EXIT_UNFLUSHED: i32 = 2;
+/// A second SIGINT or SIGTERM ended the monitor without waiting for its workers
+const EXIT_FORCED: i32 = 3;
 
 fn main() {
     env_logger::init();
@@ -17,167 +29,964 @@ fn main() {
         }
     };
 
-    
-    for username in config.monitoring_username.iter() {
-        match config.task_type {
-            TaskType::Initializing => {
-                println!("====> Initializing <====");
-                println!("====> Depending on the user's condition");
-                println!("====> This might take some time");
+    // the archive holds the responses of every monitored user
+    if let TaskType::Reprocessing = config.task_type {
+        reprocess_archive(&config);
+        return;
+    }
 
-                let conn = Connection::open(&config.db_path).expect("Unable to open the database");
+    // the threads of every user write through it
+    let (writer, writer_handle) = DbWriter::start(&config.db_path).expect("Unable to open the database");
 
+    let mut exit_status = EXIT_CLEAN;
+    match config.task_type {
+        TaskType::Initializing => {
+            println!("====> Initializing <====");
+            println!("====> Depending on the user's condition");
+            println!("====> This might take some time");
+
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+
+            if FetchCheckpoint::interrupted(&conn, &config.monitoring_username).expect("Unable to read fetch checkpoints") {
+                println!("====> Resuming the interrupted initialization");
+            } else {
                 db::init_db(&conn).expect("Unable to initialize database");
+            }
+
+            for username in config.monitoring_username.iter() {
+                initialize(&config, &conn, &writer, &StopSignal::new(), username).expect("Failed to initialize");
+            }
+        }
+
+        TaskType::Monitoring => { exit_status = monitor(config, &writer); }
+
+        TaskType::Reprocessing => unreachable!("reprocessing does not need the writer")
+    }
+
+    drop(writer);
+    writer_handle.join().unwrap();
+    process::exit(exit_status);
+}
 
-                let user_profile_fetcher = UserInfoFetcher::new(username);
-                let fetched_profile = user_profile_fetcher.fetch(&config).expect("Failed to fetch user profile");
-                fetched_profile.write_to_db(&conn, &TaskType::Initializing).expect("Failed to write user profile to database");
+/// Fetches the history of `username` into an initialized database, resuming from the fetch
+/// checkpoints of an interrupted run, and returns the account's user id. Once `stop` is given the
+/// paging fails, to resume from the checkpoints on the next run.
+fn initialize(config: &Config, conn: &Connection, writer: &DbWriter, stop: &StopSignal, username: &str) -> Result<String, Box<dyn Error>> {
+    let user_profile_fetcher = UserInfoFetcher::new(username);
+    let fetched_profile = user_profile_fetcher.fetch(config)?;
+    let user_id = fetched_profile.user.id.clone();
+    let polling = config.polling.account(username);
+    let mut profile_checkpoint = FetchCheckpoint::get_record(conn, &user_id, "profile")?;
+    if !profile_checkpoint.completed {
+        profile_checkpoint.advance(None, 1);
+        writer.write(move |conn| {
+            SqliteStore::new(conn).write_profile(&fetched_profile, &TaskType::Initializing)?;
+            profile_checkpoint.write_to_db(conn)
+        })?;
+    }
 
-                let tweet_fetcher = TweetFetcher::new(&fetched_profile.user.id, None);
-                let (tweets, ref_tweets, ref_users) = tweet_fetcher.fetch(&config).expect("Failed to fetch user tweets");
+    // every page is written through the writer as it arrives, with the checkpoint to resume
+    // after it; media is downloaded before the page is handed to the writer
+    let media_archiver = config.media_archive_dir.as_deref().map(MediaArchiver::new);
+    if polling.tweets.enabled {
+        let tweet_fetcher = TweetFetcher::new(&user_id, None);
+        checkpoint::fetch_resumable(config, writer, stop, FetchCheckpoint::get_record(conn, &user_id, "tweets")?, &tweet_fetcher, |(tweets, ref_tweets, ref_users)| {
+            let archived_media = download_media(media_archiver.as_ref(), &tweets);
+            Ok(Box::new(move |conn: &Connection| {
+                let store = SqliteStore::new(conn);
                 for ref_user in ref_users.into_iter() {
-                    ref_user.write_to_db(&conn).expect("Failed to write referenced users to database");
+                    store.write_user(&ref_user)?;
                 }
                 for ref_tweet in ref_tweets.into_iter() {
-                    ref_tweet.write_to_db(&conn).expect("Failed to write referenced tweet to database");
+                    store.write_dict_tweet(&ref_tweet)?;
                 }
-                for tweet in tweets.into_iter() {
-                    tweet.write_to_db(&conn).expect("Failed to write fetched tweet to database");
+                for tweet in tweets.iter().rev() {
+                    store.write_tweet(tweet)?;
+                    record_announcements(conn, tweet)?;
                 }
+                record_archived_media(conn, &archived_media)?;
+                Ok(tweets.len())
+            }))
+        })?;
+    }
 
-                let like_fetcher = LikeFetcher::new(&fetched_profile.user.id, None);
-                let (liked_tweet_records, liked_tweets, liked_users) = like_fetcher.fetch(&config).expect("Failed to fetch liked twitter");
+    if polling.likes.enabled {
+        let like_fetcher = LikeFetcher::new(&user_id, None);
+        checkpoint::fetch_resumable(config, writer, stop, FetchCheckpoint::get_record(conn, &user_id, "likes")?, &like_fetcher, |(liked_tweet_records, liked_tweets, liked_users)| {
+            Ok(Box::new(move |conn: &Connection| {
+                let store = SqliteStore::new(conn);
                 for liked_user in liked_users.into_iter() {
-                    liked_user.write_to_db(&conn).expect("Failed to write liked users to database");
+                    store.write_user(&liked_user)?;
                 }
                 for liked_tweet in liked_tweets.into_iter() {
-                    liked_tweet.write_to_db(&conn).expect("Failed to write liked tweet to database");
+                    store.write_dict_tweet(&liked_tweet)?;
+                }
+                // pages come newest first, each one older than the likes written before
+                for liked_tweet_record in liked_tweet_records.iter() {
+                    store.write_like(liked_tweet_record, Placement::Older)?;
+                }
+                Ok(liked_tweet_records.len())
+            }))
+        })?;
+    }
+
+    if polling.mentions.enabled {
+        let mentions_fetcher = MentionsFetcher::new(&user_id, None);
+        checkpoint::fetch_resumable(config, writer, stop, FetchCheckpoint::get_record(conn, &user_id, "mentions")?, &mentions_fetcher, |(mentions, mentioning_tweets, mentioning_users)| {
+            Ok(Box::new(move |conn: &Connection| {
+                let store = SqliteStore::new(conn);
+                for mentioning_user in mentioning_users.into_iter() {
+                    store.write_user(&mentioning_user)?;
                 }
-                for liked_tweet_record in liked_tweet_records.into_iter() {
-                    liked_tweet_record.write_to_db(&conn).expect("Failed to write liked tweet record to database");
+                for mentioning_tweet in mentioning_tweets.into_iter() {
+                    store.write_dict_tweet(&mentioning_tweet)?;
                 }
+                for mention in mentions.iter().rev() {
+                    mention.write_to_db(conn)?;
+                }
+                Ok(mentions.len())
+            }))
+        })?;
+    }
 
-                let following_fetcher = FollowingFetcher::new(&fetched_profile.user.id, None);
-                let (following_records, followed_users) = following_fetcher.fetch(&config, &conn).expect("Failed to fetch following users");
+    if polling.following.enabled {
+        let following_fetcher = FollowingFetcher::new(&user_id, None);
+        checkpoint::fetch_resumable(config, writer, stop, FetchCheckpoint::get_record(conn, &user_id, "following")?, &following_fetcher, |(following_records, followed_users, _)| {
+            Ok(Box::new(move |conn: &Connection| {
+                let store = SqliteStore::new(conn);
                 for followed_user in followed_users.into_iter() {
-                    followed_user.write_to_db(&conn).expect("Failed to write followed users to database");
-                }
-                for following_record in following_records.into_iter() {
-                    following_record.write_to_db(&conn).expect("Failed to write following records to database");
-                }
-            }
-
-            TaskType::Monitoring => {
-                let arc_username = Arc::new(username.clone());
-                let profile_username = Arc::clone(&arc_username);
-                let tweet_username = Arc::clone(&arc_username);
-                let like_username = Arc::clone(&arc_username);
-                let following_username = Arc::clone(&arc_username);
-                let conn = Connection::open(&config.db_path).expect("Unable to open the database");
-                let user_profile = FetchedUser::get_records(&conn, &arc_username, None, 0).unwrap().into_iter().next().unwrap();
-                drop(conn);
-                let arc_user_id = Arc::new(user_profile.user.id.clone());
-                let tweet_user_id = Arc::clone(&arc_user_id);
-                let like_user_id = Arc::clone(&arc_user_id);
-                let following_user_id = Arc::clone(&arc_user_id);
-
-                let user_profile_config = config.clone();
-                let user_tweet_config = config.clone();
-                let user_following_config = config.clone();
-                let user_like_config = config.clone();
-
-                let user_profile_handler = thread::spawn(move || {
-
-                    let conn = Connection::open(&user_profile_config.db_path).expect("Unable to open the database");
-                    
-                    loop {
-                        let user_profile_fetcher = UserInfoFetcher::new(&profile_username);
-                        let fetched_profile = user_profile_fetcher.fetch(&user_profile_config).expect("Failed to fetch user profile");
-                        log::info!(
-                            "{}: get user profile => user-id: {}, user_name: {}, name: {}", 
-                            &profile_username, &fetched_profile.user.id, &fetched_profile.user.username, &fetched_profile.user.name
-                        );
-                        fetched_profile.write_to_db(&conn, &TaskType::Monitoring).expect("Failed to write user profile to database");
-                        thread::sleep(time::Duration::from_secs(120));
-                    }
+                    store.write_user(&followed_user)?;
+                }
+                for following_record in following_records.iter() {
+                    store.write_following(following_record, Placement::Older)?;
+                }
+                Ok(following_records.len())
+            }))
+        })?;
+    }
 
-                });
-
-                let user_tweet_handler = thread::spawn(move || {
-                    let conn = Connection::open(&user_tweet_config.db_path).expect("Unable to open the database");
-
-                    loop {
-                        let latest_tweet_id = FetchedTweet::newest_id(&conn, &tweet_user_id).expect("should get latest id record");
-                        let tweet_fetcher = TweetFetcher::new(&tweet_user_id, latest_tweet_id.as_deref());
-                        let (tweets, ref_tweets, ref_users) = tweet_fetcher.fetch(&user_tweet_config).expect("Failed to fetch user tweets");
-                        for ref_user in ref_users.into_iter() {
-                            ref_user.write_to_db(&conn).expect("Failed to write referenced users to database");
-                        }
-                        for ref_tweet in ref_tweets.into_iter() {
-                            ref_tweet.write_to_db(&conn).expect("Failed to write referenced tweet to database");
-                        }
-                        for tweet in tweets.into_iter() {
-                            log::info!(
-                                "{}: get new tweet => text: {}, type: {:?}, created at: {}", 
-                                &tweet_username, &tweet.text, &tweet.tweet_type, &tweet.created_at
-                            );
-                            tweet.write_to_db(&conn).expect("Failed to write fetched tweet to database");
-                        }
-                        thread::sleep(time::Duration::from_secs(60));
-                    }
+    repair_placeholders(config, conn, writer);
+    if polling.engagement.enabled {
+        poll_engagement(config, conn, writer, stop, &user_id, &polling.engagement);
+    }
+    capture_saved_searches(config, conn, writer, stop);
+    Ok(user_id)
+}
 
-                });
-
-                let user_liked_handler = thread::spawn(move || {
-                    let conn = Connection::open(&user_like_config.db_path).expect("Unable to open the database");
-
-                    loop {
-                        let latest_like_id = LikedTweet::newest_id(&conn, &like_user_id).expect("should get liked id record");
-                        let like_fetcher = LikeFetcher::new(&like_user_id, latest_like_id.as_deref());
-                        let (liked_tweet_records, liked_tweets, liked_users) = like_fetcher.fetch(&user_like_config).expect("Failed to fetch liked twitter");
-                        for liked_user in liked_users.into_iter() {
-                            liked_user.write_to_db(&conn).expect("Failed to write liked users to database");
-                        }
-                        for liked_tweet in liked_tweets.into_iter() {
-                            liked_tweet.write_to_db(&conn).expect("Failed to write liked tweet to database");
-                        }
-                        for liked_tweet_record in liked_tweet_records.into_iter() {
-                            log::info!(
-                                "{}: get new liked: text: {}, author: {}", 
-                                &like_username, &liked_tweet_record.tweet.text, &liked_tweet_record.author.username
-                            );
-                            liked_tweet_record.write_to_db(&conn).expect("Failed to write liked tweet record to database");
-                        }
-                        thread::sleep(time::Duration::from_secs(60));
-                    }
-                });
-
-                let user_follow_handler = thread::spawn(move || {
-                    let conn = Connection::open(&user_following_config.db_path).expect("Unable to open the database");
-
-                    loop {
-                        let latest_follow_id = FollowingUser::get_newest_ids(&conn, &following_user_id).expect("should get liked id record");
-                        let following_fetcher = FollowingFetcher::new(&following_user_id, Some(latest_follow_id));
-                        let (following_records, followed_users) = following_fetcher.fetch(&user_following_config, &conn).expect("Failed to fetch following users");
-                        for followed_user in followed_users.into_iter() {
-                            followed_user.write_to_db(&conn).expect("Failed to write followed users to database");
-                        }
-                        for following_record in following_records.into_iter() {
-                            log::info!(
-                                "{}: get new following action => username: {}, action: {:?}", 
-                                &following_username, &following_record.followed_user.username, &following_record.action
-                            );
-                            following_record.write_to_db(&conn).expect("Failed to write following records to database");
-                        }
-                        thread::sleep(time::Duration::from_secs(180));
+/// Monitors every configured account until SIGINT or SIGTERM, then lets every worker finish what
+/// it is doing, flushes the pending writes and records the shutdown, returning the exit status.
+/// The config file is reloaded when it changes or on SIGHUP: workers of removed accounts stop,
+/// added accounts get workers of their own, and workers whose options changed restart with them
+/// once their predecessors finished. Stopped workers finish in the background, so reloads and
+/// signals are handled meanwhile.
+fn monitor(mut config: Config, writer: &DbWriter) -> i32 {
+    let reload_requested = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, Arc::clone(&reload_requested)).expect("Unable to handle SIGHUP");
+    let shutdown_requested = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        // registered first, so only a second signal ends the process right away
+        signal_hook::flag::register_conditional_shutdown(signal, EXIT_FORCED, Arc::clone(&shutdown_requested)).expect("Unable to handle shutdown signals");
+        signal_hook::flag::register(signal, Arc::clone(&shutdown_requested)).expect("Unable to handle shutdown signals");
+    }
+    let mut conf_modified = modified_time(&config.conf_path);
+
+    let (previous_session, mut session) = writer.write(|conn| Ok((MonitorSession::last(conn)?, MonitorSession::start(conn)?)))
+        .expect("Failed to record the monitor session");
+    if let Some(previous_session) = previous_session.filter(|previous_session| !previous_session.clean_shutdown) {
+        log::warn!("the monitor started at {} did not shut down cleanly, its last writes may be missing", &previous_session.started_at);
+    }
+
+    let mut accounts: HashMap<String, Account> = HashMap::new();
+    for username in config.monitoring_username.iter() {
+        accounts.insert(username.clone(), start_account(&config, writer, username));
+    }
+    let mut shared_workers: HashMap<SharedWorker, Worker> = SharedWorker::ALL.into_iter()
+        .filter_map(|kind| spawn_shared_worker(&config, writer, kind).map(|worker| (kind, worker)))
+        .collect();
+    // shared workers to start again once the one they replace finished
+    let mut shared_restarting: Vec<(SharedWorker, Option<Worker>)> = Vec::new();
+    // workers of removed accounts finishing what they are doing
+    let mut stopping: Vec<Worker> = Vec::new();
+
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+        if shutdown_requested.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let mut stream_outdated = false;
+        for (username, account) in accounts.iter_mut() {
+            let current = mem::replace(account, Account::Failed);
+            let initializing = matches!(current, Account::Initializing(..));
+            *account = current.advance(&config, writer, username);
+            // the filtered stream only follows initialized accounts
+            stream_outdated |= initializing && matches!(account, Account::Running(_))
+                && config.ingestion == IngestionMode::Stream && config.polling.account(username).tweets.enabled;
+        }
+        if stream_outdated {
+            restart_shared_worker(SharedWorker::Stream, &mut shared_workers, &mut shared_restarting);
+        }
+        shared_restarting.retain_mut(|(kind, replaced)| {
+            if replaced.as_ref().is_some_and(|replaced| !replaced.is_finished()) {
+                return true;
+            }
+            if let Some(replaced) = replaced.take() {
+                replaced.join();
+            }
+            if let Some(worker) = spawn_shared_worker(&config, writer, *kind) {
+                shared_workers.insert(*kind, worker);
+            }
+            false
+        });
+        let (finished, unfinished): (Vec<Worker>, Vec<Worker>) = stopping.into_iter().partition(Worker::is_finished);
+        finished.into_iter().for_each(Worker::join);
+        stopping = unfinished;
+
+        let modified = modified_time(&config.conf_path);
+        if !reload_requested.swap(false, Ordering::SeqCst) && modified == conf_modified {
+            continue;
+        }
+        conf_modified = modified;
+
+        let reloaded = match config.reload() {
+            Ok(reloaded) => reloaded, 
+            Err(e) => {
+                log::warn!("failed to reload {}, keeping the running config: {}", &config.conf_path, e);
+                continue;
+            }
+        };
+        let mut change = config.diff(&reloaded);
+        // accounts that failed to initialize are tried again
+        for (username, account) in accounts.iter() {
+            if matches!(account, Account::Failed) && !change.removed.contains(username) && !change.restarted.contains(username) {
+                change.restarted.push(username.clone());
+            }
+        }
+        log::info!(
+            "reloaded {} => added: {:?}, removed: {:?}, restarted: {:?}, shared workers restarted: {:?}", 
+            &config.conf_path, &change.added, &change.removed, &change.restarted, &change.shared_restarted
+        );
+
+        config = reloaded;
+        for username in change.removed.iter() {
+            stopping.extend(accounts.remove(username).map(Account::stop).unwrap_or_default());
+        }
+        for username in change.restarted.iter() {
+            let replaced = accounts.remove(username).map(Account::stop).unwrap_or_default();
+            accounts.insert(username.clone(), Account::Restarting(replaced));
+        }
+        for username in change.added.iter() {
+            accounts.insert(username.clone(), start_account(&config, writer, username));
+        }
+        for kind in change.shared_restarted.iter() {
+            restart_shared_worker(*kind, &mut shared_workers, &mut shared_restarting);
+        }
+    }
+
+    log::info!("shutting down once every worker finished what it is doing");
+    let mut workers: Vec<Worker> = accounts.into_values().flat_map(Account::stop).collect();
+    workers.extend(shared_workers.into_values());
+    workers.extend(shared_restarting.into_iter().filter_map(|(_, replaced)| replaced));
+    workers.extend(stopping);
+    worker::stop_all(workers);
+    let flushed = writer.flush();
+    if let Err(e) = &flushed {
+        log::error!("failed to flush pending writes: {}", e);
+    }
+    let clean_shutdown = flushed.is_ok();
+    match writer.write(move |conn| session.finish(conn, clean_shutdown)) {
+        Ok(()) if clean_shutdown => EXIT_CLEAN, 
+        Ok(()) => EXIT_UNFLUSHED, 
+        Err(e) => {
+            log::error!("failed to record the shutdown: {}", e);
+            EXIT_UNFLUSHED
+        }
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Stops the shared worker `kind`, if running, to start it again with the current config once it
+/// finished
+fn restart_shared_worker(kind: SharedWorker, shared_workers: &mut HashMap<SharedWorker, Worker>, shared_restarting: &mut Vec<(SharedWorker, Option<Worker>)>) {
+    if shared_restarting.iter().any(|(restarting, _)| *restarting == kind) {
+        return;
+    }
+    let replaced = shared_workers.remove(&kind);
+    if let Some(replaced) = &replaced {
+        replaced.stop();
+    }
+    shared_restarting.push((kind, replaced));
+}
+
+/// What runs for one monitored account
+enum Account {
+    /// The account has no profile yet; the worker initializes it and sends its user id
+    Initializing(Worker, Receiver<String>),
+    Running(Vec<Worker>),
+    /// The account failed to initialize and is left without workers until the next reload
+    Failed,
+    /// The account's workers were stopped to restart; it starts again once they finished
+    Restarting(Vec<Worker>)
+}
+
+impl Account {
+    /// Starts the account's workers once it is initialized or its stopped workers finished.
+    /// Running workers only end on their own when they failed, restarting the whole account.
+    fn advance(self, config: &Config, writer: &DbWriter, username: &str) -> Account {
+        match self {
+            Account::Initializing(worker, user_id) => match user_id.try_recv() {
+                Ok(user_id) => {
+                    worker.join();
+                    Account::Running(spawn_account_workers(config, writer, username, &user_id))
+                }
+                Err(TryRecvError::Empty) => Account::Initializing(worker, user_id),
+                // the worker ended without a user id
+                Err(TryRecvError::Disconnected) => {
+                    worker.join();
+                    log::warn!("{}: failed to initialize, not monitored until the next reload", username);
+                    Account::Failed
+                }
+            },
+            Account::Running(workers) if workers.iter().any(Worker::is_finished) => {
+                for worker in workers.iter().filter(|worker| worker.is_finished()) {
+                    log::warn!("{}: worker {} ended on its own, restarting the account", username, worker.name());
+                }
+                Account::Restarting(Account::Running(workers).stop())
+            }
+            Account::Restarting(workers) if workers.iter().all(Worker::is_finished) => {
+                workers.into_iter().for_each(Worker::join);
+                start_account(config, writer, username)
+            }
+            account => account
+        }
+    }
+
+    /// Signals every worker of the account to stop and returns them to finish
+    fn stop(self) -> Vec<Worker> {
+        let workers = match self {
+            Account::Initializing(worker, _) => vec![worker],
+            Account::Running(workers) | Account::Restarting(workers) => workers,
+            Account::Failed => Vec::new()
+        };
+        for worker in workers.iter() {
+            worker.stop();
+        }
+        workers
+    }
+}
+
+/// Starts the workers of `username`, or a worker initializing the account first when it has no
+/// profile yet
+fn start_account(config: &Config, writer: &DbWriter, username: &str) -> Account {
+    let conn = db::open(&config.db_path).expect("Unable to open the database");
+    let user_profile = SqliteStore::new(&conn).get_profiles(username, None, 0).expect("should get user profile record").into_iter().next();
+    match user_profile {
+        Some(user_profile) => Account::Running(spawn_account_workers(config, writer, username, &user_profile.user.id)), 
+        None => {
+            log::info!("{}: initializing the newly monitored account", username);
+            let mut init_config = config.clone();
+            init_config.task_type = TaskType::Initializing;
+            let (init_writer, init_username) = (writer.clone(), username.to_string());
+            let (user_id_sender, user_id) = mpsc::channel();
+            let worker = Worker::spawn(&format!("{username}/initialize"), move |stop| {
+                let conn = db::open(&init_config.db_path).expect("Unable to open the database");
+                match initialize(&init_config, &conn, &init_writer, &stop, &init_username) {
+                    // the account may have been removed meanwhile
+                    Ok(user_id) => { let _ = user_id_sender.send(user_id); }
+                    Err(_) if stop.is_stopped() => log::info!("{}: initialization stopped, it resumes from its checkpoints", &init_username),
+                    Err(e) => log::warn!("{}: failed to initialize: {}", &init_username, e)
+                }
+            });
+            Account::Initializing(worker, user_id)
+        }
+    }
+}
+
+/// Starts a worker for every enabled stream of one account. Tweets are polled here unless they
+/// are streamed for every account by the shared workers.
+fn spawn_account_workers(config: &Config, writer: &DbWriter, username: &str, user_id: &str) -> Vec<Worker> {
+    let polling = *config.polling.account(username);
+    let accounts = config.monitoring_username.len();
+    let mut workers: Vec<Worker> = Vec::new();
+
+    if polling.profile.enabled {
+        let (config, writer, username, user_id) = (config.clone(), writer.clone(), username.to_string(), user_id.to_string());
+        let profile_schedule = AdaptiveInterval::new(&polling.profile, schedule::PROFILE_RATE_LIMIT, accounts);
+        workers.push(Worker::spawn(&format!("{username}/profile"), move |stop| {
+            // reads on its own connection, writes through the writer
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+            let media_archiver = config.media_archive_dir.as_deref().map(MediaArchiver::new);
+
+            loop {
+                poll_profile(&config, &conn, &writer, media_archiver.as_ref(), &username, &user_id);
+                // a profile is fetched in a single request
+                if wait_next_poll(&stop, &profile_schedule, &conn, &user_id, false, 1) {
+                    break;
+                }
+            }
+        }));
+    }
+
+    if polling.tweets.enabled && config.ingestion == IngestionMode::Polling {
+        let (config, writer, username, user_id) = (config.clone(), writer.clone(), username.to_string(), user_id.to_string());
+        let tweet_schedule = AdaptiveInterval::new(&polling.tweets, schedule::TWEETS_RATE_LIMIT, accounts);
+        workers.push(Worker::spawn(&format!("{username}/tweets"), move |stop| {
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+            let media_archiver = config.media_archive_dir.as_deref().map(MediaArchiver::new);
+
+            loop {
+                let (found_new, requests) = poll_tweets(&config, &conn, &writer, media_archiver.as_ref(), &stop, &username, &user_id);
+                if wait_next_poll(&stop, &tweet_schedule, &conn, &user_id, found_new, requests) {
+                    break;
+                }
+            }
+        }));
+    }
+
+    if polling.likes.enabled {
+        let (config, writer, username, user_id) = (config.clone(), writer.clone(), username.to_string(), user_id.to_string());
+        let like_schedule = AdaptiveInterval::new(&polling.likes, schedule::LIKES_RATE_LIMIT, accounts);
+        workers.push(Worker::spawn(&format!("{username}/likes"), move |stop| {
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+
+            loop {
+                let (found_new, requests) = poll_likes(&config, &conn, &writer, &stop, &username, &user_id);
+                if wait_next_poll(&stop, &like_schedule, &conn, &user_id, found_new, requests) {
+                    break;
+                }
+            }
+        }));
+    }
+
+    if polling.mentions.enabled {
+        let (config, writer, username, user_id) = (config.clone(), writer.clone(), username.to_string(), user_id.to_string());
+        let mention_schedule = AdaptiveInterval::new(&polling.mentions, schedule::MENTIONS_RATE_LIMIT, accounts);
+        workers.push(Worker::spawn(&format!("{username}/mentions"), move |stop| {
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+
+            loop {
+                let (found_new, requests) = poll_mentions(&config, &conn, &writer, &stop, &username, &user_id);
+                if wait_next_poll(&stop, &mention_schedule, &conn, &user_id, found_new, requests) {
+                    break;
+                }
+            }
+        }));
+    }
+
+    if polling.following.enabled {
+        let (config, writer, username, user_id) = (config.clone(), writer.clone(), username.to_string(), user_id.to_string());
+        let following_schedule = AdaptiveInterval::new(&polling.following, schedule::FOLLOWING_RATE_LIMIT, accounts);
+        workers.push(Worker::spawn(&format!("{username}/following"), move |stop| {
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+
+            loop {
+                let (found_new, requests) = poll_following(&config, &conn, &writer, &stop, &username, &user_id);
+                if wait_next_poll(&stop, &following_schedule, &conn, &user_id, found_new, requests) {
+                    break;
+                }
+            }
+        }));
+    }
+
+    if polling.engagement.enabled {
+        let (config, writer, user_id) = (config.clone(), writer.clone(), user_id.to_string());
+        let engagement_schedule = AdaptiveInterval::new(&polling.engagement, schedule::ENGAGEMENT_RATE_LIMIT, accounts);
+        workers.push(Worker::spawn(&format!("{username}/engagement"), move |stop| {
+            let conn = db::open(&config.db_path).expect("Unable to open the database");
+
+            loop {
+                let (found_new, requests) = poll_engagement(&config, &conn, &writer, &stop, &user_id, &polling.engagement);
+                if wait_next_poll(&stop, &engagement_schedule, &conn, &user_id, found_new, requests) {
+                    break;
+                }
+            }
+        }));
+    }
+
+    workers
+}
+
+/// Starts a worker shared by every account; the filtered stream only runs when tweets are
+/// streamed
+fn spawn_shared_worker(config: &Config, writer: &DbWriter, kind: SharedWorker) -> Option<Worker> {
+    match kind {
+        SharedWorker::Repair => Some(spawn_repair_worker(config, writer)),
+        SharedWorker::Search => Some(spawn_search_worker(config, writer)),
+        SharedWorker::Stream if config.ingestion == IngestionMode::Stream => Some(spawn_stream_worker(config, writer)),
+        SharedWorker::Stream => None
+    }
+}
+
+fn spawn_repair_worker(config: &Config, writer: &DbWriter) -> Worker {
+    let (repair_config, repair_writer) = (config.clone(), writer.clone());
+    Worker::spawn(SharedWorker::Repair.as_str(), move |stop| {
+        let conn = db::open(&repair_config.db_path).expect("Unable to open the database");
+
+        loop {
+            repair_placeholders(&repair_config, &conn, &repair_writer);
+            if stop.wait(repair_config.polling.repair) {
+                break;
+            }
+        }
+    })
+}
+
+fn spawn_search_worker(config: &Config, writer: &DbWriter) -> Worker {
+    let (search_config, search_writer) = (config.clone(), writer.clone());
+    Worker::spawn(SharedWorker::Search.as_str(), move |stop| {
+        let conn = db::open(&search_config.db_path).expect("Unable to open the database");
+
+        loop {
+            capture_saved_searches(&search_config, &conn, &search_writer, &stop);
+            if stop.wait(search_config.polling.search) {
+                break;
+            }
+        }
+    })
+}
+
+/// Follows the filtered stream for every account with tweets enabled, polling their timelines
+/// while it is unavailable
+fn spawn_stream_worker(config: &Config, writer: &DbWriter) -> Worker {
+    let (stream_config, stream_writer) = (config.clone(), writer.clone());
+    Worker::spawn(SharedWorker::Stream.as_str(), move |stop| {
+        let conn = db::open(&stream_config.db_path).expect("Unable to open the database");
+        let store = SqliteStore::new(&conn);
+        let media_archiver = stream_config.media_archive_dir.as_deref().map(MediaArchiver::new);
+        // initialized accounts with tweets enabled
+        let accounts: Vec<(String, String)> = stream_config.monitoring_username.iter()
+            .filter(|username| stream_config.polling.account(username).tweets.enabled)
+            .filter_map(|username| {
+                let user_profile = store.get_profiles(username, None, 0).expect("should get user profile record").into_iter().next();
+                user_profile.map(|user_profile| (username.clone(), user_profile.user.id))
+            })
+            .collect();
+        let usernames: Vec<String> = accounts.iter().map(|(username, _)| username.clone()).collect();
+        // the fallback polls every account on its own schedule, as its tweets worker would
+        let tweet_schedules: Vec<AdaptiveInterval> = accounts.iter()
+            .map(|(username, _)| AdaptiveInterval::new(
+                &stream_config.polling.account(username).tweets, 
+                schedule::TWEETS_RATE_LIMIT, 
+                stream_config.monitoring_username.len()
+            ))
+            .collect();
+        let poll_accounts = || {
+            for (username, user_id) in accounts.iter() {
+                poll_tweets(&stream_config, &conn, &stream_writer, media_archiver.as_ref(), &stop, username, user_id);
+            }
+        };
+
+        loop {
+            let filtered_stream = FilteredStream::new();
+            let stream_rules = stream::monitoring_rules(&usernames);
+            let stream_result = filtered_stream.sync_rules(&stream_config, &stream_rules).and_then(|_| filtered_stream.run(
+                &stream_config, 
+                &mut Backoff::new(time::Duration::from_secs(5), time::Duration::from_secs(320)), 
+                5, 
+                &stop, 
+                &mut || poll_accounts(), 
+                &mut |fetch_result| write_tweets(&stream_writer, media_archiver.as_ref(), "filtered stream", fetch_result)
+            ));
+            if stop.is_stopped() {
+                break;
+            }
+            if let Err(e) = stream_result {
+                log::warn!("filtered stream unavailable, polling instead: {}", e);
+            }
+
+            // polling stands in for the stream for 15 minutes before it is tried again
+            let polling_until = time::Instant::now() + time::Duration::from_secs(900);
+            let mut next_polls: Vec<time::Instant> = vec![time::Instant::now(); accounts.len()];
+            loop {
+                for (((username, user_id), tweet_schedule), next_poll) in accounts.iter().zip(tweet_schedules.iter()).zip(next_polls.iter_mut()) {
+                    if *next_poll > time::Instant::now() {
+                        continue;
                     }
-                });
+                    let (found_new, requests) = poll_tweets(&stream_config, &conn, &stream_writer, media_archiver.as_ref(), &stop, username, user_id);
+                    *next_poll = time::Instant::now() + next_poll_delay(tweet_schedule, &conn, user_id, found_new, requests);
+                }
+                let wake_at = next_polls.iter().copied().min().unwrap_or(polling_until).min(polling_until);
+                if stop.wait(wake_at.saturating_duration_since(time::Instant::now())) {
+                    return;
+                }
+                if time::Instant::now() >= polling_until {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Rebuilds a database next to the configured one from the raw response archive, leaving the
+/// configured one untouched
+fn reprocess_archive(config: &Config) {
+    let raw_archive = match RawArchive::from_config(config) {
+        Some(raw_archive) => raw_archive, 
+        None => {
+            eprintln!("raw_archive_dir must be configured to reprocess");
+            process::exit(1);
+        }
+    };
+    println!("====> Reprocessing <====");
+
+    let reprocessed_path = format!("{}.reprocessed", &config.db_path);
+    let conn = db::open(&reprocessed_path).expect("Unable to open the database");
+    db::init_db(&conn).expect("Unable to initialize database");
+    let report = reprocess::reprocess(config, &raw_archive, &conn).expect("Failed to reprocess the raw archive");
+    println!("====> Replayed {} responses into {}, skipped {}", report.replayed, &reprocessed_path, report.skipped);
+}
+
+/// Waits until the next poll of a stream of `user_id`, after a poll that sent `requests` requests
+/// and found something new or not, and returns whether the worker was stopped meanwhile
+fn wait_next_poll(stop: &StopSignal, schedule: &AdaptiveInterval, conn: &Connection, user_id: &str, found_new: bool, requests: u16) -> bool {
+    stop.wait(next_poll_delay(schedule, conn, user_id, found_new, requests))
+}
+
+/// Delay until the next poll of a stream of `user_id` as [`wait_next_poll`] waits it. Without the
+/// account's activity the stream is polled at its longest interval.
+fn next_poll_delay(schedule: &AdaptiveInterval, conn: &Connection, user_id: &str, found_new: bool, requests: u16) -> time::Duration {
+    let now = Utc::now();
+    let activity = ActivityProfile::load(conn, user_id, now).unwrap_or_else(|e| {
+        log::warn!("{}: failed to read the account's activity: {}", user_id, e);
+        ActivityProfile::default()
+    });
+    schedule.next(&activity, now, found_new, requests)
+}
+
+/// Fetches and writes the profile of `username`, archiving changed images. A failed poll is only
+/// logged, the profile is fetched again on the next poll.
+fn poll_profile(config: &Config, conn: &Connection, writer: &DbWriter, media_archiver: Option<&MediaArchiver>, username: &str, user_id: &str) {
+    let fetched_profile = match UserInfoFetcher::new(username).fetch(config) {
+        Ok(fetched_profile) => fetched_profile,
+        Err(e) => {
+            log::warn!("{}: failed to poll the profile: {}", username, e);
+            return;
+        }
+    };
+    log::info!(
+        "{}: get user profile => user-id: {}, user_name: {}, name: {}", 
+        username, &fetched_profile.user.id, &fetched_profile.user.username, &fetched_profile.user.name
+    );
+    if let Err(e) = writer.write(move |conn| SqliteStore::new(conn).write_profile(&fetched_profile, &TaskType::Monitoring)) {
+        log::warn!("{}: failed to write the profile: {}", username, e);
+        return;
+    }
+    archive_profile_images(writer, media_archiver, conn, user_id);
+}
+
+/// Fetches and writes the likes of `user_id` newer than the newest stored one, returning whether
+/// there were any and the number of requests sent. Once `stop` is given nothing is written; a
+/// failed poll is only logged and counts as one request.
+fn poll_likes(config: &Config, conn: &Connection, writer: &DbWriter, stop: &StopSignal, username: &str, user_id: &str) -> (bool, u16) {
+    let fetch_result = SqliteStore::new(conn).newest_like_id(user_id)
+        .and_then(|latest_like_id| LikeFetcher::new(user_id, latest_like_id.as_deref()).fetch(config, stop));
+    if stop.is_stopped() {
+        return (false, 0);
+    }
+    let ((liked_tweet_records, liked_tweets, liked_users), requests) = match fetch_result {
+        Ok(fetch_result) => fetch_result,
+        Err(e) => {
+            log::warn!("{}: failed to poll likes: {}", username, e);
+            return (false, 1);
+        }
+    };
+    let found_new = !liked_tweet_records.is_empty();
+    for liked_tweet_record in liked_tweet_records.iter() {
+        log::info!(
+            "{}: get new liked: text: {}, author: {}", 
+            username, &liked_tweet_record.tweet.text, &liked_tweet_record.author.username
+        );
+    }
+    let written = writer.write(move |conn| {
+        let store = SqliteStore::new(conn);
+        for liked_user in liked_users.into_iter() {
+            store.write_user(&liked_user)?;
+        }
+        for liked_tweet in liked_tweets.into_iter() {
+            store.write_dict_tweet(&liked_tweet)?;
+        }
+        for liked_tweet_record in liked_tweet_records.into_iter() {
+            store.write_like(&liked_tweet_record, Placement::Newer)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        log::warn!("{}: failed to write likes: {}", username, e);
+    }
+    (found_new, requests)
+}
+
+/// Fetches and writes the mentions of `user_id` newer than the newest stored one, returning
+/// whether there were any and the number of requests sent. Once `stop` is given nothing is
+/// written; a failed poll is only logged and counts as one request.
+fn poll_mentions(config: &Config, conn: &Connection, writer: &DbWriter, stop: &StopSignal, username: &str, user_id: &str) -> (bool, u16) {
+    let fetch_result = Mention::newest_id(conn, user_id)
+        .and_then(|latest_mention_id| MentionsFetcher::new(user_id, latest_mention_id.as_deref()).fetch(config, stop));
+    if stop.is_stopped() {
+        return (false, 0);
+    }
+    let ((mentions, mentioning_tweets, mentioning_users), requests) = match fetch_result {
+        Ok(fetch_result) => fetch_result,
+        Err(e) => {
+            log::warn!("{}: failed to poll mentions: {}", username, e);
+            return (false, 1);
+        }
+    };
+    let found_new = !mentions.is_empty();
+    for mention in mentions.iter() {
+        log::info!(
+            "{}: get new mention => text: {}, author: {}, type: {:?}", 
+            username, &mention.tweet.text, &mention.author.username, &mention.tweet_type
+        );
+    }
+    let written = writer.write(move |conn| {
+        let store = SqliteStore::new(conn);
+        for mentioning_user in mentioning_users.into_iter() {
+            store.write_user(&mentioning_user)?;
+        }
+        for mentioning_tweet in mentioning_tweets.into_iter() {
+            store.write_dict_tweet(&mentioning_tweet)?;
+        }
+        for mention in mentions.into_iter() {
+            mention.write_to_db(conn)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        log::warn!("{}: failed to write mentions: {}", username, e);
+    }
+    (found_new, requests)
+}
+
+/// Fetches the users `user_id` follows and writes the follows and unfollows since the last poll,
+/// returning whether there were any and the number of requests sent. Once `stop` is given nothing
+/// is written; a failed poll is only logged and counts as one request.
+fn poll_following(config: &Config, conn: &Connection, writer: &DbWriter, stop: &StopSignal, username: &str, user_id: &str) -> (bool, u16) {
+    let fetch_result = SqliteStore::new(conn).current_following_ids(user_id)
+        .and_then(|following_ids| FollowingFetcher::new(user_id, Some(following_ids)).fetch(config, conn, stop));
+    if stop.is_stopped() {
+        return (false, 0);
+    }
+    let (following_records, followed_users, requests) = match fetch_result {
+        Ok(fetch_result) => fetch_result,
+        Err(e) => {
+            log::warn!("{}: failed to poll following: {}", username, e);
+            return (false, 1);
+        }
+    };
+    let found_new = !following_records.is_empty();
+    for following_record in following_records.iter() {
+        log::info!(
+            "{}: get new following action => username: {}, action: {:?}", 
+            username, &following_record.followed_user.username, &following_record.action
+        );
+    }
+    let written = writer.write(move |conn| {
+        let store = SqliteStore::new(conn);
+        for followed_user in followed_users.into_iter() {
+            store.write_user(&followed_user)?;
+        }
+        for following_record in following_records.into_iter() {
+            store.write_following(&following_record, Placement::Newer)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        log::warn!("{}: failed to write following records: {}", username, e);
+    }
+    (found_new, requests)
+}
+
+/// Fetches and writes the tweets of `user_id` newer than the newest stored one, returning whether
+/// there were any and the number of requests sent. Once `stop` is given nothing is written; a
+/// failed poll is only logged and counts as one request.
+fn poll_tweets(config: &Config, conn: &Connection, writer: &DbWriter, media_archiver: Option<&MediaArchiver>, stop: &StopSignal, username: &str, user_id: &str) -> (bool, u16) {
+    let fetch_result = SqliteStore::new(conn).newest_tweet_id(user_id)
+        .and_then(|latest_tweet_id| TweetFetcher::new(user_id, latest_tweet_id.as_deref()).fetch(config, stop));
+    if stop.is_stopped() {
+        return (false, 0);
+    }
+    let (fetch_result, requests) = match fetch_result {
+        Ok(fetch_result) => fetch_result,
+        Err(e) => {
+            log::warn!("{}: failed to poll tweets: {}", username, e);
+            return (false, 1);
+        }
+    };
+    let found_new = !fetch_result.0.is_empty();
+    write_tweets(writer, media_archiver, username, fetch_result);
+    (found_new, requests)
+}
+
+/// Writes fetched or streamed tweets of the monitored user with what they reference. A failed write
+/// is only logged, polling picks the tweets up again from the newest stored one.
+fn write_tweets(writer: &DbWriter, media_archiver: Option<&MediaArchiver>, username: &str, (tweets, ref_tweets, ref_users): TweetFetchResult) {
+    for tweet in tweets.iter() {
+        log::info!(
+            "{}: get new tweet => text: {}, type: {:?}, created at: {}", 
+            username, &tweet.text, &tweet.tweet_type, &tweet.created_at
+        );
+    }
+    let written_tweets = tweets.clone();
+    let written = writer.write(move |conn| {
+        let store = SqliteStore::new(conn);
+        for ref_user in ref_users.into_iter() {
+            store.write_user(&ref_user)?;
+        }
+        for ref_tweet in ref_tweets.into_iter() {
+            store.write_dict_tweet(&ref_tweet)?;
+        }
+        for tweet in written_tweets.iter() {
+            store.write_tweet(tweet)?;
+            record_announcements(conn, tweet)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        log::warn!("{}: failed to write tweets: {}", username, e);
+        return;
+    }
+
+    // downloads do not hold up the writer
+    let archived_media = download_media(media_archiver, &tweets);
+    if !archived_media.is_empty() {
+        if let Err(e) = writer.write(move |conn| record_archived_media(conn, &archived_media)) {
+            log::warn!("{}: failed to record archived media: {}", username, e);
+        }
+    }
+}
 
-                user_profile_handler.join().unwrap();
-                user_tweet_handler.join().unwrap();
-                user_liked_handler.join().unwrap();
-                user_follow_handler.join().unwrap();
+/// Downloads the media of `tweets` not archived yet when archiving is configured, returning the
+/// archived file of every media key. Failed downloads are only logged, the media stays
+/// unarchived in `media_dict`.
+fn download_media(media_archiver: Option<&MediaArchiver>, tweets: &[FetchedTweet]) -> Vec<(String, ArchivedFile)> {
+    let mut archived_media: Vec<(String, ArchivedFile)> = Vec::new();
+    if let Some(media_archiver) = media_archiver {
+        for tweet in tweets.iter() {
+            for media in tweet.media.iter().flatten() {
+                match media_archiver.download_media(media) {
+                    Ok(Some(archived_file)) => { archived_media.push((media.media_key.clone(), archived_file)); }
+                    Ok(None) => {}
+                    Err(e) => { log::warn!("failed to archive media {} of tweet {}: {}", &media.media_key, &tweet.id, e); }
+                }
             }
         }
     }
+    archived_media
+}
+
+/// Records downloaded media in `media_dict`, once the tweets using it are written
+fn record_archived_media(conn: &Connection, archived_media: &[(String, ArchivedFile)]) -> Result<(), Box<dyn Error>> {
+    let store = SqliteStore::new(conn);
+    for (media_key, archived_file) in archived_media.iter() {
+        store.set_media_archived(media_key, &archived_file.content_hash, &archived_file.local_path)?;
+    }
+    Ok(())
+}
+
+/// Downloads the images of recorded avatar and banner changes when archiving is configured. 
+/// Failed downloads are only logged and retried after the next profile fetch.
+fn archive_profile_images(writer: &DbWriter, media_archiver: Option<&MediaArchiver>, conn: &Connection, user_id: &str) {
+    if let Some(media_archiver) = media_archiver {
+        let archived = media_archiver.download_profile_images(conn, user_id).and_then(|(_, images)| {
+            let user_id = user_id.to_string();
+            writer.write(move |conn| {
+                for (field, image_url, archived_file) in images.iter() {
+                    ProfileChange::set_archived(conn, &user_id, *field, image_url, archived_file)?;
+                }
+                Ok(())
+            })
+        });
+        if let Err(e) = archived {
+            log::warn!("failed to archive profile images of {}: {}", user_id, e);
+        }
+    }
+}
+
+/// Stores the stream links found in a written tweet
+fn record_announcements(conn: &Connection, tweet: &FetchedTweet) -> Result<(), Box<dyn Error>> {
+    for announcement in StreamAnnouncement::extract(tweet) {
+        log::info!(
+            "{}: get stream {:?} => {}, scheduled at: {:?}", 
+            &tweet.author_id, &announcement.kind, &announcement.url, &announcement.scheduled_at
+        );
+        announcement.write_to_db(conn)?;
+    }
+    Ok(())
+}
+
+/// Looks placeholder tweets and users up again. Failed lookups are only logged, the placeholders
+/// are retried on the next run.
+fn repair_placeholders(config: &Config, conn: &Connection, writer: &DbWriter) {
+    match repair::repair_placeholders(config, conn, writer, 100) {
+        Ok(report) => log::info!(
+            "repaired placeholders => restored tweets: {}, restored users: {}, unavailable tweets: {}, unavailable users: {}, still missing: {}", 
+            report.restored_tweets, report.restored_users, report.unavailable_tweets, report.unavailable_users, report.still_missing
+        ), 
+        Err(e) => log::warn!("failed to repair placeholders: {}", e)
+    }
+}
+
+/// Polls who engaged with the user's newest tweets within the budget of `engagement_polling`,
+/// returning whether there were new engagements and the number of requests sent. Failed polls are
+/// only logged and count as one request, the tweets are polled again on the next run.
+fn poll_engagement(config: &Config, conn: &Connection, writer: &DbWriter, stop: &StopSignal, user_id: &str, engagement_polling: &StreamPolling) -> (bool, u16) {
+    let (recent_tweets, request_budget) = (engagement_polling.recent_tweets, engagement_polling.request_budget);
+    match engagement::poll_engagement(config, conn, writer, stop, user_id, recent_tweets, request_budget) {
+        Ok(report) => {
+            log::info!(
+                "polled engagement => tweets: {}, new engagements: {}, requests: {}", 
+                report.polled_tweets, report.new_engagements, report.requests
+            );
+            (report.new_engagements > 0, u16::try_from(report.requests).unwrap_or(u16::MAX))
+        }
+        Err(e) => {
+            log::warn!("failed to poll engagement: {}", e);
+            (false, 1)
+        }
+    }
 }
+
+/// Fetches the hits of every saved search newer than its newest stored hit. Failed searches are
+/// only logged, they continue from the same hit on the next run.
+fn capture_saved_searches(config: &Config, conn: &Connection, writer: &DbWriter, stop: &StopSignal) {
+    for saved_search in config.saved_searches.iter() {
+        let fetch_result = SavedSearchHit::newest_id(conn, &saved_search.name)
+            .and_then(|newest_id| SearchFetcher::new(saved_search, newest_id.as_deref()).fetch(config, stop));
+        if stop.is_stopped() {
+            break;
+        }
+        let (hits, hit_tweets, hit_users) = match fetch_result {
+            Ok(fetch_result) => fetch_result, 
+            Err(e) => {
+                log::warn!("{}: failed to search: {}", &saved_search.name, e);
+                continue;
+            }
+        };
+        for hit in hits.iter() {
+            log::info!(
+                "{}: get new search hit => text: {}, author: {}", 
+                &saved_search.name, &hit.tweet.text, &hit.author.username
+            );
+        }
+        let written = writer.write(move |conn| {
+            let store = SqliteStore::new(conn);
+            for hit_user in hit_users.into_iter() {
+                store.write_user(&hit_user)?;
+            }
+            for hit_tweet in hit_tweets.into_iter() {
+                store.write_dict_tweet(&hit_tweet)?;
+            }
+            for hit in hits.into_iter() {
+                hit.write_to_db(conn)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            log::warn!("{}: failed to write search hits: {}", &saved_search.name, e);
+        }
+    }
+}
\ No newline at end of file
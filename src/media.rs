@@ -0,0 +1,183 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use reqwest::blocking::Client;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+
+use crate::profile::{ProfileChange, ProfileField};
+use crate::query_result::Media;
+
+/// A file saved in the archive directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedFile {
+    /// Hex SHA-256 of the file content
+    pub content_hash: String,
+    pub local_path: String
+}
+
+/// A downloaded avatar or banner with the field and url it was downloaded for
+pub type ProfileImage = (ProfileField, String, ArchivedFile);
+
+/// Downloads media into a content-addressed directory: every file is stored once as
+/// `<archive_dir>/<first two hash digits>/<hash>.<extension>`, however many tweets use it.
+pub struct MediaArchiver {
+    archive_dir: PathBuf,
+    client: Client
+}
+
+impl MediaArchiver {
+    pub fn new(archive_dir: &str) -> MediaArchiver {
+        MediaArchiver {
+            archive_dir: PathBuf::from(archive_dir),
+            client: Client::builder().build().expect("error in client builder")
+        }
+    }
+
+    /// Downloads the file of `media` unless it was archived before, leaving it to the caller to
+    /// record the archived file in `media_dict`. Returns `None` for media without a downloadable
+    /// url.
+    pub fn download_media(&self, media: &Media) -> Result<Option<ArchivedFile>, Box<dyn Error>> {
+        if media.content_hash.is_some() {
+            return Ok(None);
+        }
+        let url = match &media.url {
+            Some(url) => url,
+            None => { return Ok(None); }
+        };
+        Ok(Some(self.download(&full_size_url(url, &media.media_type))?))
+    }
+
+    /// Downloads the images of `user_id`'s avatar and banner changes not archived yet, the new 
+    /// image of every change and the old one when it can still be fetched, leaving it to the 
+    /// caller to reference them from `profile_change`. Returns the number of changes with every 
+    /// image's field and url. A new image failing to download is logged and left for the next poll.
+    pub fn download_profile_images(&self, conn: &Connection, user_id: &str) -> Result<(usize, Vec<ProfileImage>), Box<dyn Error>> {
+        let changes = ProfileChange::get_unarchived_images(conn, user_id)?;
+        let mut images: Vec<ProfileImage> = Vec::new();
+        for change in changes.iter() {
+            if let (Some(old_url), None) = (&change.old_value, &change.old_archived) {
+                // the replaced image is often deleted right away
+                if let Ok(archived_file) = self.download(&full_size_profile_url(old_url, change.field)) {
+                    images.push((change.field, old_url.clone(), archived_file));
+                }
+            }
+            if let Some(new_url) = &change.new_value {
+                match self.download(&full_size_profile_url(new_url, change.field)) {
+                    Ok(archived_file) => { images.push((change.field, new_url.clone(), archived_file)); }
+                    Err(e) => { log::warn!("Failed to archive the {} {} of {}: {}", change.field.as_str(), new_url, user_id, e); }
+                }
+            }
+        }
+        Ok((changes.len(), images))
+    }
+
+    /// Downloads `url` into the archive
+    pub fn download(&self, url: &str) -> Result<ArchivedFile, Box<dyn Error>> {
+        let response = self.client.get(url).send()?.error_for_status()?;
+        let bytes = response.bytes()?;
+        self.store(&bytes, url_extension(url).as_deref())
+    }
+
+    /// Saves `content` into the archive, keeping an existing file with the same content
+    pub fn store(&self, content: &[u8], extension: Option<&str>) -> Result<ArchivedFile, Box<dyn Error>> {
+        let content_hash = format!("{:x}", Sha256::digest(content));
+        let file_name = match extension {
+            Some(extension) => format!("{content_hash}.{extension}"),
+            None => content_hash.clone()
+        };
+        let sub_dir = self.archive_dir.join(&content_hash[..2]);
+        let local_path = sub_dir.join(file_name);
+
+        if !local_path.exists() {
+            fs::create_dir_all(&sub_dir)?;
+            // write under a temporary name first so an interrupted download never leaves a
+            // truncated file behind the final name
+            let partial_path = local_path.with_extension("partial");
+            fs::write(&partial_path, content)?;
+            fs::rename(&partial_path, &local_path)?;
+        }
+
+        Ok(ArchivedFile {
+            content_hash,
+            local_path: local_path.to_string_lossy().to_string()
+        })
+    }
+}
+
+/// Photos are served downsized unless the original size is requested
+fn full_size_url(url: &str, media_type: &str) -> String {
+    if media_type == "photo" && url.starts_with("https://pbs.twimg.com/") && !url.contains('?') {
+        format!("{url}?name=orig")
+    } else {
+        url.to_string()
+    }
+}
+
+/// Avatars are served as 48x48 thumbnails named `*_normal.<ext>`, the original drops the suffix; 
+/// banners are served at their largest size under `/1500x500`
+fn full_size_profile_url(url: &str, field: ProfileField) -> String {
+    match field {
+        ProfileField::ProfileImageUrl => {
+            for size_suffix in ["_normal.", "_bigger.", "_mini."] {
+                if let Some(suffix_start) = url.rfind(size_suffix) {
+                    return format!("{}.{}", &url[..suffix_start], &url[suffix_start + size_suffix.len()..]);
+                }
+            }
+            url.to_string()
+        }
+        ProfileField::ProfileBannerUrl if url.starts_with("https://pbs.twimg.com/profile_banners/") && url.matches('/').count() == 5 => {
+            format!("{url}/1500x500")
+        }
+        _ => url.to_string()
+    }
+}
+
+/// Extension of the last path segment of `url`, ignoring the query string
+fn url_extension(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let file_name = path.rsplit('/').next()?;
+    Path::new(file_name).extension().map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_helpers() {
+        assert_eq!(url_extension("https://pbs.twimg.com/media/abc.jpg"), Some("jpg".to_string()));
+        assert_eq!(url_extension("https://video.twimg.com/ext_tw_video/1/pu/vid/720x1280/x.MP4?tag=12"), Some("mp4".to_string()));
+        assert_eq!(url_extension("https://example.com/file"), None);
+        assert_eq!(full_size_url("https://pbs.twimg.com/media/abc.jpg", "photo"), "https://pbs.twimg.com/media/abc.jpg?name=orig");
+        assert_eq!(full_size_url("https://video.twimg.com/x.mp4", "video"), "https://video.twimg.com/x.mp4");
+        assert_eq!(
+            full_size_profile_url("https://pbs.twimg.com/profile_images/1/abc_normal.jpg", ProfileField::ProfileImageUrl),
+            "https://pbs.twimg.com/profile_images/1/abc.jpg"
+        );
+        assert_eq!(
+            full_size_profile_url("https://pbs.twimg.com/profile_banners/975275878673408001/1672531200", ProfileField::ProfileBannerUrl),
+            "https://pbs.twimg.com/profile_banners/975275878673408001/1672531200/1500x500"
+        );
+        assert_eq!(
+            full_size_profile_url("https://pbs.twimg.com/profile_banners/975275878673408001/1672531200/600x200", ProfileField::ProfileBannerUrl),
+            "https://pbs.twimg.com/profile_banners/975275878673408001/1672531200/600x200"
+        );
+    }
+
+    #[test]
+    fn test_store() {
+        let archive_dir = std::env::temp_dir().join(format!("sui_media_archive_{}", std::process::id()));
+        let archiver = MediaArchiver::new(&archive_dir.to_string_lossy());
+
+        let stored = archiver.store(b"suisei", Some("jpg")).unwrap();
+        let stored_again = archiver.store(b"suisei", Some("jpg")).unwrap();
+        assert_eq!(stored, stored_again);
+        assert_eq!(stored.content_hash, format!("{:x}", Sha256::digest(b"suisei")));
+        assert!(stored.local_path.ends_with(&format!("{}.jpg", stored.content_hash)));
+        assert_eq!(fs::read(&stored.local_path).unwrap(), b"suisei");
+
+        fs::remove_dir_all(&archive_dir).unwrap();
+    }
+}
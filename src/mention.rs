@@ -0,0 +1,222 @@
+use std::error::Error;
+
+use rusqlite::{Connection, named_params, OptionalExtension};
+
+use crate::query_result::{BasicTweet, BasicUserDetail, TweetType};
+use crate::store::SqliteStore;
+
+/// Common table expression selecting one page of `user_mentioned` rows of `:user_id`, newest first
+const USER_MENTIONED_PAGE: &str = "WITH page AS (
+    SELECT * FROM user_mentioned WHERE user_id = :user_id
+    ORDER BY length(tweet_id) DESC, tweet_id DESC LIMIT :limit OFFSET :offset
+)";
+
+/// A tweet of another account mentioning a monitored user
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mention {
+    /// The mentioned monitored user
+    pub user_id: String,
+    pub created_at: String,
+    pub tweet: BasicTweet,
+    pub author: BasicUserDetail,
+    /// The tweet the mention replies to, retweets or quotes
+    pub tweet_type: TweetType
+}
+
+impl Mention {
+    /// Stores the mention; the tweet, its author and its reference go to `tweet_dict` and
+    /// `user_dict` with the rest of the fetch result
+    pub fn write_to_db(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        let (tweet_type, ref_tweet_id) = match &self.tweet_type {
+            TweetType::Tweet => ("tweet", None),
+            TweetType::Reply { tweet, author: _ } => ("reply", Some(tweet.id.as_str())),
+            TweetType::Retweet { tweet, author: _ } => ("retweet", Some(tweet.id.as_str()))
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO user_mentioned
+            (user_id, tweet_id, time, author_id, tweet_type, ref_tweet_id)
+            VALUES (:user_id, :tweet_id, :time, :author_id, :tweet_type, :ref_tweet_id)",
+            named_params! {
+                ":user_id": &self.user_id,
+                ":tweet_id": &self.tweet.id,
+                ":time": &self.created_at,
+                ":author_id": &self.author.id,
+                ":tweet_type": tweet_type,
+                ":ref_tweet_id": ref_tweet_id
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Id of the newest stored mention of `user_id`, to fetch newer ones from
+    pub fn newest_id(conn: &Connection, user_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let newest_id: Option<String> = conn.query_row(
+            "SELECT tweet_id FROM user_mentioned WHERE user_id = ?
+            ORDER BY length(tweet_id) DESC, tweet_id DESC LIMIT 1",
+            [user_id],
+            |row| row.get(0)
+        ).optional()?;
+        Ok(newest_id)
+    }
+
+    /// Reads a page of `user_id`'s mentions, newest first
+    pub fn get_records(conn: &Connection, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<Mention>, Box<dyn Error>> {
+        let page_params = named_params! {
+            ":user_id": user_id,
+            ":limit": max_results.map(i64::from).unwrap_or(-1),
+            ":offset": offset
+        };
+
+        let mut mention_stmt = conn.prepare(&format!(
+            "{USER_MENTIONED_PAGE}
+            SELECT page.time, page.tweet_type,
+                tweet.tweet_id, tweet.author_id, tweet.text, tweet.conversation_id, tweet.replied_to_id,
+                author.user_id, author.username, author.name,
+                ref_tweet.tweet_id, ref_tweet.author_id, ref_tweet.text, ref_tweet.conversation_id, ref_tweet.replied_to_id,
+                ref_user.username, ref_user.name
+            FROM page
+            LEFT JOIN tweet_dict AS tweet ON tweet.tweet_id = page.tweet_id
+            LEFT JOIN user_dict AS author ON author.user_id = page.author_id
+            LEFT JOIN tweet_dict AS ref_tweet ON ref_tweet.tweet_id = page.ref_tweet_id
+            LEFT JOIN user_dict AS ref_user ON ref_user.user_id = ref_tweet.author_id
+            ORDER BY length(page.tweet_id) DESC, page.tweet_id DESC"
+        ))?;
+        let query_results = mention_stmt.query_map(page_params, |row| {
+            let tweet_at = |column: usize| -> rusqlite::Result<Option<BasicTweet>> {
+                Ok(match (row.get::<_, Option<String>>(column)?, row.get::<_, Option<String>>(column + 1)?, row.get::<_, Option<String>>(column + 2)?) {
+                    (Some(id), Some(author_id), Some(text)) => Some(BasicTweet {
+                        id,
+                        author_id,
+                        text,
+                        hashtags: None,
+                        urls: None,
+                        conversation_id: row.get(column + 3)?,
+                        replied_to_id: row.get(column + 4)?
+                    }),
+                    _ => None
+                })
+            };
+            let author = match (row.get::<_, Option<String>>(7)?, row.get::<_, Option<String>>(8)?, row.get::<_, Option<String>>(9)?) {
+                (Some(id), Some(username), Some(name)) => Some(BasicUserDetail { id, username, name }),
+                _ => None
+            };
+            let ref_tweet = tweet_at(10)?;
+            let ref_user = match (&ref_tweet, row.get::<_, Option<String>>(15)?, row.get::<_, Option<String>>(16)?) {
+                (Some(ref_tweet), Some(username), Some(name)) => Some(BasicUserDetail { id: ref_tweet.author_id.clone(), username, name }),
+                // authors of placeholder tweets are unknown
+                (Some(ref_tweet), _, _) => Some(BasicUserDetail::placeholder(&ref_tweet.author_id)),
+                _ => None
+            };
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, tweet_at(2)?, author, ref_tweet, ref_user))
+        })?;
+        let mut queried_mentions = Vec::new();
+        for query_result in query_results {
+            queried_mentions.push(query_result?);
+        }
+
+        let mut entity_ids: Vec<String> = Vec::new();
+        for (_, _, tweet, _, ref_tweet, _) in queried_mentions.iter() {
+            entity_ids.extend(tweet.iter().chain(ref_tweet.iter()).map(|tweet| tweet.id.clone()));
+        }
+        let store = SqliteStore::new(conn);
+        let hashtag_map = store.get_hashtag_map(&entity_ids)?;
+        let url_map = store.get_url_map(&entity_ids)?;
+
+        let mut mentions: Vec<Mention> = Vec::new();
+        for (created_at, tweet_type_str, tweet, author, ref_tweet, ref_user) in queried_mentions.into_iter() {
+            let (mut tweet, author) = match (tweet, author) {
+                (Some(tweet), Some(author)) => (tweet, author),
+                _ => { return Err(Box::new(rusqlite::Error::QueryReturnedNoRows)); }
+            };
+            tweet.hashtags = hashtag_map.get(&tweet.id).cloned();
+            tweet.urls = url_map.get(&tweet.id).cloned();
+
+            let tweet_type = match (tweet_type_str.as_str(), ref_tweet, ref_user) {
+                ("tweet", _, _) => TweetType::Tweet,
+                (ref_type @ ("reply" | "retweet"), Some(mut ref_tweet), Some(ref_user)) => {
+                    ref_tweet.hashtags = hashtag_map.get(&ref_tweet.id).cloned();
+                    ref_tweet.urls = url_map.get(&ref_tweet.id).cloned();
+                    if ref_type == "reply" {
+                        TweetType::Reply { tweet: ref_tweet, author: ref_user }
+                    } else {
+                        TweetType::Retweet { tweet: ref_tweet, author: ref_user }
+                    }
+                }
+                _ => { return Err(Box::new(rusqlite::Error::QueryReturnedNoRows)); }
+            };
+
+            mentions.push(Mention {
+                user_id: user_id.to_string(),
+                created_at,
+                tweet,
+                author,
+                tweet_type
+            });
+        }
+
+        Ok(mentions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::store::Store;
+
+    use super::*;
+
+    fn basic_tweet(id: &str, author_id: &str, text: &str) -> BasicTweet {
+        BasicTweet {
+            id: id.to_string(),
+            author_id: author_id.to_string(),
+            text: text.to_string(),
+            hashtags: None,
+            urls: None,
+            conversation_id: Some("10".to_string()),
+            replied_to_id: None
+        }
+    }
+
+    #[test]
+    fn test_mentions() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let suisei = BasicUserDetail { id: "0".to_string(), username: "suisei_hosimati".to_string(), name: "Suisei".to_string() };
+        let towa = BasicUserDetail { id: "2".to_string(), username: "tokoyami_towa".to_string(), name: "Towa".to_string() };
+        let own_tweet = basic_tweet("10", "0", "Stellar Stellar");
+        let mut reply = basic_tweet("11", "2", "@suisei_hosimati comet #hoshimachi");
+        reply.hashtags = Some(vec!["hoshimachi".to_string()]);
+        reply.replied_to_id = Some("10".to_string());
+        let greeting = basic_tweet("9", "2", "@suisei_hosimati hi");
+
+        for user in [&suisei, &towa] {
+            SqliteStore::new(&conn).write_user(user).unwrap();
+        }
+        for tweet in [&own_tweet, &reply, &greeting] {
+            SqliteStore::new(&conn).write_dict_tweet(tweet).unwrap();
+        }
+        let reply_mention = Mention {
+            user_id: "0".to_string(),
+            created_at: "2023-01-02T00:00:00.000Z".to_string(),
+            tweet: reply.clone(),
+            author: towa.clone(),
+            tweet_type: TweetType::Reply { tweet: own_tweet.clone(), author: suisei.clone() }
+        };
+        let greeting_mention = Mention {
+            user_id: "0".to_string(),
+            created_at: "2023-01-01T00:00:00.000Z".to_string(),
+            tweet: greeting.clone(),
+            author: towa.clone(),
+            tweet_type: TweetType::Tweet
+        };
+        greeting_mention.write_to_db(&conn).unwrap();
+        reply_mention.write_to_db(&conn).unwrap();
+        reply_mention.write_to_db(&conn).unwrap();
+
+        assert_eq!(Mention::newest_id(&conn, "0").unwrap(), Some("11".to_string()));
+        assert_eq!(Mention::get_records(&conn, "0", None, 0).unwrap(), vec![reply_mention, greeting_mention.clone()]);
+        assert_eq!(Mention::get_records(&conn, "0", Some(1), 1).unwrap(), vec![greeting_mention]);
+        assert!(Mention::get_records(&conn, "2", None, 0).unwrap().is_empty());
+    }
+}
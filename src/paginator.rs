@@ -0,0 +1,286 @@
+use std::error::Error;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+use reqwest::blocking::RequestBuilder;
+use serde::de::DeserializeOwned;
+
+use crate::api_response::Response;
+use crate::configuration;
+use crate::errors::PagingStopped;
+use crate::raw_archive::RawArchive;
+use crate::transport::Transport;
+use crate::worker::StopSignal;
+
+/// Pages of a fetcher, requested lazily
+pub type PageIter<'a, P> = Box<dyn Iterator<Item = Result<FetchedPage<P>, Box<dyn Error>>> + 'a>;
+
+/// A parsed page with the token of the page after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedPage<P> {
+    pub records: P,
+    /// `None` for the last page, including a page the paging stopped at
+    pub next_token: Option<String>
+}
+
+/// What a page was parsed into; `Break` when the pages after it are not needed, e.g. once a page
+/// reached the newest stored record
+pub type PageStep<P> = ControlFlow<P, P>;
+
+type ParsePage<'a, T, P> = Box<dyn FnMut(&Response<Vec<T>>) -> Result<PageStep<P>, Box<dyn Error>> + 'a>;
+
+/// A fetcher of a paginated endpoint
+pub trait Fetcher {
+    /// What one page is parsed into
+    type Page;
+
+    /// Pages newest first, starting at the page of `page_token`. A page is only requested once
+    /// the previous one has been consumed, so callers can write every page as it arrives instead
+    /// of buffering the whole history. Once `stop` is given the paging ends with a
+    /// [`PagingStopped`] error.
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, Self::Page>;
+
+    /// Parses an archived page fetched at `fetched_at` the way fetched pages are parsed
+    fn parse_archived(&self, conf: &configuration::Config, body: &str, fetched_at: &str) -> Result<Self::Page, Box<dyn Error>>;
+
+    /// Pages newest first, from the first one
+    fn pages<'a>(&'a self, conf: &'a configuration::Config, stop: &StopSignal) -> PageIter<'a, Self::Page> {
+        self.pages_from(conf, None, stop)
+    }
+}
+
+/// Follows the `next_token` of a paginated endpoint, parsing every page with `parse`
+pub struct Paginator<'a, T, P> {
+    request: RequestBuilder,
+    token_param: &'static str,
+    page_token: Option<String>,
+    delay: Duration,
+    pause: Option<(u16, Duration)>,
+    requested: u16,
+    finished: bool,
+    transport: Transport,
+    stop: StopSignal,
+    /// Where the raw pages are archived, with the endpoint and key they are archived under
+    archive: Option<(RawArchive, &'static str, String)>,
+    parse: ParsePage<'a, T, P>
+}
+
+impl<'a, T, P> Paginator<'a, T, P> {
+    /// Pages through `request` over HTTP with the `pagination_token` query parameter, without
+    /// delay
+    pub fn new(request: RequestBuilder, parse: impl FnMut(&Response<Vec<T>>) -> Result<PageStep<P>, Box<dyn Error>> + 'a) -> Paginator<'a, T, P> {
+        Paginator {
+            request,
+            token_param: "pagination_token",
+            page_token: None,
+            delay: Duration::ZERO,
+            pause: None,
+            requested: 0,
+            finished: false,
+            transport: Transport::Http,
+            stop: StopSignal::new(),
+            archive: None,
+            parse: Box::new(parse)
+        }
+    }
+
+    /// Start at the page of `page_token` instead of the first one
+    pub fn start_at(mut self, page_token: Option<String>) -> Self {
+        self.page_token = page_token;
+        self
+    }
+
+    /// Query parameter the endpoint takes the token in, e.g. `next_token` for search
+    pub fn token_param(mut self, token_param: &'static str) -> Self {
+        self.token_param = token_param;
+        self
+    }
+
+    /// Wait between two requests, unless they are replayed
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Wait `pause` instead of the delay after every `pages` requests, unless they are replayed
+    pub fn pause_every(mut self, pages: u16, pause: Duration) -> Self {
+        self.pause = Some((pages, pause));
+        self
+    }
+
+    /// Send the requests with `transport` instead of over HTTP, e.g. to replay a cassette
+    pub fn transport(mut self, transport: &Transport) -> Self {
+        self.transport = transport.clone();
+        self
+    }
+
+    /// Stop paging once `stop` is given, also during a delay, ending with a [`PagingStopped`]
+    /// error instead of the next page
+    pub fn stop_on(mut self, stop: &StopSignal) -> Self {
+        self.stop = stop.clone();
+        self
+    }
+
+    /// Archive every raw page under `endpoint` and `key` when `conf` enables it
+    pub fn archive(mut self, conf: &configuration::Config, endpoint: &'static str, key: &str) -> Self {
+        self.archive = RawArchive::from_config(conf).map(|raw_archive| (raw_archive, endpoint, key.to_string()));
+        self
+    }
+
+    fn request_page(&mut self) -> Result<PageStep<P>, Box<dyn Error>>
+    where
+        T: DeserializeOwned
+    {
+        let mut request = self.request.try_clone().expect("the request should be cloned");
+        if let Some(page_token) = &self.page_token {
+            request = request.query(&[(self.token_param, page_token)]);
+        }
+        let response = self.transport.send(request)?;
+        self.requested += 1;
+        if let Some((raw_archive, endpoint, key)) = &self.archive {
+            if let Err(e) = raw_archive.store(endpoint, key, &response) {
+                log::warn!("Failed to archive a {} response of {}: {}", endpoint, key, e);
+            }
+        }
+        let page: Response<Vec<T>> = serde_json::from_str(&response)?;
+
+        self.page_token = page.meta.next_token.clone();
+        (self.parse)(&page)
+    }
+}
+
+impl<T: DeserializeOwned, P> Iterator for Paginator<'_, T, P> {
+    type Item = Result<FetchedPage<P>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        // replayed cassettes are not rate limited
+        let wait = if self.requested == 0 || matches!(self.transport, Transport::Replay(_)) {
+            Duration::ZERO
+        } else {
+            match self.pause {
+                Some((pages, pause)) if self.requested.is_multiple_of(pages) => pause,
+                _ => self.delay
+            }
+        };
+        if self.stop.wait(wait) {
+            self.finished = true;
+            return Some(Err(Box::new(PagingStopped::new())));
+        }
+
+        let page_step = self.request_page();
+        if !matches!(page_step, Ok(ControlFlow::Continue(_))) {
+            self.page_token = None;
+        }
+        self.finished = self.page_token.is_none();
+        Some(page_step.map(|page_step| match page_step {
+            ControlFlow::Continue(records) | ControlFlow::Break(records) => FetchedPage {
+                records,
+                next_token: self.page_token.clone()
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::Client;
+
+    use crate::api_response::User;
+    use crate::test_support::{query_of, serve_pages, FIRST_PAGE, LAST_PAGE};
+    use crate::transport::Interaction;
+
+    use super::*;
+
+    fn user_ids(page: &Response<Vec<User>>) -> Vec<String> {
+        page.data.iter().flatten().map(|user| user.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_pages() {
+        let (base_url, server) = serve_pages(&[FIRST_PAGE, LAST_PAGE]);
+        let request = Client::new().get(format!("{base_url}/2/users")).query(&[("max_results", "2")]);
+        let mut pages = Paginator::new(request, |page: &Response<Vec<User>>| Ok(ControlFlow::Continue(user_ids(page))));
+
+        assert_eq!(pages.next().unwrap().unwrap(), FetchedPage { records: vec!["3".to_string(), "2".to_string()], next_token: Some("page2".to_string()) });
+        assert_eq!(pages.next().unwrap().unwrap(), FetchedPage { records: vec!["1".to_string()], next_token: None });
+        assert!(pages.next().is_none());
+        assert_eq!(server.join().unwrap().iter().map(|request_line| query_of(request_line)).collect::<Vec<String>>(), vec!["max_results=2", "max_results=2&pagination_token=page2"]);
+
+        let (base_url, server) = serve_pages(&[LAST_PAGE]);
+        let request = Client::new().get(format!("{base_url}/2/users")).query(&[("max_results", "2")]);
+        let pages = Paginator::new(request, |page: &Response<Vec<User>>| Ok(ControlFlow::Continue(user_ids(page)))).start_at(Some("page2".to_string()));
+        assert_eq!(pages.count(), 1);
+        assert_eq!(server.join().unwrap().iter().map(|request_line| query_of(request_line)).collect::<Vec<String>>(), vec!["max_results=2&pagination_token=page2"]);
+    }
+
+    #[test]
+    fn test_replay_without_delay() {
+        let cassette_path = std::env::temp_dir().join(format!("sui_paginator_{}.json", std::process::id()));
+        let interactions: Vec<Interaction> = [(FIRST_PAGE, ""), (LAST_PAGE, "?pagination_token=page2")].iter().map(|(body, query)| Interaction {
+            method: String::from("GET"),
+            url: format!("https://api.twitter.com/2/users{query}"),
+            status: 200,
+            body: body.to_string()
+        }).collect();
+        std::fs::write(&cassette_path, serde_json::to_string(&interactions).unwrap()).unwrap();
+        let transport = Transport::replay(cassette_path.to_str().unwrap()).unwrap();
+
+        let started = std::time::Instant::now();
+        let pages = Paginator::new(Client::new().get("https://api.twitter.com/2/users"), |page: &Response<Vec<User>>| Ok(ControlFlow::Continue(user_ids(page))))
+            .transport(&transport)
+            .delay(Duration::from_secs(60))
+            .pause_every(1, Duration::from_secs(180));
+        assert_eq!(pages.count(), 2);
+        assert!(started.elapsed() < Duration::from_secs(60));
+        std::fs::remove_file(cassette_path).unwrap();
+    }
+
+    #[test]
+    fn test_stop() {
+        // the server only answers one request, a second one would fail
+        let (base_url, server) = serve_pages(&[FIRST_PAGE]);
+        let request = Client::new().get(format!("{base_url}/2/tweets/search/recent"));
+        let pages = Paginator::new(request, |page: &Response<Vec<User>>| {
+            let ids = user_ids(page);
+            match ids.contains(&"2".to_string()) {
+                true => Ok(ControlFlow::Break(ids)),
+                false => Ok(ControlFlow::Continue(ids))
+            }
+        }).token_param("next_token");
+        // a page the paging stopped at is the last one
+        assert_eq!(pages.collect::<Result<Vec<FetchedPage<Vec<String>>>, _>>().unwrap(), vec![FetchedPage {
+            records: vec!["3".to_string(), "2".to_string()],
+            next_token: None
+        }]);
+        server.join().unwrap();
+
+        let (base_url, server) = serve_pages(&[FIRST_PAGE]);
+        let request = Client::new().get(format!("{base_url}/2/users"));
+        let pages = Paginator::new(request, |page: &Response<Vec<User>>| Ok(ControlFlow::Continue(user_ids(page))));
+        assert_eq!(pages.take(1).count(), 1);
+        server.join().unwrap();
+
+        // stopped during the delay before the second page
+        let (base_url, server) = serve_pages(&[FIRST_PAGE]);
+        let request = Client::new().get(format!("{base_url}/2/users"));
+        let stop = StopSignal::new();
+        let mut pages = Paginator::new(request, |page: &Response<Vec<User>>| Ok(ControlFlow::Continue(user_ids(page))))
+            .delay(Duration::from_secs(60))
+            .stop_on(&stop);
+        assert!(pages.next().unwrap().is_ok());
+        server.join().unwrap();
+        let stopping = stop.clone();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            stopping.stop();
+        });
+        let started = std::time::Instant::now();
+        assert!(pages.next().unwrap().unwrap_err().is::<PagingStopped>());
+        assert!(started.elapsed() < Duration::from_secs(60));
+        assert!(pages.next().is_none());
+        stopper.join().unwrap();
+    }
+}
@@ -0,0 +1,340 @@
+use std::error::Error;
+
+use rusqlite::{Connection, named_params};
+
+use crate::media::ArchivedFile;
+use crate::query_result::{FetchedUser, UserDetail};
+
+/// A tracked profile field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileField {
+    Name,
+    Username,
+    Description,
+    Location,
+    Url,
+    ProfileImageUrl,
+    ProfileBannerUrl,
+    PinnedTweetId,
+    Protected,
+    Verified
+}
+
+impl ProfileField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProfileField::Name => "name",
+            ProfileField::Username => "username",
+            ProfileField::Description => "description",
+            ProfileField::Location => "location",
+            ProfileField::Url => "url",
+            ProfileField::ProfileImageUrl => "profile_image_url",
+            ProfileField::ProfileBannerUrl => "profile_banner_url",
+            ProfileField::PinnedTweetId => "pinned_tweet_id",
+            ProfileField::Protected => "protected",
+            ProfileField::Verified => "verified"
+        }
+    }
+
+    fn from_str(field: &str) -> Option<ProfileField> {
+        match field {
+            "name" => Some(ProfileField::Name),
+            "username" => Some(ProfileField::Username),
+            "description" => Some(ProfileField::Description),
+            "location" => Some(ProfileField::Location),
+            "url" => Some(ProfileField::Url),
+            "profile_image_url" => Some(ProfileField::ProfileImageUrl),
+            "profile_banner_url" => Some(ProfileField::ProfileBannerUrl),
+            "pinned_tweet_id" => Some(ProfileField::PinnedTweetId),
+            "protected" => Some(ProfileField::Protected),
+            "verified" => Some(ProfileField::Verified),
+            _ => None
+        }
+    }
+
+    /// The field's value in `user`, as stored in `profile_change`
+    fn value_of(&self, user: &UserDetail) -> Option<String> {
+        match self {
+            ProfileField::Name => Some(user.name.clone()),
+            ProfileField::Username => Some(user.username.clone()),
+            ProfileField::Description => user.description.clone(),
+            ProfileField::Location => user.location.clone(),
+            ProfileField::Url => user.url.clone(),
+            ProfileField::ProfileImageUrl => user.profile_image_url.clone(),
+            ProfileField::ProfileBannerUrl => user.profile_banner_url.clone(),
+            ProfileField::PinnedTweetId => user.pinned_tweet_id.clone(),
+            ProfileField::Protected => user.protected.map(|protected| protected.to_string()),
+            ProfileField::Verified => user.verified.map(|verified| verified.to_string())
+        }
+    }
+
+    /// Whether the field's values are image urls
+    pub fn is_image(&self) -> bool {
+        matches!(self, ProfileField::ProfileImageUrl | ProfileField::ProfileBannerUrl)
+    }
+}
+
+const TRACKED_FIELDS: [ProfileField; 10] = [
+    ProfileField::Name,
+    ProfileField::Username,
+    ProfileField::Description,
+    ProfileField::Location,
+    ProfileField::Url,
+    ProfileField::ProfileImageUrl,
+    ProfileField::ProfileBannerUrl,
+    ProfileField::PinnedTweetId,
+    ProfileField::Protected,
+    ProfileField::Verified
+];
+
+/// One field of a monitored profile changing between two fetches
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileChange {
+    /// Fetch time of the profile with the new value
+    pub time: Option<String>,
+    pub user_id: String,
+    pub field: ProfileField,
+    /// `None` when the field was empty; booleans are `true` or `false`
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// Archived images of the old and new value, for image fields once downloaded
+    pub old_archived: Option<ArchivedFile>,
+    pub new_archived: Option<ArchivedFile>
+}
+
+impl ProfileChange {
+    /// The changes turning `old_user` into the profile of `new_record`
+    pub fn diff(old_user: &UserDetail, new_record: &FetchedUser) -> Vec<ProfileChange> {
+        TRACKED_FIELDS.iter()
+            .filter_map(|field| {
+                let old_value = field.value_of(old_user);
+                let new_value = field.value_of(&new_record.user);
+                (old_value != new_value).then(|| ProfileChange {
+                    time: new_record.recorded_time.clone(),
+                    user_id: new_record.user.id.clone(),
+                    field: *field,
+                    old_value,
+                    new_value,
+                    old_archived: None,
+                    new_archived: None
+                })
+            })
+            .collect()
+    }
+
+    pub fn write_to_db(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        conn.execute(
+            "INSERT INTO profile_change
+            (time, user_id, field, old_value, new_value)
+            VALUES (:time, :user_id, :field, :old_value, :new_value)",
+            named_params! {
+                ":time": &self.time,
+                ":user_id": &self.user_id,
+                ":field": self.field.as_str(),
+                ":old_value": &self.old_value,
+                ":new_value": &self.new_value
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Changes of `user_id`'s profile recorded within `[since, until)` (ISO 8601 times, either
+    /// bound optional), oldest first
+    pub fn get_records(conn: &Connection, user_id: &str, since: Option<&str>, until: Option<&str>) -> Result<Vec<ProfileChange>, Box<dyn Error>> {
+        let mut change_stmt = conn.prepare(
+            "SELECT time, user_id, field, old_value, new_value,
+                old_content_hash, old_local_path, new_content_hash, new_local_path
+            FROM profile_change
+            WHERE user_id = :user_id
+                AND (:since IS NULL OR time >= :since)
+                AND (:until IS NULL OR time < :until)
+            ORDER BY id"
+        )?;
+        let query_results = change_stmt.query_map(
+            named_params! {
+                ":user_id": user_id,
+                ":since": since,
+                ":until": until
+            },
+            ProfileChange::from_row
+        )?;
+        ProfileChange::collect(query_results)
+    }
+
+    /// Image changes of `user_id` whose new image is not archived yet, oldest first
+    pub fn get_unarchived_images(conn: &Connection, user_id: &str) -> Result<Vec<ProfileChange>, Box<dyn Error>> {
+        let mut change_stmt = conn.prepare(
+            "SELECT time, user_id, field, old_value, new_value,
+                old_content_hash, old_local_path, new_content_hash, new_local_path
+            FROM profile_change
+            WHERE user_id = :user_id AND field IN ('profile_image_url', 'profile_banner_url')
+                AND new_value IS NOT NULL AND new_content_hash IS NULL
+            ORDER BY id"
+        )?;
+        let query_results = change_stmt.query_map(
+            named_params! { ":user_id": user_id },
+            ProfileChange::from_row
+        )?;
+        ProfileChange::collect(query_results)
+    }
+
+    /// References the archived copy of `image_url` from the changes of `user_id`'s `field` from
+    /// or to that image
+    pub fn set_archived(conn: &Connection, user_id: &str, field: ProfileField, image_url: &str, archived_file: &ArchivedFile) -> Result<(), Box<dyn Error>> {
+        for side in ["old", "new"] {
+            conn.execute(
+                &format!(
+                    "UPDATE profile_change
+                    SET {side}_content_hash = :content_hash, {side}_local_path = :local_path
+                    WHERE user_id = :user_id AND field = :field AND {side}_value = :image_url"
+                ),
+                named_params! {
+                    ":content_hash": &archived_file.content_hash,
+                    ":local_path": &archived_file.local_path,
+                    ":user_id": user_id,
+                    ":field": field.as_str(),
+                    ":image_url": image_url
+                }
+            )?;
+        }
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<(ProfileChange, String)> {
+        let archived_file = |hash_column: usize| -> rusqlite::Result<Option<ArchivedFile>> {
+            Ok(match (row.get::<_, Option<String>>(hash_column)?, row.get::<_, Option<String>>(hash_column + 1)?) {
+                (Some(content_hash), Some(local_path)) => Some(ArchivedFile { content_hash, local_path }),
+                _ => None
+            })
+        };
+        Ok((
+            ProfileChange {
+                time: row.get(0)?,
+                user_id: row.get(1)?,
+                field: ProfileField::Name,
+                old_value: row.get(3)?,
+                new_value: row.get(4)?,
+                old_archived: archived_file(5)?,
+                new_archived: archived_file(7)?
+            },
+            row.get(2)?
+        ))
+    }
+
+    fn collect(query_results: impl Iterator<Item = rusqlite::Result<(ProfileChange, String)>>) -> Result<Vec<ProfileChange>, Box<dyn Error>> {
+        let mut changes: Vec<ProfileChange> = Vec::new();
+        for query_result in query_results {
+            let (mut change, field) = query_result?;
+            // rows of fields this version does not know are skipped
+            if let Some(field) = ProfileField::from_str(&field) {
+                change.field = field;
+                changes.push(change);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::TaskType;
+    use crate::db::init_db;
+    use crate::store::{SqliteStore, Store};
+
+    use super::*;
+
+    fn profile(time: &str, username: &str, location: Option<&str>, protected: Option<bool>) -> FetchedUser {
+        FetchedUser {
+            recorded_time: Some(time.to_string()),
+            user: UserDetail {
+                id: "0".to_string(),
+                name: "Suisei".to_string(),
+                username: username.to_string(),
+                location: location.map(|location| location.to_string()),
+                description: None,
+                url: None,
+                profile_image_url: None,
+                profile_banner_url: None,
+                pinned_tweet_id: None,
+                protected,
+                verified: None
+            }
+        }
+    }
+
+    #[test]
+    fn test_profile_changes() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let store = SqliteStore::new(&conn);
+
+        store.write_profile(&profile("2023-01-01T00:00:00.000Z", "suisei_hosimati", None, Some(false)), &TaskType::Initializing).unwrap();
+        store.write_profile(&profile("2023-01-02T00:00:00.000Z", "suisei_hosimati", None, Some(false)), &TaskType::Monitoring).unwrap();
+        store.write_profile(&profile("2023-01-03T00:00:00.000Z", "hoshimatisuisei", Some("Tokyo"), Some(false)), &TaskType::Monitoring).unwrap();
+        store.write_profile(&profile("2023-01-04T00:00:00.000Z", "hoshimatisuisei", Some("Tokyo"), Some(true)), &TaskType::Monitoring).unwrap();
+
+        let changes = ProfileChange::get_records(&conn, "0", None, None).unwrap();
+        assert_eq!(changes, vec![
+            ProfileChange {
+                time: Some("2023-01-03T00:00:00.000Z".to_string()),
+                user_id: "0".to_string(),
+                field: ProfileField::Username,
+                old_value: Some("suisei_hosimati".to_string()),
+                new_value: Some("hoshimatisuisei".to_string()),
+                old_archived: None,
+                new_archived: None
+            },
+            ProfileChange {
+                time: Some("2023-01-03T00:00:00.000Z".to_string()),
+                user_id: "0".to_string(),
+                field: ProfileField::Location,
+                old_value: None,
+                new_value: Some("Tokyo".to_string()),
+                old_archived: None,
+                new_archived: None
+            },
+            ProfileChange {
+                time: Some("2023-01-04T00:00:00.000Z".to_string()),
+                user_id: "0".to_string(),
+                field: ProfileField::Protected,
+                old_value: Some("false".to_string()),
+                new_value: Some("true".to_string()),
+                old_archived: None,
+                new_archived: None
+            }
+        ]);
+
+        let later_changes = ProfileChange::get_records(&conn, "0", Some("2023-01-04T00:00:00.000Z"), None).unwrap();
+        assert_eq!(later_changes.len(), 1);
+        assert_eq!(later_changes[0].field, ProfileField::Protected);
+        assert!(ProfileChange::get_records(&conn, "0", None, Some("2023-01-03T00:00:00.000Z")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_image_archive_references() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let store = SqliteStore::new(&conn);
+
+        let mut old_profile = profile("2023-01-01T00:00:00.000Z", "suisei_hosimati", None, None);
+        old_profile.user.profile_image_url = Some("https://pbs.twimg.com/profile_images/1/a_normal.jpg".to_string());
+        store.write_profile(&old_profile, &TaskType::Initializing).unwrap();
+        let mut new_profile = profile("2023-01-02T00:00:00.000Z", "suisei_hosimati", None, None);
+        new_profile.user.profile_image_url = Some("https://pbs.twimg.com/profile_images/2/b_normal.jpg".to_string());
+        store.write_profile(&new_profile, &TaskType::Monitoring).unwrap();
+
+        let unarchived = ProfileChange::get_unarchived_images(&conn, "0").unwrap();
+        assert_eq!(unarchived.len(), 1);
+        assert_eq!(unarchived[0].field, ProfileField::ProfileImageUrl);
+
+        let old_file = ArchivedFile { content_hash: "aa".to_string(), local_path: "archive/aa/aa.jpg".to_string() };
+        let new_file = ArchivedFile { content_hash: "bb".to_string(), local_path: "archive/bb/bb.jpg".to_string() };
+        ProfileChange::set_archived(&conn, "0", ProfileField::ProfileImageUrl, "https://pbs.twimg.com/profile_images/1/a_normal.jpg", &old_file).unwrap();
+        ProfileChange::set_archived(&conn, "0", ProfileField::ProfileImageUrl, "https://pbs.twimg.com/profile_images/2/b_normal.jpg", &new_file).unwrap();
+
+        assert!(ProfileChange::get_unarchived_images(&conn, "0").unwrap().is_empty());
+        let changes = ProfileChange::get_records(&conn, "0", None, None).unwrap();
+        assert_eq!(changes[0].old_archived, Some(old_file));
+        assert_eq!(changes[0].new_archived, Some(new_file));
+    }
+}
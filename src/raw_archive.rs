@@ -0,0 +1,180 @@
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+use crate::configuration;
+
+/// Endpoint names responses are archived under
+pub const USERS_BY: &str = "users_by";
+pub const USER_TWEETS: &str = "tweets";
+pub const LIKED_TWEETS: &str = "liked_tweets";
+pub const MENTIONS: &str = "mentions";
+pub const FOLLOWING: &str = "following";
+pub const LIKING_USERS: &str = "liking_users";
+pub const RETWEETED_BY: &str = "retweeted_by";
+pub const QUOTE_TWEETS: &str = "quote_tweets";
+pub const SEARCH_RECENT: &str = "search_recent";
+pub const TWEET_LOOKUP: &str = "tweet_lookup";
+pub const USER_LOOKUP: &str = "user_lookup";
+pub const STREAM: &str = "stream";
+
+/// Format of the fetch time in archived file names, sortable and valid on every file system
+const FILE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.6fZ";
+
+/// An archived response body
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedResponse {
+    pub endpoint: String,
+    /// What the request was about, e.g. the user id for timelines or the search name
+    pub key: String,
+    /// In the `%Y-%m-%dT%H:%M:%S%.3fZ` format of recorded times
+    pub fetched_at: String,
+    pub path: PathBuf
+}
+
+impl ArchivedResponse {
+    pub fn read_body(&self) -> Result<String, Box<dyn Error>> {
+        let mut body = String::new();
+        GzDecoder::new(fs::File::open(&self.path)?).read_to_string(&mut body)?;
+        Ok(body)
+    }
+}
+
+/// Keeps the raw body of every API response gzip-compressed as
+/// `<archive_dir>/<endpoint>/<key>/<fetch time>-<sequence>.json.gz`, so the derived tables can be
+/// rebuilt when parsing changes.
+#[derive(Debug, Clone)]
+pub struct RawArchive {
+    archive_dir: PathBuf
+}
+
+impl RawArchive {
+    pub fn new(archive_dir: &str) -> RawArchive {
+        RawArchive { archive_dir: PathBuf::from(archive_dir) }
+    }
+
+    /// The archive of `conf`, `None` when archiving is not configured
+    pub fn from_config(conf: &configuration::Config) -> Option<RawArchive> {
+        conf.raw_archive_dir.as_deref().map(RawArchive::new)
+    }
+
+    /// Stores `body` as fetched now
+    pub fn store(&self, endpoint: &str, key: &str, body: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let key_dir = self.archive_dir.join(endpoint).join(sanitize(key));
+        fs::create_dir_all(&key_dir)?;
+        let file_time = Utc::now().format(FILE_TIME_FORMAT).to_string();
+        // responses fetched within the same microsecond get the next sequence number
+        for sequence in 0.. {
+            let path = key_dir.join(format!("{file_time}-{sequence:03}.json.gz"));
+            let file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => { continue; }
+                Err(e) => { return Err(Box::new(e)); }
+            };
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(body.as_bytes())?;
+            encoder.finish()?;
+            return Ok(path);
+        }
+        unreachable!("the sequence numbers are unbounded")
+    }
+
+    /// Every archived response, oldest first
+    pub fn entries(&self) -> Result<Vec<ArchivedResponse>, Box<dyn Error>> {
+        let mut entries: Vec<ArchivedResponse> = Vec::new();
+        for endpoint_dir in read_dirs(&self.archive_dir)? {
+            for key_dir in read_dirs(&endpoint_dir)? {
+                for file in fs::read_dir(&key_dir)? {
+                    let path = file?.path();
+                    let fetched_at = match path.file_name().and_then(|file_name| file_name.to_str()).and_then(parse_file_time) {
+                        Some(fetched_at) => fetched_at,
+                        None => { continue; }
+                    };
+                    entries.push(ArchivedResponse {
+                        endpoint: dir_name(&endpoint_dir),
+                        key: dir_name(&key_dir),
+                        fetched_at,
+                        path
+                    });
+                }
+            }
+        }
+        entries.sort_by(|entry, other| entry.path.file_name().cmp(&other.path.file_name()));
+        Ok(entries)
+    }
+}
+
+/// Archives `body` when `conf` enables it. Failures are only logged, the fetch goes on.
+pub fn archive_response(conf: &configuration::Config, endpoint: &str, key: &str, body: &str) {
+    if let Some(raw_archive) = RawArchive::from_config(conf) {
+        if let Err(e) = raw_archive.store(endpoint, key, body) {
+            log::warn!("Failed to archive a {} response of {}: {}", endpoint, key, e);
+        }
+    }
+}
+
+/// Keys are ids, usernames or search names; anything that could leave the directory is replaced
+pub(crate) fn sanitize(key: &str) -> String {
+    key.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' }).collect()
+}
+
+fn read_dirs(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Fetch time of an archived file name
+fn parse_file_time(file_name: &str) -> Option<String> {
+    let (file_time, _) = file_name.strip_suffix(".json.gz")?.rsplit_once('-')?;
+    let fetched_at = NaiveDateTime::parse_from_str(file_time, FILE_TIME_FORMAT).ok()?;
+    Some(fetched_at.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_raw_archive() {
+        let archive_dir = env::temp_dir().join(format!("sui_raw_archive_{}", std::process::id()));
+        let raw_archive = RawArchive::new(archive_dir.to_str().unwrap());
+        assert!(raw_archive.entries().unwrap().is_empty());
+
+        let first_path = raw_archive.store(USER_TWEETS, "975275878673408001", r#"{"meta":{"result_count":0}}"#).unwrap();
+        raw_archive.store(USER_TWEETS, "975275878673408001", r#"{"data":[]}"#).unwrap();
+        raw_archive.store(SEARCH_RECENT, "../art", r#"{"data":[]}"#).unwrap();
+        assert!(first_path.starts_with(archive_dir.join("tweets").join("975275878673408001")));
+
+        let entries = raw_archive.entries().unwrap();
+        assert_eq!(entries.iter().map(|entry| (entry.endpoint.as_str(), entry.key.as_str())).collect::<Vec<(&str, &str)>>(), vec![
+            ("tweets", "975275878673408001"),
+            ("tweets", "975275878673408001"),
+            ("search_recent", "___art")
+        ]);
+        assert_eq!(entries[0].path, first_path);
+        assert_eq!(entries[0].read_body().unwrap(), r#"{"meta":{"result_count":0}}"#);
+        assert_eq!(entries[0].fetched_at.len(), "2023-01-01T00:00:00.000Z".len());
+        assert!(entries[0].fetched_at <= entries[2].fetched_at);
+
+        fs::remove_dir_all(&archive_dir).unwrap();
+    }
+}
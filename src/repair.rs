@@ -0,0 +1,295 @@
+use std::error::Error;
+
+use rusqlite::{Connection, named_params};
+
+use crate::configuration;
+use crate::query_result::{now, Availability, BasicUserDetail};
+use crate::request_builder::{LookupError, TweetLookupFetcher, TweetLookupResult, UserLookupFetcher};
+use crate::store::{SqliteStore, Store};
+use crate::writer::DbWriter;
+
+/// Dictionary tables holding placeholders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dictionary {
+    Tweet,
+    User
+}
+
+impl Dictionary {
+    fn table(&self) -> &'static str {
+        match self {
+            Dictionary::Tweet => "tweet_dict",
+            Dictionary::User => "user_dict"
+        }
+    }
+
+    fn id_column(&self) -> &'static str {
+        match self {
+            Dictionary::Tweet => "tweet_id",
+            Dictionary::User => "user_id"
+        }
+    }
+}
+
+/// What one repair run did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RepairReport {
+    pub restored_tweets: usize,
+    pub restored_users: usize,
+    /// Placeholders the API reported as deleted, suspended or protected
+    pub unavailable_tweets: usize,
+    pub unavailable_users: usize,
+    /// Placeholders the API neither returned nor reported, left for the next run
+    pub still_missing: usize
+}
+
+/// Looks up to `max_items` placeholder tweets and as many placeholder users up again, least
+/// recently checked first. Returned items replace their placeholders, items the API reports
+/// errors for are flagged unavailable and not looked up again. Placeholders are read on `conn`,
+/// the results of every lookup are written through `writer`.
+pub fn repair_placeholders(conf: &configuration::Config, conn: &Connection, writer: &DbWriter, max_items: u16) -> Result<RepairReport, Box<dyn Error>> {
+    let mut report = RepairReport::default();
+
+    let tweet_ids = placeholder_ids(conn, Dictionary::Tweet, max_items)?;
+    if !tweet_ids.is_empty() {
+        let lookup_result = TweetLookupFetcher::new(&tweet_ids).fetch(conf)?;
+        report = writer.write(move |conn| {
+            apply_tweet_lookup(conn, &tweet_ids, lookup_result, &mut report)?;
+            Ok(report)
+        })?;
+    }
+
+    // authors of restored tweets came with them, so only look up who is still missing
+    let user_ids = placeholder_ids(conn, Dictionary::User, max_items)?;
+    if !user_ids.is_empty() {
+        let (users, lookup_errors) = UserLookupFetcher::new(&user_ids).fetch(conf)?;
+        report = writer.write(move |conn| {
+            apply_user_lookup(conn, &user_ids, users, lookup_errors, &mut report)?;
+            Ok(report)
+        })?;
+    }
+
+    Ok(report)
+}
+
+/// Ids of placeholders in `dictionary`, least recently checked first
+pub fn placeholder_ids(conn: &Connection, dictionary: Dictionary, max_items: u16) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut placeholder_stmt = conn.prepare(&format!(
+        "SELECT {id_column} FROM {table}
+        WHERE availability = :availability
+        ORDER BY checked_at IS NOT NULL, checked_at, id
+        LIMIT :limit",
+        id_column = dictionary.id_column(),
+        table = dictionary.table()
+    ))?;
+    let query_results = placeholder_stmt.query_map(
+        named_params! { ":availability": Availability::Placeholder.as_str(), ":limit": max_items },
+        |row| row.get(0)
+    )?;
+
+    let mut ids: Vec<String> = Vec::new();
+    for query_result in query_results {
+        ids.push(query_result?);
+    }
+    Ok(ids)
+}
+
+/// Ids and error titles of the items in `dictionary` flagged unavailable
+pub fn unavailable_items(conn: &Connection, dictionary: Dictionary) -> Result<Vec<LookupError>, Box<dyn Error>> {
+    let mut unavailable_stmt = conn.prepare(&format!(
+        "SELECT {id_column}, unavailable_reason FROM {table}
+        WHERE availability = :availability
+        ORDER BY id",
+        id_column = dictionary.id_column(),
+        table = dictionary.table()
+    ))?;
+    let query_results = unavailable_stmt.query_map(
+        named_params! { ":availability": Availability::Unavailable.as_str() },
+        |row| Ok(LookupError {
+            resource_id: row.get(0)?,
+            title: row.get::<_, Option<String>>(1)?.unwrap_or_default()
+        })
+    )?;
+
+    let mut items: Vec<LookupError> = Vec::new();
+    for query_result in query_results {
+        items.push(query_result?);
+    }
+    Ok(items)
+}
+
+fn apply_tweet_lookup(
+    conn: &Connection,
+    requested_ids: &[String],
+    (tweets, users, lookup_errors): TweetLookupResult,
+    report: &mut RepairReport
+) -> Result<(), Box<dyn Error>> {
+    let store = SqliteStore::new(conn);
+    for user in users.iter() {
+        report.restored_users += is_placeholder(conn, Dictionary::User, &user.id)? as usize;
+        store.write_user(user)?;
+    }
+    for tweet in tweets.iter() {
+        report.restored_tweets += is_placeholder(conn, Dictionary::Tweet, &tweet.id)? as usize;
+        store.write_dict_tweet(tweet)?;
+    }
+    report.unavailable_tweets += mark_unavailable(conn, Dictionary::Tweet, &lookup_errors)?;
+    report.still_missing += mark_checked(conn, Dictionary::Tweet, requested_ids)?;
+    Ok(())
+}
+
+fn apply_user_lookup(
+    conn: &Connection,
+    requested_ids: &[String],
+    users: Vec<BasicUserDetail>,
+    lookup_errors: Vec<LookupError>,
+    report: &mut RepairReport
+) -> Result<(), Box<dyn Error>> {
+    let store = SqliteStore::new(conn);
+    for user in users.iter() {
+        report.restored_users += is_placeholder(conn, Dictionary::User, &user.id)? as usize;
+        store.write_user(user)?;
+    }
+    report.unavailable_users += mark_unavailable(conn, Dictionary::User, &lookup_errors)?;
+    report.still_missing += mark_checked(conn, Dictionary::User, requested_ids)?;
+    Ok(())
+}
+
+fn is_placeholder(conn: &Connection, dictionary: Dictionary, id: &str) -> Result<bool, Box<dyn Error>> {
+    let placeholder_count: i64 = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM {table} WHERE {id_column} = :id AND availability = :placeholder",
+            id_column = dictionary.id_column(),
+            table = dictionary.table()
+        ),
+        named_params! { ":id": id, ":placeholder": Availability::Placeholder.as_str() },
+        |row| row.get(0)
+    )?;
+    Ok(placeholder_count > 0)
+}
+
+/// Flags the placeholders the API reported errors for, returning how many were flagged
+pub(crate) fn mark_unavailable(conn: &Connection, dictionary: Dictionary, lookup_errors: &[LookupError]) -> Result<usize, Box<dyn Error>> {
+    let mut unavailable_stmt = conn.prepare(&format!(
+        "UPDATE {table}
+        SET availability = :unavailable, unavailable_reason = :reason, checked_at = :checked_at
+        WHERE {id_column} = :id AND availability = :placeholder",
+        id_column = dictionary.id_column(),
+        table = dictionary.table()
+    ))?;
+    let checked_at = now();
+    let mut flagged = 0;
+    for lookup_error in lookup_errors {
+        flagged += unavailable_stmt.execute(named_params! {
+            ":unavailable": Availability::Unavailable.as_str(),
+            ":reason": &lookup_error.title,
+            ":checked_at": &checked_at,
+            ":id": &lookup_error.resource_id,
+            ":placeholder": Availability::Placeholder.as_str()
+        })?;
+    }
+    Ok(flagged)
+}
+
+/// Records the lookup of the requested ids that are still placeholders, returning how many are
+fn mark_checked(conn: &Connection, dictionary: Dictionary, requested_ids: &[String]) -> Result<usize, Box<dyn Error>> {
+    let checked = conn.execute(
+        &format!(
+            "UPDATE {table} SET checked_at = :checked_at
+            WHERE availability = :placeholder AND {id_column} IN (SELECT value FROM json_each(:ids))",
+            id_column = dictionary.id_column(),
+            table = dictionary.table()
+        ),
+        named_params! {
+            ":checked_at": now(),
+            ":placeholder": Availability::Placeholder.as_str(),
+            ":ids": serde_json::to_string(requested_ids)?
+        }
+    )?;
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::query_result::BasicTweet;
+    use crate::search::search_dict_tweets;
+
+    use super::*;
+
+    fn availability_of(conn: &Connection, dictionary: Dictionary, id: &str) -> String {
+        conn.query_row(
+            &format!("SELECT availability FROM {} WHERE {} = ?", dictionary.table(), dictionary.id_column()),
+            [id],
+            |row| row.get(0)
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_apply_lookup() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        SqliteStore::new(&conn).write_dict_tweet(&BasicTweet::placeholder("1")).unwrap();
+        SqliteStore::new(&conn).write_dict_tweet(&BasicTweet::placeholder("2")).unwrap();
+        SqliteStore::new(&conn).write_dict_tweet(&BasicTweet::placeholder("3")).unwrap();
+        SqliteStore::new(&conn).write_user(&BasicUserDetail::placeholder("20")).unwrap();
+        SqliteStore::new(&conn).write_user(&BasicUserDetail::placeholder("30")).unwrap();
+        // a placeholder never replaces what is known
+        SqliteStore::new(&conn).write_user(&BasicUserDetail { id: "10".to_string(), username: "suisei_hosimati".to_string(), name: "Suisei".to_string() }).unwrap();
+        SqliteStore::new(&conn).write_user(&BasicUserDetail::placeholder("10")).unwrap();
+        assert_eq!(SqliteStore::new(&conn).get_user("10").unwrap().unwrap().username, "suisei_hosimati");
+
+        let tweet_ids = placeholder_ids(&conn, Dictionary::Tweet, 10).unwrap();
+        assert_eq!(tweet_ids, vec!["1".to_string(), "2".to_string(), "3".to_string()]);
+
+        let restored_tweet = BasicTweet {
+            id: "1".to_string(),
+            author_id: "20".to_string(),
+            text: "comet".to_string(),
+            hashtags: Some(vec!["hoshimachi".to_string()]),
+            urls: None,
+            conversation_id: Some("1".to_string()),
+            replied_to_id: None
+        };
+        let restored_user = BasicUserDetail { id: "20".to_string(), username: "tokoyami_towa".to_string(), name: "Towa".to_string() };
+        let mut report = RepairReport::default();
+        apply_tweet_lookup(
+            &conn,
+            &tweet_ids,
+            (vec![restored_tweet.clone()], vec![restored_user.clone()], vec![LookupError { resource_id: "2".to_string(), title: "Not Found Error".to_string() }]),
+            &mut report
+        ).unwrap();
+        assert_eq!(SqliteStore::new(&conn).get_dict_tweet("1").unwrap(), Some(restored_tweet));
+        assert_eq!(SqliteStore::new(&conn).get_user("20").unwrap(), Some(restored_user));
+        assert_eq!(availability_of(&conn, Dictionary::Tweet, "2"), "unavailable");
+        assert_eq!(availability_of(&conn, Dictionary::Tweet, "3"), "placeholder");
+        assert_eq!(search_dict_tweets(&conn, "comet", 10).unwrap().len(), 1);
+        assert!(search_dict_tweets(&conn, "Unavailable", 10).unwrap().iter().all(|hit| hit.item.id != "1"));
+
+        let user_ids = placeholder_ids(&conn, Dictionary::User, 10).unwrap();
+        assert_eq!(user_ids, vec!["30".to_string()]);
+        apply_user_lookup(
+            &conn,
+            &user_ids,
+            Vec::new(),
+            vec![LookupError { resource_id: "30".to_string(), title: "Forbidden".to_string() }],
+            &mut report
+        ).unwrap();
+
+        assert_eq!(report, RepairReport {
+            restored_tweets: 1,
+            restored_users: 1,
+            unavailable_tweets: 1,
+            unavailable_users: 1,
+            still_missing: 1
+        });
+        assert_eq!(
+            unavailable_items(&conn, Dictionary::User).unwrap(),
+            vec![LookupError { resource_id: "30".to_string(), title: "Forbidden".to_string() }]
+        );
+        // the tweet checked last time goes last
+        SqliteStore::new(&conn).write_dict_tweet(&BasicTweet::placeholder("4")).unwrap();
+        assert_eq!(placeholder_ids(&conn, Dictionary::Tweet, 10).unwrap(), vec!["4".to_string(), "3".to_string()]);
+    }
+}
@@ -0,0 +1,228 @@
+use std::error::Error;
+
+use rusqlite::Connection;
+
+use crate::announcement::StreamAnnouncement;
+use crate::api_response::{Response, Tweet};
+use crate::configuration::{self, SavedSearch, TaskType};
+use crate::engagement::{Engagement, EngagementKind};
+use crate::paginator::Fetcher;
+use crate::query_result::{BasicTweet, BasicUserDetail, FetchedTweet, FetchedUser, Placement};
+use crate::raw_archive::{self, ArchivedResponse, RawArchive};
+use crate::repair::{self, Dictionary};
+use crate::request_builder::{self, EngagementFetcher, FollowingFetcher, LikeFetcher, MentionsFetcher, SearchFetcher, TweetFetcher};
+use crate::store::{SqliteStore, Store};
+
+/// What one reprocessing run did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReprocessReport {
+    pub replayed: usize,
+    /// Responses that could not be parsed, e.g. error payloads, or of unknown endpoints
+    pub skipped: usize
+}
+
+/// Rebuilds the derived tables in `conn`, usually a freshly initialized database, by parsing
+/// every archived response again in the order they were fetched. Parsing follows monitoring, so
+/// profiles are diffed and likes and follows already replayed are not written twice. Unfollows
+/// are only found by comparing full following lists and are not rebuilt.
+pub fn reprocess(conf: &configuration::Config, raw_archive: &RawArchive, conn: &Connection) -> Result<ReprocessReport, Box<dyn Error>> {
+    let mut replay_conf = conf.clone();
+    replay_conf.task_type = TaskType::Monitoring;
+
+    let mut report = ReprocessReport::default();
+    let transaction = conn.unchecked_transaction()?;
+    for entry in raw_archive.entries()? {
+        let replay_result = entry.read_body().and_then(|body| replay(&replay_conf, &transaction, &entry, &body));
+        match replay_result {
+            Ok(true) => { report.replayed += 1; }
+            Ok(false) => { report.skipped += 1; }
+            Err(e) => {
+                log::warn!("Skipped the {} response of {} fetched at {}: {}", &entry.endpoint, &entry.key, &entry.fetched_at, e);
+                report.skipped += 1;
+            }
+        }
+    }
+    transaction.commit()?;
+    Ok(report)
+}
+
+/// Writes what an archived response is parsed into; `false` for unknown endpoints
+fn replay(conf: &configuration::Config, conn: &Connection, entry: &ArchivedResponse, body: &str) -> Result<bool, Box<dyn Error>> {
+    let store = SqliteStore::new(conn);
+    match entry.endpoint.as_str() {
+        raw_archive::USERS_BY => {
+            let mut fetched_user = FetchedUser::record(&conf.task_type);
+            fetched_user.recorded_time = Some(entry.fetched_at.clone());
+            fetched_user.user = request_builder::parse_user_detail(&serde_json::from_str(body)?)?;
+            store.write_profile(&fetched_user, &conf.task_type)?;
+        }
+        raw_archive::USER_TWEETS => {
+            let (tweets, ref_tweets, ref_users) = TweetFetcher::new(&entry.key, None).parse_archived(conf, body, &entry.fetched_at)?;
+            write_tweets(conn, tweets, ref_tweets, ref_users)?;
+        }
+        raw_archive::STREAM => {
+            let payload: Response<Tweet> = serde_json::from_str(body)?;
+            // disconnect messages carry no tweet
+            if payload.data.is_none() {
+                return Ok(false);
+            }
+            let (tweets, ref_tweets, ref_users) = request_builder::parse_tweet_payload(&payload)?;
+            write_tweets(conn, tweets, ref_tweets, ref_users)?;
+        }
+        raw_archive::LIKED_TWEETS => {
+            let (liked_tweet_records, liked_tweets, liked_users) = LikeFetcher::new(&entry.key, None).parse_archived(conf, body, &entry.fetched_at)?;
+            write_related(conn, liked_tweets, liked_users)?;
+            // likes come back on every fetch until the newest stored one is reached
+            let mut stored = Vec::new();
+            for liked_tweet_record in liked_tweet_records.iter() {
+                stored.push(store.has_like(liked_tweet_record)?);
+            }
+            // polled pages reach the stored likes, pages archived while initializing go back
+            // past them
+            if stored.contains(&true) {
+                for (liked_tweet_record, _) in liked_tweet_records.iter().zip(stored).rev().filter(|(_, stored)| !stored) {
+                    store.write_like(liked_tweet_record, Placement::Newer)?;
+                }
+            } else {
+                for liked_tweet_record in liked_tweet_records.iter() {
+                    store.write_like(liked_tweet_record, Placement::Older)?;
+                }
+            }
+        }
+        raw_archive::MENTIONS => {
+            let (mentions, mentioning_tweets, mentioning_users) = MentionsFetcher::new(&entry.key, None).parse_archived(conf, body, &entry.fetched_at)?;
+            write_related(conn, mentioning_tweets, mentioning_users)?;
+            for mention in mentions.iter().rev() {
+                mention.write_to_db(conn)?;
+            }
+        }
+        raw_archive::FOLLOWING => {
+            let following_ids = store.current_following_ids(&entry.key)?;
+            let (following_records, followed_users, page_ids) = FollowingFetcher::new(&entry.key, Some(following_ids.clone())).parse_archived(conf, body, &entry.fetched_at)?;
+            write_related(conn, Vec::new(), followed_users)?;
+            // like likes, a page sharing no follow with the stored ones was paged in while
+            // initializing
+            if page_ids.iter().any(|followed_id| following_ids.contains(followed_id)) {
+                for following_record in following_records.iter().rev() {
+                    store.write_following(following_record, Placement::Newer)?;
+                }
+            } else {
+                for following_record in following_records.iter() {
+                    store.write_following(following_record, Placement::Older)?;
+                }
+            }
+        }
+        raw_archive::LIKING_USERS | raw_archive::RETWEETED_BY | raw_archive::QUOTE_TWEETS => {
+            let kind = match entry.endpoint.as_str() {
+                raw_archive::LIKING_USERS => EngagementKind::Like,
+                raw_archive::RETWEETED_BY => EngagementKind::Retweet,
+                _ => EngagementKind::Quote
+            };
+            let known_ids = Engagement::known_ids(conn, &entry.key, kind)?;
+            let (engagements, quote_tweets, engagers) = EngagementFetcher::new(&entry.key, kind, known_ids).parse_archived(conf, body, &entry.fetched_at)?;
+            write_related(conn, quote_tweets, engagers)?;
+            for engagement in engagements.iter().rev() {
+                engagement.write_to_db(conn)?;
+            }
+        }
+        raw_archive::SEARCH_RECENT => {
+            // archived under the sanitized name, the query is not needed to parse
+            let search = conf.saved_searches.iter()
+                .find(|saved_search| raw_archive::sanitize(&saved_search.name) == entry.key)
+                .cloned()
+                .unwrap_or_else(|| SavedSearch { name: entry.key.clone(), query: String::new() });
+            let (hits, hit_tweets, hit_users) = SearchFetcher::new(&search, None).parse_archived(conf, body, &entry.fetched_at)?;
+            write_related(conn, hit_tweets, hit_users)?;
+            for hit in hits.iter().rev() {
+                hit.write_to_db(conn)?;
+            }
+        }
+        raw_archive::TWEET_LOOKUP => {
+            let (tweets, users, lookup_errors) = request_builder::parse_tweet_lookup(&serde_json::from_str(body)?)?;
+            write_related(conn, tweets, users)?;
+            repair::mark_unavailable(conn, Dictionary::Tweet, &lookup_errors)?;
+        }
+        raw_archive::USER_LOOKUP => {
+            let (users, lookup_errors) = request_builder::parse_user_lookup(&serde_json::from_str(body)?)?;
+            write_related(conn, Vec::new(), users)?;
+            repair::mark_unavailable(conn, Dictionary::User, &lookup_errors)?;
+        }
+        _ => { return Ok(false); }
+    }
+    Ok(true)
+}
+
+fn write_related(conn: &Connection, related_tweets: Vec<BasicTweet>, related_users: Vec<BasicUserDetail>) -> Result<(), Box<dyn Error>> {
+    let store = SqliteStore::new(conn);
+    for related_user in related_users.iter() {
+        store.write_user(related_user)?;
+    }
+    for related_tweet in related_tweets.iter() {
+        store.write_dict_tweet(related_tweet)?;
+    }
+    Ok(())
+}
+
+/// Writes tweets of a monitored user oldest first, with their stream announcements
+fn write_tweets(conn: &Connection, tweets: Vec<FetchedTweet>, ref_tweets: Vec<BasicTweet>, ref_users: Vec<BasicUserDetail>) -> Result<(), Box<dyn Error>> {
+    write_related(conn, ref_tweets, ref_users)?;
+    for tweet in tweets.iter().rev() {
+        SqliteStore::new(conn).write_tweet(tweet)?;
+        for announcement in StreamAnnouncement::extract(tweet) {
+            announcement.write_to_db(conn)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::configuration::Config;
+    use crate::db::init_db;
+    use crate::mention::Mention;
+    use crate::test_support;
+
+    use super::*;
+
+    fn test_config() -> Config {
+        test_support::config(TaskType::Reprocessing)
+    }
+
+    #[test]
+    fn test_reprocess() {
+        let archive_dir = env::temp_dir().join(format!("sui_reprocess_{}", std::process::id()));
+        let raw_archive = RawArchive::new(archive_dir.to_str().unwrap());
+        let user_id = "975275878673408001";
+        raw_archive.store(raw_archive::USERS_BY, "suisei_hosimati", include_str!("../tests/fixtures/users_by.json")).unwrap();
+        raw_archive.store(raw_archive::USER_TWEETS, user_id, include_str!("../tests/fixtures/user_tweets.json")).unwrap();
+        raw_archive.store(raw_archive::LIKED_TWEETS, user_id, include_str!("../tests/fixtures/user_tweets.json")).unwrap();
+        raw_archive.store(raw_archive::MENTIONS, user_id, include_str!("../tests/fixtures/mentions.json")).unwrap();
+        // polled again with nothing new
+        raw_archive.store(raw_archive::USERS_BY, "suisei_hosimati", include_str!("../tests/fixtures/users_by.json")).unwrap();
+        raw_archive.store(raw_archive::USER_TWEETS, user_id, include_str!("../tests/fixtures/user_tweets.json")).unwrap();
+        raw_archive.store(raw_archive::LIKED_TWEETS, user_id, include_str!("../tests/fixtures/user_tweets.json")).unwrap();
+        raw_archive.store(raw_archive::STREAM, "filtered", include_str!("../tests/fixtures/stream_disconnect.json")).unwrap();
+        raw_archive.store("unknown", "1", "{}").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let report = reprocess(&test_config(), &raw_archive, &conn).unwrap();
+        assert_eq!(report, ReprocessReport { replayed: 7, skipped: 2 });
+
+        let expected_tweets = TweetFetcher::new(user_id, None)
+            .parse_archived(&test_config(), include_str!("../tests/fixtures/user_tweets.json"), "2023-01-01T00:00:00.000Z")
+            .unwrap().0.len();
+        let store = SqliteStore::new(&conn);
+        assert_eq!(store.get_tweets(user_id, None, 0).unwrap().len(), expected_tweets);
+        assert_eq!(store.get_likes(user_id, None, 0).unwrap().len(), expected_tweets);
+        assert!(store.get_likes(user_id, None, 0).unwrap().iter().all(|liked_tweet| liked_tweet.recorded_time.is_some()));
+        assert!(!Mention::get_records(&conn, user_id, None, 0).unwrap().is_empty());
+        let profiles = store.get_profiles("suisei_hosimati", None, 0).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].recorded_time.as_deref().map(str::len), Some("2023-01-01T00:00:00.000Z".len()));
+
+        fs::remove_dir_all(&archive_dir).unwrap();
+    }
+}
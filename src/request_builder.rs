@@ -1,17 +1,45 @@
 use std::collections::HashSet;
 use std::error::Error;
+use std::ops::ControlFlow;
 use std::{thread, time};
 use reqwest::{blocking::{Client}};
 use rusqlite::Connection;
-use serde_json::Value;
 
 
-use crate::configuration::TaskType;
+use crate::api_response::{Response, Tweet, User};
+use crate::configuration::{SavedSearch, TaskType};
 use crate::errors::*;
-use crate::query_result::{self, UserDetail, LikedTweet, FollowingUser, FetchedUser, FollowingAction};
-use crate::query_result::{FetchedTweet, BasicUserDetail, TweetType, BasicTweet};
+use crate::query_result::{self, LikedTweet, FollowingUser, FetchedUser, FollowingAction};
+use crate::query_result::{FetchedTweet, BasicUserDetail, BasicTweet, UserDetail};
+use crate::engagement::{Engagement, EngagementKind};
+use crate::mention::Mention;
+use crate::paginator::{Fetcher, PageIter, PageStep, Paginator};
+use crate::raw_archive;
+use crate::saved_search_hit::SavedSearchHit;
+use crate::store::{SqliteStore, Store};
+use crate::worker::StopSignal;
 use crate::{configuration};
 
+/// Fetched tweets with the tweets and users they reference
+pub type TweetFetchResult = (Vec<FetchedTweet>, Vec<BasicTweet>, Vec<BasicUserDetail>);
+
+/// Fetched like records with the liked tweets and their authors
+pub type LikeFetchResult = (Vec<LikedTweet>, Vec<BasicTweet>, Vec<BasicUserDetail>);
+
+/// Fetched mentions with the mentioning tweets, the tweets they reference and their authors
+pub type MentionFetchResult = (Vec<Mention>, Vec<BasicTweet>, Vec<BasicUserDetail>);
+
+/// Fetched engagements with the quoting tweets, the engagers and the number of requests sent
+pub type EngagementFetchResult = (Vec<Engagement>, Vec<BasicTweet>, Vec<BasicUserDetail>, u16);
+
+/// Fetched search hits with the hit tweets, the tweets they reference and their authors
+pub type SearchFetchResult = (Vec<SavedSearchHit>, Vec<BasicTweet>, Vec<BasicUserDetail>);
+
+/// Expansions, tweet fields and media fields the monitored user's tweets are requested with
+pub(crate) const TWEET_EXPANSIONS: &str = "referenced_tweets.id.author_id,attachments.media_keys";
+pub(crate) const TWEET_FIELDS: &str = "author_id,referenced_tweets,entities,created_at,attachments,conversation_id,in_reply_to_user_id,edit_history_tweet_ids,edit_controls,note_tweet";
+pub(crate) const TWEET_MEDIA_FIELDS: &str = "media_key,type,url,preview_image_url,width,height,alt_text,duration_ms,variants";
+
 pub enum RequestMethod {
     Get, 
     Post,
@@ -33,44 +61,14 @@ impl UserInfoFetcher {
             .get("https://api.twitter.com/2/users/by")
             .query(&[
                 ("usernames".to_string(), self.username.clone()), 
-                ("user.fields".to_string(), "description,location".to_string())]
+                ("user.fields".to_string(), "description,location,url,profile_image_url,profile_banner_url,pinned_tweet_id,protected,verified".to_string())]
             ).header(
                 "Authorization", 
                 format!("Bearer {}", &conf.bearer_token)
             );
-        let response = request.send()?.text()?;
-        let raw_user: Value = serde_json::from_str(&response)?;
-        let mut user_detail = UserDetail {
-            id: String::new(), 
-            username: String::new(), 
-            name: String::new(), 
-            location: None, 
-            description: None, 
-        }; 
-        if let Value::Array(user_list) = &raw_user["data"] {
-            let user_entity = &user_list[0];
-            user_detail.id = match &user_entity["id"] {
-                Value::String(id) => id.clone(), 
-                _ => {return Err(Box::new(InvalidUserField::new("id")));}
-            }; 
-            user_detail.username = match &user_entity["username"] {
-                Value::String(username) => username.clone(), 
-                _ => {return Err(Box::new(InvalidUserField::new("username")));}
-            }; 
-            user_detail.name = match &user_entity["name"] {
-                Value::String(name) => name.clone(), 
-                _ => {return Err(Box::new(InvalidUserField::new("name")));}
-            }; 
-            user_detail.location = match &user_entity["location"] {
-                Value::String(location) => Some(location.clone()), 
-                _ => None
-            }; 
-            user_detail.description = match &user_entity["description"] {
-                Value::String(description) => Some(description.clone()), 
-                _ => None
-            }; 
-
-        }
+        let response = conf.transport.send(request)?;
+        raw_archive::archive_response(conf, raw_archive::USERS_BY, &self.username, &response);
+        let user_detail = parse_user_detail(&serde_json::from_str(&response)?)?;
 
         let mut fetched_user = FetchedUser::record(&conf.task_type);
         fetched_user.user = user_detail;
@@ -97,211 +95,55 @@ impl TweetFetcher {
     pub fn new(user_id: &str, since_tweet_id: Option<&str>) -> TweetFetcher {
         TweetFetcher { 
             user_id: user_id.to_string(), 
-            since_tweet_id: match since_tweet_id {
-                Some(tweet_id) => Some(tweet_id.to_string()), 
-                None => None
-            }
+            since_tweet_id: since_tweet_id.map(|tweet_id| tweet_id.to_string())
         }
     }
 
-    pub fn fetch(&self, conf: &configuration::Config) -> Result<(
-        Vec<FetchedTweet>, 
-        Vec<BasicTweet>, 
-        Vec<BasicUserDetail>
-    ), Box<dyn Error>> {
+    /// Fetches every page, returning the tweets with the number of requests sent
+    pub fn fetch(&self, conf: &configuration::Config, stop: &StopSignal) -> Result<(TweetFetchResult, u16), Box<dyn Error>> {
+        let mut requests: u16 = 0;
+        let fetch_result = collect_pages(Box::new(self.pages(conf, stop).inspect(|_| requests += 1)))?;
+        Ok((fetch_result, requests))
+    }
+
+    fn parse_page(&self, page: &Response<Vec<Tweet>>) -> Result<TweetFetchResult, Box<dyn Error>> {
+        let mut related_users: Vec<BasicUserDetail> = Vec::new(); 
+        let mut related_tweets: Vec<BasicTweet> = Vec::new();
+        let fetched_in_page = parse_tweet_page(page, &mut related_tweets, &mut related_users)?;
+        Ok((fetched_in_page, related_tweets, related_users))
+    }
+}
+
+impl Fetcher for TweetFetcher {
+    type Page = TweetFetchResult;
+
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, TweetFetchResult> {
         let client = Client::builder().build().expect("error in client builder");
         let query_url = format!("https://api.twitter.com/2/users/{}/tweets", &self.user_id);
         let mut request = client.get(&query_url).query(&[
-            ("expansions".to_string(), "referenced_tweets.id.author_id".to_string()), 
+            ("expansions".to_string(), TWEET_EXPANSIONS.to_string()), 
             ("max_results".to_string(), "100".to_string()), 
-            ("tweet.fields".to_string(), "referenced_tweets,entities,created_at".to_string()),
-            ("user.fields".to_string(), "id,name,username".to_string())
+            ("tweet.fields".to_string(), TWEET_FIELDS.to_string()),
+            ("user.fields".to_string(), "id,name,username".to_string()), 
+            ("media.fields".to_string(), TWEET_MEDIA_FIELDS.to_string())
         ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
 
-        match &self.since_tweet_id {
-            Some(since_twitter_id) => {
-                request = request.query(&[
-                    ("since_id", since_twitter_id)
-                ]);
-            }
-            None => ()
+        if let Some(since_twitter_id) = &self.since_tweet_id {
+            request = request.query(&[
+                ("since_id", since_twitter_id)
+            ]);
         }
 
-        let mut fetched_list: Vec<FetchedTweet> = Vec::new();
-        let mut related_users: Vec<BasicUserDetail> = Vec::new(); 
-        let mut related_tweets: Vec<BasicTweet> = Vec::new();
-        let mut page_token: Option<String> = None;
-        
-        loop {
-            let mut request_cloned = request.try_clone().expect("the request should be cloned");
-            if let Some(next_token) = &page_token {
-                request_cloned = request_cloned.query(&[("pagination_token".to_string(), next_token.clone())]);
-            }
-            let response = request_cloned.send()?.text()?;
-            let response_parsed: serde_json::Value = serde_json::from_str(&response)?;
-
-            let data_list = &response_parsed["data"];
-            match data_list {
-                Value::Array(tweet_list) => {
-                    let mut related_user_in_page = collect_include_users(&response_parsed["includes"]["users"])?;
-                    related_users.append(&mut related_user_in_page);
-        
-                    let related_tweet_raw = &response_parsed["includes"]["tweets"];
-                    if let Value::Array(related_tweet_list) = related_tweet_raw {
-                        for tweet_raw in related_tweet_list {
-                            let related_tweet_item = parse_related_tweet(tweet_raw)?;
-                            related_tweets.push(related_tweet_item);
-                        }
-                    }
-
-                    for tweet_item_raw in tweet_list {
-                        let mut tweet_item = FetchedTweet::new();
-
-                        if let Value::String(tweet_id) = &tweet_item_raw["id"] {
-                            tweet_item.id = tweet_id.to_owned();
-                        } else {
-                            return Err(Box::new(InvalidTweetField::new("id")));
-                        }
-
-                        if let Value::String(tweet_text) = &tweet_item_raw["text"] {
-                            tweet_item.text = tweet_text.to_owned();
-                        } else {
-                            return Err(Box::new(InvalidTweetField::new("text")));
-                        }
-
-                        if let Value::String(created_at) = &tweet_item_raw["created_at"] {
-                            tweet_item.created_at = created_at.to_owned();
-                        } else {
-                            return Err(Box::new(InvalidTweetField::new("created_at")));
-                        }
-
-                        if let Value::String(author_id) = &tweet_item_raw["author_id"] {
-                            tweet_item.author_id = author_id.to_owned();
-                        } else {
-                            return Err(Box::new(InvalidTweetField::new("created_at")));
-                        }
-
-                        if let Value::String(ref_type) = &tweet_item_raw["referenced_tweets"][0]["type"] {
-                            let related_tweet_id = match &tweet_item_raw["referenced_tweets"][0]["id"] {
-                                Value::String(id) => id.to_owned(), 
-                                _ => {
-                                    return Err(Box::new(InvalidTweetField::new("referenced_tweets.id")));
-                                }
-                            };
-                            
-                            let related_tweed_detail = query_result::find_by_id(
-                                &related_tweet_id, 
-                                &related_tweets
-                            ).cloned().unwrap_or(
-                                    BasicTweet { 
-                                        text: String::from("Unavailable tweet"), 
-                                        id: related_tweet_id.clone(), 
-                                        author_id: "".to_string(), 
-                                        hashtags: None
-                                    }
-                                );
-                                
-                            let related_user_detail = query_result::find_by_id(
-                                &related_tweed_detail.author_id, 
-                                &related_users).cloned().unwrap_or(
-                                    BasicUserDetail { 
-                                        id: (&related_tweed_detail.author_id).to_string(), 
-                                        username: "".to_string(), 
-                                        name: "".to_string() 
-                                    }
-                                );
-
-                            match ref_type.as_str() {
-                                "replied_to" => {
-                                    tweet_item.tweet_type = TweetType::Reply { 
-                                        tweet: related_tweed_detail, 
-                                        author: related_user_detail
-                                    };
-                                }
-                                "quoted" => {
-                                    tweet_item.tweet_type = TweetType::Retweet { 
-                                        tweet: related_tweed_detail, 
-                                        author: related_user_detail
-                                    };
-                                }
-                                "retweeted" => {
-                                    tweet_item.tweet_type = TweetType::Retweet { 
-                                        tweet: related_tweed_detail, 
-                                        author: related_user_detail
-                                    };
-                                }
-                                _ => { return Err(Box::new(InvalidTweetField::new("referenced_tweets.type"))); }
-                            }
-                        }
-
-                        if let Value::Array(hashtag_list) = &tweet_item_raw["entities"]["hashtags"] {
-                            for hashtag_item in hashtag_list {
-                                if let Value::String(hashtag) = &hashtag_item["tag"] {
-                                    if let None = tweet_item.hashtags {
-                                        tweet_item.hashtags = Some(Vec::new());
-                                    }
-
-                                    tweet_item.hashtags.as_mut().unwrap().push(hashtag.to_owned());
-                                }
-                            }
-                        }
-
-                        if let Value::Array(metion_list) = &tweet_item_raw["entities"]["mentions"] {
-                            for mention_entity in metion_list {
-                                if let Value::String(mentioned_username) = &mention_entity["username"] {
-                                    let mentioned_id = match &mention_entity["id"] {
-                                        Value::String(id) => id.to_owned(), 
-                                        _ => {
-                                            return Err(Box::new(InvalidTweetField::new("entities.mentions.id")));
-                                        }
-                                    };
-
-                                    if let None = tweet_item.mentions {
-                                        tweet_item.mentions = Some(Vec::new());
-                                    }
-
-                                    let basic_user_detail = query_result::find_by_id(
-                                        &mentioned_id, 
-                                        &related_users
-                                    ).cloned().unwrap_or(BasicUserDetail {
-                                        id: mentioned_id.clone(), 
-                                        username: mentioned_username.clone(), 
-                                        name: String::from("Unavailable account")
-                                    });
-
-                                    tweet_item.mentions.as_mut().unwrap().push(BasicUserDetail {
-                                        id: mentioned_id, 
-                                        username: mentioned_username.to_owned(), 
-                                        name: basic_user_detail.name
-                                    });
-                                }
-                            }
-                        }
-                        fetched_list.push(tweet_item);
-                    }
-                }
-                _ => {
-                    if let Value::Number(n_result) = &response_parsed["meta"]["result_count"] {
-                        if n_result.as_i64().expect("should return result num") == 0 {
-                            break;
-                        }
-                    } else {
-                        return Err(Box::new(InvalidTweetField::new("data")));
-                    }
-                }
-            }
-
-            page_token = match &response_parsed["meta"]["next_token"] {
-                Value::String(token) => Some(token.clone()), 
-                _ => { break; }
-            };
-        }
-        
-        fetched_list.reverse();
-        Ok((fetched_list, related_tweets, related_users))
+        Box::new(Paginator::new(request, |page: &Response<Vec<Tweet>>| Ok(ControlFlow::Continue(self.parse_page(page)?)))
+            .start_at(page_token)
+            .transport(&conf.transport)
+            .stop_on(stop)
+            .archive(conf, raw_archive::USER_TWEETS, &self.user_id))
     }
 
-
+    fn parse_archived(&self, _conf: &configuration::Config, body: &str, _fetched_at: &str) -> Result<TweetFetchResult, Box<dyn Error>> {
+        self.parse_page(&serde_json::from_str(body)?)
+    }
 }
 
 
@@ -314,122 +156,453 @@ impl LikeFetcher {
     pub fn new(user_id: &str, latest_recorded_id: Option<&str>) -> LikeFetcher {
         LikeFetcher { 
             user_id: user_id.to_string(), 
-            latest_recorded_id: match latest_recorded_id {
-                Some(latest_recorded_id) => Some(latest_recorded_id.to_string()), 
-                None => None 
-            }
+            latest_recorded_id: latest_recorded_id.map(|latest_recorded_id| latest_recorded_id.to_string())
         }
     }
 
-    pub fn fetch(&self, conf:&configuration::Config) -> Result<(Vec<LikedTweet>, Vec<BasicTweet>, Vec<BasicUserDetail>), Box<dyn Error>> {
-        let mut latest_recorded_id: Option<String> = None;
+    /// Fetches every page, returning the likes with the number of requests sent
+    pub fn fetch(&self, conf:&configuration::Config, stop: &StopSignal) -> Result<(LikeFetchResult, u16), Box<dyn Error>> {
+        let mut requests: u16 = 0;
+        let fetch_result = collect_pages(Box::new(self.pages(conf, stop).inspect(|_| requests += 1)))?;
+        Ok((fetch_result, requests))
+    }
+
+    /// Stops at the newest recorded like when monitoring
+    fn parse_page(&self, conf: &configuration::Config, page: &Response<Vec<Tweet>>) -> Result<PageStep<LikeFetchResult>, Box<dyn Error>> {
+        let mut latest_recorded_id: Option<&str> = None;
         if let TaskType::Monitoring = conf.task_type {
-            latest_recorded_id = self.latest_recorded_id.clone();
+            latest_recorded_id = self.latest_recorded_id.as_deref();
         }
 
+        let liked_list = match &page.data {
+            Some(liked_list) => liked_list,
+            None if page.is_empty_page() => { return Ok(ControlFlow::Continue(Default::default())); }
+            None => { return Err(Box::new(InvalidTweetField::new("data"))); }
+        };
+        let mut fetched_list: Vec<LikedTweet> = Vec::new();
+        let mut related_users = page.includes.basic_users();
+        let mut related_tweets: Vec<BasicTweet> = Vec::new();
+        for liked_tweet_raw in liked_list {
+            let related_tweet_item = liked_tweet_raw.to_basic_tweet()?;
+            if latest_recorded_id == Some(related_tweet_item.id.as_str()) {
+                return Ok(ControlFlow::Break((fetched_list, related_tweets, related_users)));
+            }
+
+            let mut liked_tweet_item = LikedTweet::record(&conf.task_type, &self.user_id);
+            liked_tweet_item.author = author_or_placeholder(&related_tweet_item.author_id, &mut related_users);
+            liked_tweet_item.tweet = related_tweet_item.clone();
+
+            related_tweets.push(related_tweet_item); 
+            fetched_list.push(liked_tweet_item);
+        }
+        Ok(ControlFlow::Continue((fetched_list, related_tweets, related_users)))
+    }
+}
+
+impl Fetcher for LikeFetcher {
+    type Page = LikeFetchResult;
+
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, LikeFetchResult> {
         let client = Client::builder().build().expect("error in client builder");
         let query_url = format!("https://api.twitter.com/2/users/{}/liked_tweets", &self.user_id);
         let request = client.get(&query_url).query(&[
             ("expansions".to_string(), "author_id".to_string()), 
             ("max_results".to_string(), "100".to_string()), 
-            ("tweet.fields".to_string(), "id,text,entities".to_string()),
+            ("tweet.fields".to_string(), "id,text,entities,note_tweet".to_string()),
             ("user.fields".to_string(), "id,name,username".to_string())
         ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
 
-        let mut fetched_list: Vec<LikedTweet> = Vec::new();
-        let mut related_users: Vec<BasicUserDetail> = Vec::new(); 
+        Box::new(Paginator::new(request, |page: &Response<Vec<Tweet>>| self.parse_page(conf, page))
+            .start_at(page_token)
+            .transport(&conf.transport)
+            .stop_on(stop)
+            .archive(conf, raw_archive::LIKED_TWEETS, &self.user_id)
+            .delay(time::Duration::from_secs(13)))
+    }
+
+    /// The likes are recorded at `fetched_at`
+    fn parse_archived(&self, conf: &configuration::Config, body: &str, fetched_at: &str) -> Result<LikeFetchResult, Box<dyn Error>> {
+        let (mut fetched_list, related_tweets, related_users) = page_records(self.parse_page(conf, &serde_json::from_str(body)?)?);
+        for liked_tweet_item in fetched_list.iter_mut() {
+            liked_tweet_item.recorded_time = Some(fetched_at.to_string());
+        }
+        Ok((fetched_list, related_tweets, related_users))
+    }
+}
+
+
+pub struct MentionsFetcher {
+    user_id: String,
+    since_tweet_id: Option<String>
+}
+
+impl MentionsFetcher {
+    pub fn new(user_id: &str, since_tweet_id: Option<&str>) -> MentionsFetcher {
+        MentionsFetcher {
+            user_id: user_id.to_string(),
+            since_tweet_id: since_tweet_id.map(|tweet_id| tweet_id.to_string())
+        }
+    }
+
+    /// Fetches every page, returning the mentions with the number of requests sent
+    pub fn fetch(&self, conf: &configuration::Config, stop: &StopSignal) -> Result<(MentionFetchResult, u16), Box<dyn Error>> {
+        let mut requests: u16 = 0;
+        let fetch_result = collect_pages(Box::new(self.pages(conf, stop).inspect(|_| requests += 1)))?;
+        Ok((fetch_result, requests))
+    }
+
+    fn parse_page(&self, page: &Response<Vec<Tweet>>) -> Result<MentionFetchResult, Box<dyn Error>> {
+        let mut related_users: Vec<BasicUserDetail> = Vec::new();
+        let mut related_tweets: Vec<BasicTweet> = Vec::new();
+        let fetched_in_page = parse_mention_page(&self.user_id, page, &mut related_tweets, &mut related_users)?;
+        Ok((fetched_in_page, related_tweets, related_users))
+    }
+}
+
+impl Fetcher for MentionsFetcher {
+    type Page = MentionFetchResult;
+
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, MentionFetchResult> {
+        let client = Client::builder().build().expect("error in client builder");
+        let query_url = format!("https://api.twitter.com/2/users/{}/mentions", &self.user_id);
+        let mut request = client.get(&query_url).query(&[
+            ("expansions".to_string(), "author_id,referenced_tweets.id,referenced_tweets.id.author_id".to_string()),
+            ("max_results".to_string(), "100".to_string()),
+            ("tweet.fields".to_string(), "author_id,referenced_tweets,entities,created_at,conversation_id,note_tweet".to_string()),
+            ("user.fields".to_string(), "id,name,username".to_string())
+        ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
+
+        if let Some(since_twitter_id) = &self.since_tweet_id {
+            request = request.query(&[
+                ("since_id", since_twitter_id)
+            ]);
+        }
+
+        Box::new(Paginator::new(request, |page: &Response<Vec<Tweet>>| Ok(ControlFlow::Continue(self.parse_page(page)?)))
+            .start_at(page_token)
+            .transport(&conf.transport)
+            .stop_on(stop)
+            .archive(conf, raw_archive::MENTIONS, &self.user_id)
+            .delay(time::Duration::from_secs(13)))
+    }
+
+    fn parse_archived(&self, _conf: &configuration::Config, body: &str, _fetched_at: &str) -> Result<MentionFetchResult, Box<dyn Error>> {
+        self.parse_page(&serde_json::from_str(body)?)
+    }
+}
+
+
+pub struct EngagementFetcher {
+    tweet_id: String,
+    kind: EngagementKind,
+    known_ids: HashSet<String>
+}
+
+impl EngagementFetcher {
+    /// `known_ids` are the engager ids, or quoting tweet ids for quotes, already stored; paging
+    /// stops at the first page containing one
+    pub fn new(tweet_id: &str, kind: EngagementKind, known_ids: HashSet<String>) -> EngagementFetcher {
+        EngagementFetcher {
+            tweet_id: tweet_id.to_string(),
+            kind,
+            known_ids
+        }
+    }
+
+    pub fn fetch(&self, conf: &configuration::Config, max_requests: u16, stop: &StopSignal) -> Result<EngagementFetchResult, Box<dyn Error>> {
+        let mut requests: u16 = 0;
+        let pages = self.pages(conf, stop).take(max_requests.into()).inspect(|_| requests += 1);
+        let (fetched_list, related_tweets, related_users) = collect_pages(Box::new(pages))?;
+        Ok((fetched_list, related_tweets, related_users, requests))
+    }
+
+    /// Name of the endpoint listing the engagements, also the name they are archived under
+    fn endpoint(&self) -> &'static str {
+        match self.kind {
+            EngagementKind::Like => raw_archive::LIKING_USERS,
+            EngagementKind::Retweet => raw_archive::RETWEETED_BY,
+            EngagementKind::Quote => raw_archive::QUOTE_TWEETS
+        }
+    }
+
+    fn parse_quote_page(&self, page: &Response<Vec<Tweet>>) -> Result<PageStep<<Self as Fetcher>::Page>, Box<dyn Error>> {
+        let quote_list = match &page.data {
+            Some(quote_list) => quote_list,
+            None if page.is_empty_page() => { return Ok(ControlFlow::Continue(Default::default())); }
+            None => { return Err(Box::new(InvalidTweetField::new("data"))); }
+        };
+        let mut fetched_list: Vec<Engagement> = Vec::new();
+        let mut related_users = page.includes.basic_users();
         let mut related_tweets: Vec<BasicTweet> = Vec::new();
-        let mut page_token: Option<String> = None;
-        
-        'over_pages: loop {
-            let mut request_cloned = request.try_clone().expect("Should be able to clone request");
-            if let Some(next_token) = &page_token {
-                request_cloned = request_cloned.query(&[("pagination_token".to_string(), next_token.clone())]);
+        let mut reached_known = false;
+        for quote_raw in quote_list {
+            let quote_tweet = quote_raw.to_basic_tweet()?;
+            if self.known_ids.contains(&quote_tweet.id) {
+                reached_known = true;
+                continue;
             }
-            let response = request_cloned.send()?.text()?;
-            let response_parsed: serde_json::Value = serde_json::from_str(&response)?;
-            
-            let data_list = &response_parsed["data"];
-            
-            match data_list {
-                Value::Array(liked_list) => {
-                    let mut related_user_in_page = collect_include_users(&response_parsed["includes"]["users"])?;
-                    related_users.append(&mut related_user_in_page);
-                    for liked_tweet_raw in liked_list {
-                        let mut liked_tweet_item = LikedTweet::record(&conf.task_type, &self.user_id);
-                        let related_tweet_item = parse_related_tweet(liked_tweet_raw)?;
-                        if let Some(latest_id) = &latest_recorded_id {
-                            if latest_id.as_str() == related_tweet_item.id.as_str() {
-                                break 'over_pages;
-                            }
-                        }
-                        let basic_user_info = query_result::find_by_id(&related_tweet_item.author_id, &related_users).cloned().unwrap_or(BasicUserDetail {
-                            id: related_tweet_item.author_id.clone(), 
-                            username: String::from("unavailable_account"), 
-                            name: String::from("Unavailable account")
-                        });
-    
-                        liked_tweet_item.tweet = related_tweet_item.clone();
-                        liked_tweet_item.author = basic_user_info.clone();
-    
-                        related_tweets.push(related_tweet_item); 
-                        fetched_list.push(liked_tweet_item);
-                    }
-                }
-                _ => {
-                    if let Value::Number(n_result) = &response_parsed["meta"]["result_count"] {
-                        if n_result.as_i64().expect("should return result num") == 0 {
-                            break 'over_pages;
-                        }
-                    } else {
-                        return Err(Box::new(InvalidTweetField::new("data")));
-                    }
-                }
+            fetched_list.push(Engagement {
+                tweet_id: self.tweet_id.clone(),
+                kind: self.kind,
+                user: author_or_placeholder(&quote_tweet.author_id, &mut related_users),
+                quote_tweet_id: Some(quote_tweet.id.clone()),
+                time: quote_raw.created_at()?
+            });
+            related_tweets.push(quote_tweet);
+        }
+        Ok(page_step((fetched_list, related_tweets, related_users), reached_known))
+    }
+
+    /// Likes and retweets carry no time; they are recorded at `fetched_time`
+    fn parse_engager_page(&self, page: &Response<Vec<User>>, fetched_time: &str) -> Result<PageStep<<Self as Fetcher>::Page>, Box<dyn Error>> {
+        let engager_list = match &page.data {
+            Some(engager_list) => engager_list,
+            None if page.is_empty_page() => { return Ok(ControlFlow::Continue(Default::default())); }
+            None => { return Err(Box::new(InvalidUserField::new("data"))); }
+        };
+        let mut fetched_list: Vec<Engagement> = Vec::new();
+        let mut related_users: Vec<BasicUserDetail> = Vec::new();
+        let mut reached_known = false;
+        for engager_raw in engager_list {
+            let engager = engager_raw.to_basic_user();
+            if self.known_ids.contains(&engager.id) {
+                reached_known = true;
+                continue;
             }
+            fetched_list.push(Engagement {
+                tweet_id: self.tweet_id.clone(),
+                kind: self.kind,
+                user: engager.clone(),
+                quote_tweet_id: None,
+                time: fetched_time.to_string()
+            });
+            related_users.push(engager);
+        }
+        Ok(page_step((fetched_list, Vec::new(), related_users), reached_known))
+    }
+}
 
-            page_token = match &response_parsed["meta"]["next_token"] {
-                Value::String(token) => {
-                    thread::sleep(time::Duration::from_secs(13));
-                    Some(token.clone())
-                }, 
-                _ => { break 'over_pages; }
-            };
+impl Fetcher for EngagementFetcher {
+    /// Engagements with the quoting tweets and the engagers
+    type Page = (Vec<Engagement>, Vec<BasicTweet>, Vec<BasicUserDetail>);
 
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, Self::Page> {
+        let client = Client::builder().build().expect("error in client builder");
+        let query_url = format!("https://api.twitter.com/2/tweets/{}/{}", &self.tweet_id, self.endpoint());
+        let request = client.get(&query_url).query(&[
+            ("max_results".to_string(), "100".to_string()),
+            ("user.fields".to_string(), "id,name,username".to_string())
+        ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
+
+        if let EngagementKind::Quote = self.kind {
+            let request = request.query(&[
+                ("expansions".to_string(), "author_id".to_string()),
+                ("tweet.fields".to_string(), "author_id,created_at,entities,conversation_id,referenced_tweets,note_tweet".to_string())
+            ]);
+            return Box::new(Paginator::new(request, |page: &Response<Vec<Tweet>>| self.parse_quote_page(page))
+                .start_at(page_token)
+                .transport(&conf.transport)
+                .stop_on(stop)
+                .archive(conf, self.endpoint(), &self.tweet_id)
+                .delay(time::Duration::from_secs(12)));
         }
 
-        fetched_list.reverse();
+        let fetched_time = query_result::now();
+        Box::new(Paginator::new(request, move |page: &Response<Vec<User>>| self.parse_engager_page(page, &fetched_time))
+            .start_at(page_token)
+            .transport(&conf.transport)
+            .stop_on(stop)
+            .archive(conf, self.endpoint(), &self.tweet_id)
+            .delay(time::Duration::from_secs(12)))
+    }
+
+    fn parse_archived(&self, _conf: &configuration::Config, body: &str, fetched_at: &str) -> Result<Self::Page, Box<dyn Error>> {
+        let page_step = match self.kind {
+            EngagementKind::Quote => self.parse_quote_page(&serde_json::from_str(body)?)?,
+            _ => self.parse_engager_page(&serde_json::from_str(body)?, fetched_at)?
+        };
+        Ok(page_records(page_step))
+    }
+}
+
+
+pub struct SearchFetcher {
+    search: SavedSearch,
+    since_tweet_id: Option<String>
+}
+
+impl SearchFetcher {
+    pub fn new(search: &SavedSearch, since_tweet_id: Option<&str>) -> SearchFetcher {
+        SearchFetcher {
+            search: search.clone(),
+            since_tweet_id: since_tweet_id.map(|tweet_id| tweet_id.to_string())
+        }
+    }
+
+    /// Fetches the hits of the last 7 days, or those newer than `since_tweet_id`
+    pub fn fetch(&self, conf: &configuration::Config, stop: &StopSignal) -> Result<SearchFetchResult, Box<dyn Error>> {
+        collect_pages(self.pages(conf, stop))
+    }
+
+    fn parse_page(&self, page: &Response<Vec<Tweet>>) -> Result<SearchFetchResult, Box<dyn Error>> {
+        let hit_list = match &page.data {
+            Some(hit_list) => hit_list,
+            None if page.is_empty_page() => { return Ok(Default::default()); }
+            None => { return Err(Box::new(InvalidTweetField::new("data"))); }
+        };
+        let mut fetched_list: Vec<SavedSearchHit> = Vec::new();
+        let mut related_users = page.includes.basic_users();
+        let mut related_tweets = page.includes.basic_tweets()?;
+        for hit_raw in hit_list {
+            let tweet = hit_raw.to_basic_tweet()?;
+            fetched_list.push(SavedSearchHit {
+                search_name: self.search.name.clone(),
+                created_at: hit_raw.created_at()?,
+                author: author_or_placeholder(&tweet.author_id, &mut related_users),
+                tweet: tweet.clone()
+            });
+            related_tweets.push(tweet);
+        }
         Ok((fetched_list, related_tweets, related_users))
     }
 }
 
+impl Fetcher for SearchFetcher {
+    type Page = SearchFetchResult;
+
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, SearchFetchResult> {
+        let client = Client::builder().build().expect("error in client builder");
+        let mut request = client.get("https://api.twitter.com/2/tweets/search/recent").query(&[
+            ("query".to_string(), self.search.query.clone()),
+            ("expansions".to_string(), "author_id,referenced_tweets.id".to_string()),
+            ("max_results".to_string(), "100".to_string()),
+            ("tweet.fields".to_string(), "author_id,referenced_tweets,entities,created_at,conversation_id,note_tweet".to_string()),
+            ("user.fields".to_string(), "id,name,username".to_string())
+        ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
+
+        if let Some(since_twitter_id) = &self.since_tweet_id {
+            request = request.query(&[
+                ("since_id", since_twitter_id)
+            ]);
+        }
+
+        Box::new(Paginator::new(request, |page: &Response<Vec<Tweet>>| Ok(ControlFlow::Continue(self.parse_page(page)?)))
+            .start_at(page_token)
+            .transport(&conf.transport)
+            .stop_on(stop)
+            .archive(conf, raw_archive::SEARCH_RECENT, &self.search.name)
+            // the search endpoint pages with `next_token` instead of `pagination_token`
+            .token_param("next_token")
+            .delay(time::Duration::from_secs(2)))
+    }
+
+    fn parse_archived(&self, _conf: &configuration::Config, body: &str, _fetched_at: &str) -> Result<SearchFetchResult, Box<dyn Error>> {
+        self.parse_page(&serde_json::from_str(body)?)
+    }
+}
+
 
 pub struct FollowingFetcher{
     user_id: String, 
     following_ids: Option<Vec<String>>
 }
 
+/// Newly followed users with their details, and the ids of every followed user of the page
+pub type FollowingPage = (Vec<FollowingUser>, Vec<BasicUserDetail>, Vec<String>);
+
+/// Follows and unfollows with the followed users and the number of requests sent
+pub type FollowingFetchResult = (Vec<FollowingUser>, Vec<BasicUserDetail>, u16);
+
 impl FollowingFetcher {
     pub fn new(user_id: &str, following_ids: Option<Vec<String>>) -> FollowingFetcher{
         FollowingFetcher {
             user_id: user_id.to_string(), 
-            following_ids: following_ids,
+            following_ids, 
         }
     }
 
-    pub fn fetch(&self, conf: &configuration::Config, conn: &Connection) -> Result<(Vec<FollowingUser>, Vec<BasicUserDetail>), Box<dyn Error>> {
-        let mut latest_records= None;
-        if let TaskType::Monitoring = conf.task_type {
-            latest_records = (&self.following_ids).as_ref();
+    /// Fetches every page, comparing the followed users with the recorded ones when monitoring
+    pub fn fetch(&self, conf: &configuration::Config, conn: &Connection, stop: &StopSignal) -> Result<FollowingFetchResult, Box<dyn Error>> {
+        let mut fetched_list: Vec<FollowingUser> = Vec::new();
+        let mut related_users: Vec<BasicUserDetail> = Vec::new();
+        let mut current_following_ids: Vec<String> = Vec::new();
+        let mut requests: u16 = 0;
+        for page in self.pages(conf, stop) {
+            requests += 1;
+            let (mut fetched_in_page, mut users_in_page, mut ids_in_page) = page?.records;
+            fetched_list.append(&mut fetched_in_page);
+            related_users.append(&mut users_in_page);
+            current_following_ids.append(&mut ids_in_page);
+        }
+
+        let current_following_set: HashSet<&String> = current_following_ids.iter().collect();
+        let mut prev_following_set: HashSet<&String> = HashSet::new();
+        if let Some(prev_following) = self.latest_records(conf) {
+            prev_following_set = prev_following.iter().collect();
+        }
+        for unfollowed_id in prev_following_set.difference(&current_following_set) {
+            let mut unfollowed_entity = FollowingUser::record(&conf.task_type, &self.user_id);
+            unfollowed_entity.action = FollowingAction::Unfollow;
+            unfollowed_entity.followed_user = SqliteStore::new(conn).get_user(unfollowed_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            fetched_list.push(unfollowed_entity);
+        }
+
+
+        fetched_list.reverse();
+        Ok((fetched_list, related_users, requests))
+    }
+
+    /// The recorded following ids, newest first, when monitoring
+    fn latest_records(&self, conf: &configuration::Config) -> Option<&Vec<String>> {
+        match conf.task_type {
+            TaskType::Monitoring => self.following_ids.as_ref(),
+            _ => None
         }
-        let mut latest_record_id: Option<&str> = None;
-        if let Some(latest_record_list) = latest_records {
-            let old_following_num = latest_record_list.len();
-            if old_following_num > 0 {
-                latest_record_id = Some(&latest_record_list[0].as_str());
+    }
+
+    /// Users from the newest recorded one on, and users recorded before, are not new.
+    /// `existing_following` carries over to the next pages once the newest recorded user is met.
+    fn parse_page(&self, conf: &configuration::Config, page: &Response<Vec<User>>, existing_following: &mut bool) -> Result<FollowingPage, Box<dyn Error>> {
+        let latest_records = self.latest_records(conf);
+        let latest_record_id: Option<&str> = latest_records
+            .and_then(|latest_record_list| latest_record_list.first())
+            .map(|latest_record_id| latest_record_id.as_str());
+
+        let following_list = match &page.data {
+            Some(following_list) => following_list,
+            None if page.is_empty_page() => { return Ok(Default::default()); }
+            None => { return Err(Box::new(InvalidUserField::new("data"))); }
+        };
+        let mut fetched_list: Vec<FollowingUser> = Vec::new();
+        let mut related_users: Vec<BasicUserDetail> = Vec::new();
+        let mut following_ids: Vec<String> = Vec::new();
+        for following_user_raw in following_list {
+            let following_id = following_user_raw.id.clone();
+            if latest_record_id == Some(following_id.as_str()) {
+                *existing_following = true;
+            }
+            let recorded = latest_records.is_some_and(|latest_record_list| latest_record_list.contains(&following_id));
+            following_ids.push(following_id);
+
+            if !*existing_following && !recorded {
+                let following_user_detail = following_user_raw.to_basic_user();
+                let mut following_entity = FollowingUser::record(&conf.task_type, &self.user_id);
+                following_entity.followed_user = following_user_detail.clone();
+
+                fetched_list.push(following_entity);
+                related_users.push(following_user_detail);
             }
         }
-        
+        Ok((fetched_list, related_users, following_ids))
+    }
+}
+
+impl Fetcher for FollowingFetcher {
+    type Page = FollowingPage;
 
+    /// Pages through every followed user, since unfollows are only found by comparing the full
+    /// list
+    fn pages_from<'a>(&'a self, conf: &'a configuration::Config, page_token: Option<String>, stop: &StopSignal) -> PageIter<'a, FollowingPage> {
         let client = Client::builder().build().expect("error in client builder");
         let query_url = format!("https://api.twitter.com/2/users/{}/following", &self.user_id);
         let request = client.get(&query_url).query(&[
@@ -437,169 +610,422 @@ impl FollowingFetcher {
             ("user.fields".to_string(), "id,name,username".to_string())
         ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
 
-        let mut fetched_list: Vec<FollowingUser> = Vec::new();
-        let mut related_users: Vec<BasicUserDetail> = Vec::new(); 
-        let mut current_following_ids: Vec<String> = Vec::new();
-        let mut page_token: Option<String> = None;
-        
         let mut existing_following = false;
-        let mut req_num = 0;
-        'over_pages: loop {
-            let mut request_cloned = request.try_clone().expect("Should be able to clone request");
-            if let Some(next_token) = &page_token {
-                request_cloned = request_cloned.query(&[("pagination_token".to_string(), next_token.clone())]);
-            }
-            let response = request_cloned.send()?.text()?;
-            let response_parsed: serde_json::Value = serde_json::from_str(&response)?;
-            
-            let data_list = &response_parsed["data"];
-
-            match data_list {
-                Value::Array(following_list) => {
-                    for following_user_raw in following_list {
-                        let mut following_entity = FollowingUser::record(&conf.task_type, &self.user_id);
-
-                        let following_id = match &following_user_raw["id"] {
-                            Value::String(id) => id.to_string(), 
-                            _ => { return Err(Box::new(InvalidUserField::new("id"))); }
-                        };
-                        current_following_ids.push(following_id.clone());
-
-                        if !existing_following  {
-                            if let Some(latest_id) = latest_record_id {
-                                if latest_id == following_id.as_str() {
-                                    existing_following = true;
-                                }
-                            }
-                        }
-                    
-
-                        if !existing_following {
-                            let following_username = match &following_user_raw["username"] {
-                                Value::String(username) => username.to_string(), 
-                                _ => { return Err(Box::new(InvalidUserField::new("username"))); }
-                            };
-    
-                            let following_name = match &following_user_raw["name"] {
-                                Value::String(name) => name.to_string(), 
-                                _ => { return Err(Box::new(InvalidUserField::new("name"))); }
-                            };
-    
-                            let following_user_detail = BasicUserDetail {
-                                id: following_id, 
-                                username: following_username, 
-                                name: following_name,
-                            };
-    
-                            following_entity.followed_user = following_user_detail.clone();
-    
-                            fetched_list.push(following_entity);
-                            related_users.push(following_user_detail);
-                        }
-                    }
-                }
-                _ => {
-                    if let Value::Number(n_result) = &response_parsed["meta"]["result_count"] {
-                        if n_result.as_i64().expect("should return result num") == 0 {
-                            break 'over_pages;
-                        }
-                    } else {
-                        return Err(Box::new(InvalidUserField::new("data")));
-                    }
-                }
-            }
+        Box::new(Paginator::new(request, move |page: &Response<Vec<User>>| Ok(ControlFlow::Continue(self.parse_page(conf, page, &mut existing_following)?)))
+            .start_at(page_token)
+            .transport(&conf.transport)
+            .stop_on(stop)
+            .archive(conf, raw_archive::FOLLOWING, &self.user_id)
+            .pause_every(3, time::Duration::from_secs(180)))
+    }
 
-            page_token = match &response_parsed["meta"]["next_token"] {
-                Value::String(token) => {
-                    req_num += 1;
-                    if req_num % 3 == 0 {
-                        thread::sleep(time::Duration::from_secs(180));
-                    }
-                    Some(token.clone())
-                }, 
-                _ => { break 'over_pages; }
-            };
+    /// Only new follows are found in a single page; the follows are recorded at `fetched_at`
+    fn parse_archived(&self, conf: &configuration::Config, body: &str, fetched_at: &str) -> Result<FollowingPage, Box<dyn Error>> {
+        let (mut fetched_list, related_users, following_ids) = self.parse_page(conf, &serde_json::from_str(body)?, &mut false)?;
+        for following_entity in fetched_list.iter_mut() {
+            following_entity.recorded_time = Some(fetched_at.to_string());
         }
+        Ok((fetched_list, related_users, following_ids))
+    }
+}
 
-        let current_following_set: HashSet<&String> = current_following_ids.iter().collect();
-        let mut prev_following_set: HashSet<&String> = HashSet::new();
-        if let Some(prev_following) = latest_records {
-            prev_following_set = prev_following.into_iter().collect();
-        }
-        for unfollowed_id in prev_following_set.difference(&current_following_set) {
-            let mut unfollowed_entity = FollowingUser::record(&conf.task_type, &self.user_id);
-            unfollowed_entity.action = FollowingAction::Unfollow;
-            unfollowed_entity.followed_user = BasicUserDetail::get_record(conn, unfollowed_id)?;
-            fetched_list.push(unfollowed_entity);
+
+/// Looked up tweets with their authors, and the ids the API reported errors for
+pub type TweetLookupResult = (Vec<BasicTweet>, Vec<BasicUserDetail>, Vec<LookupError>);
+
+/// Why a looked up tweet or user was not returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupError {
+    pub resource_id: String, 
+    /// Error title, e.g. `Not Found Error` for deleted tweets or `Authorization Error` for
+    /// protected ones
+    pub title: String
+}
+
+/// Looks tweets up by id, e.g. the parents of stored replies
+pub struct TweetLookupFetcher {
+    tweet_ids: Vec<String>
+}
+
+impl TweetLookupFetcher {
+    pub fn new(tweet_ids: &[String]) -> TweetLookupFetcher {
+        TweetLookupFetcher { tweet_ids: tweet_ids.to_vec() }
+    }
+
+    /// Returns the tweets found with their authors. Deleted or protected tweets come back as
+    /// lookup errors.
+    pub fn fetch(&self, conf: &configuration::Config) -> Result<TweetLookupResult, Box<dyn Error>> {
+        let client = Client::builder().build().expect("error in client builder");
+        let mut fetched_tweets: Vec<BasicTweet> = Vec::new();
+        let mut related_users: Vec<BasicUserDetail> = Vec::new(); 
+        let mut lookup_errors: Vec<LookupError> = Vec::new();
+
+        // the endpoint accepts up to 100 ids per request
+        for (chunk_index, id_chunk) in self.tweet_ids.chunks(100).enumerate() {
+            if chunk_index > 0 {
+                thread::sleep(time::Duration::from_secs(3));
+            }
+            let request = client.get("https://api.twitter.com/2/tweets").query(&[
+                ("ids".to_string(), id_chunk.join(",")), 
+                ("expansions".to_string(), "author_id".to_string()), 
+                ("tweet.fields".to_string(), "id,text,author_id,entities,conversation_id,referenced_tweets,note_tweet".to_string()),
+                ("user.fields".to_string(), "id,name,username".to_string())
+            ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
+            let response = conf.transport.send(request)?;
+            raw_archive::archive_response(conf, raw_archive::TWEET_LOOKUP, "ids", &response);
+            let response_parsed: Response<Vec<Tweet>> = serde_json::from_str(&response)?;
+
+            let (mut tweets_in_page, mut users_in_page, mut errors_in_page) = parse_tweet_lookup(&response_parsed)?;
+            fetched_tweets.append(&mut tweets_in_page);
+            related_users.append(&mut users_in_page);
+            lookup_errors.append(&mut errors_in_page);
         }
 
+        Ok((fetched_tweets, related_users, lookup_errors))
+    }
+}
 
-        fetched_list.reverse();
-        Ok((fetched_list, related_users))
-    }
-}
-
-
-fn collect_include_users(include_users_raw: &Value) -> Result<Vec<BasicUserDetail>, Box<dyn Error>> {
-    let mut related_users: Vec<BasicUserDetail> = Vec::new();
-    if let Value::Array(related_user_list) = include_users_raw {
-        for user_entity_raw in related_user_list {
-            let username  = match &user_entity_raw["username"] {
-                Value::String(username) => username.to_owned(), 
-                _ => { return Err(Box::new(InvalidTweetField::new("includes.users.username"))); }
-            };
-            let user_id = match &user_entity_raw["id"] {
-                Value::String(id) => id.to_owned(), 
-                _ => { return Err(Box::new(InvalidTweetField::new("includes.users.id"))); }
-            };
-            let name = match &user_entity_raw["name"] {
-                Value::String(name) => name.to_owned(), 
-                _ => { return Err(Box::new(InvalidTweetField::new("includes.users.name"))); }
-            };
-
-            related_users.push(BasicUserDetail {
-                id: user_id, 
-                username: username, 
-                name: name
-            });
+
+/// Looks users up by id, e.g. accounts only known from placeholders
+pub struct UserLookupFetcher {
+    user_ids: Vec<String>
+}
+
+impl UserLookupFetcher {
+    pub fn new(user_ids: &[String]) -> UserLookupFetcher {
+        UserLookupFetcher { user_ids: user_ids.to_vec() }
+    }
+
+    /// Returns the users found. Deleted or suspended accounts come back as lookup errors.
+    pub fn fetch(&self, conf: &configuration::Config) -> Result<(Vec<BasicUserDetail>, Vec<LookupError>), Box<dyn Error>> {
+        let client = Client::builder().build().expect("error in client builder");
+        let mut fetched_users: Vec<BasicUserDetail> = Vec::new();
+        let mut lookup_errors: Vec<LookupError> = Vec::new();
+
+        // the endpoint accepts up to 100 ids per request
+        for (chunk_index, id_chunk) in self.user_ids.chunks(100).enumerate() {
+            if chunk_index > 0 {
+                thread::sleep(time::Duration::from_secs(3));
+            }
+            let request = client.get("https://api.twitter.com/2/users").query(&[
+                ("ids".to_string(), id_chunk.join(",")), 
+                ("user.fields".to_string(), "id,name,username".to_string())
+            ]).header("Authorization", format!("Bearer {}", &conf.bearer_token));
+            let response = conf.transport.send(request)?;
+            raw_archive::archive_response(conf, raw_archive::USER_LOOKUP, "ids", &response);
+            let response_parsed: Response<Vec<User>> = serde_json::from_str(&response)?;
+
+            let (mut users_in_page, mut errors_in_page) = parse_user_lookup(&response_parsed)?;
+            fetched_users.append(&mut users_in_page);
+            lookup_errors.append(&mut errors_in_page);
         }
-    } else {
-        return Err(Box::new(InvalidUserList::new()));
-    }
-
-    Ok(related_users)
-}
-
-
-fn parse_related_tweet(single_tweet_raw: &Value) -> Result<BasicTweet, Box<dyn Error>> {
-    let mut related_tweet_item = BasicTweet {
-        author_id: String::new(), 
-        id: String::new(), 
-        text: String::new(), 
-        hashtags: None
-    };
-    match &single_tweet_raw["text"] {
-        Value::String(text) => { related_tweet_item.text = text.to_owned(); }
-        _ => { return Err(Box::new(InvalidTweetField::new("includes.tweets.text"))); }
-    };
-    match &single_tweet_raw["author_id"] {
-        Value::String(author_id) => { related_tweet_item.author_id = author_id.to_owned(); }
-        _ => { return Err(Box::new(InvalidTweetField::new("includes.tweets.author_id"))); }
-    };
-    match &single_tweet_raw["id"] {
-        Value::String(tweet_id) => { related_tweet_item.id = tweet_id.to_owned(); }
-        _ => { return Err(Box::new(InvalidTweetField::new("includes.tweets.id"))); }
+
+        Ok((fetched_users, lookup_errors))
+    }
+}
+
+
+/// Parses a `users/by` response of `UserInfoFetcher`
+pub(crate) fn parse_user_detail(response: &Response<Vec<User>>) -> Result<UserDetail, Box<dyn Error>> {
+    match response.data.as_deref() {
+        Some([user_entity, ..]) => Ok(user_entity.to_user_detail()),
+        _ => Err(Box::new(InvalidUserField::new("data")))
+    }
+}
+
+
+/// Parses a payload holding a single tweet of a monitored user in `data`, such as a line of the
+/// filtered stream, the same way `TweetFetcher` parses its pages
+pub fn parse_tweet_payload(payload: &Response<Tweet>) -> Result<TweetFetchResult, Box<dyn Error>> {
+    let tweet_raw = match &payload.data {
+        Some(tweet_raw) => tweet_raw,
+        None => { return Err(Box::new(InvalidTweetField::new("data"))); }
+    }; 
+    let mut related_users = payload.includes.basic_users();
+    let mut related_tweets = payload.includes.basic_tweets()?;
+    let tweet_item = tweet_raw.to_fetched_tweet(&mut related_tweets, &mut related_users, &payload.includes.media())?;
+    Ok((vec![tweet_item], related_tweets, related_users))
+}
+
+
+/// Parses a timeline page of `TweetFetcher`, adding what the tweets reference to the related
+/// lists. An empty page yields no tweets.
+fn parse_tweet_page(
+    page: &Response<Vec<Tweet>>,
+    related_tweets: &mut Vec<BasicTweet>, 
+    related_users: &mut Vec<BasicUserDetail>
+) -> Result<Vec<FetchedTweet>, Box<dyn Error>> {
+    let tweet_list = match &page.data {
+        Some(tweet_list) => tweet_list,
+        None if page.is_empty_page() => { return Ok(Vec::new()); }
+        None => { return Err(Box::new(InvalidTweetField::new("data"))); }
+    }; 
+    related_users.append(&mut page.includes.basic_users());
+    related_tweets.append(&mut page.includes.basic_tweets()?);
+    let related_media = page.includes.media();
+
+    tweet_list.iter()
+        .map(|tweet_item_raw| tweet_item_raw.to_fetched_tweet(related_tweets, related_users, &related_media))
+        .collect()
+}
+
+
+/// Parses a page of `MentionsFetcher`; the mentioning tweets go to the related tweets as well
+fn parse_mention_page(
+    user_id: &str,
+    page: &Response<Vec<Tweet>>,
+    related_tweets: &mut Vec<BasicTweet>, 
+    related_users: &mut Vec<BasicUserDetail>
+) -> Result<Vec<Mention>, Box<dyn Error>> {
+    let mention_list = match &page.data {
+        Some(mention_list) => mention_list,
+        None if page.is_empty_page() => { return Ok(Vec::new()); }
+        None => { return Err(Box::new(InvalidTweetField::new("data"))); }
+    }; 
+    related_users.append(&mut page.includes.basic_users());
+    related_tweets.append(&mut page.includes.basic_tweets()?);
+
+    let mut mentions: Vec<Mention> = Vec::new();
+    for mention_raw in mention_list {
+        // the mentioning tweet itself is stored in `tweet_dict`
+        let tweet = mention_raw.to_basic_tweet()?;
+        let author = author_or_placeholder(&tweet.author_id, related_users);
+        let tweet_type = mention_raw.tweet_type(related_tweets, related_users)?;
+
+        related_tweets.push(tweet.clone());
+        mentions.push(Mention {
+            user_id: user_id.to_string(), 
+            created_at: mention_raw.created_at()?,
+            tweet,
+            author,
+            tweet_type
+        });
+    }
+    Ok(mentions)
+}
+
+
+/// Parses a page of `TweetLookupFetcher`. A page without `data` is valid when every id of it is
+/// unavailable.
+pub(crate) fn parse_tweet_lookup(page: &Response<Vec<Tweet>>) -> Result<TweetLookupResult, Box<dyn Error>> {
+    let lookup_errors = page.lookup_errors();
+    let tweets = match &page.data {
+        Some(tweet_list) => tweet_list.iter().map(|tweet_raw| tweet_raw.to_basic_tweet()).collect::<Result<Vec<BasicTweet>, _>>()?,
+        None if !lookup_errors.is_empty() => Vec::new(),
+        None => { return Err(Box::new(InvalidTweetField::new("data"))); }
     }; 
-    if let Value::Array(hashtag_list) = &single_tweet_raw["entities"]["hashtags"] {
-        for hashtag_item in hashtag_list {
-            if let Value::String(hashtag) = &hashtag_item["tag"] {
-                if let None = related_tweet_item.hashtags {
-                    related_tweet_item.hashtags = Some(Vec::new());
+    Ok((tweets, page.includes.basic_users(), lookup_errors))
+}
+
+
+/// Parses a page of `UserLookupFetcher`. A page without `data` is valid when every id of it is
+/// unavailable.
+pub(crate) fn parse_user_lookup(page: &Response<Vec<User>>) -> Result<(Vec<BasicUserDetail>, Vec<LookupError>), Box<dyn Error>> {
+    let lookup_errors = page.lookup_errors();
+    let users = match &page.data {
+        Some(user_list) => user_list.iter().map(|user| user.to_basic_user()).collect(),
+        None if !lookup_errors.is_empty() => Vec::new(),
+        None => { return Err(Box::new(InvalidUserField::new("data"))); }
+    }; 
+    Ok((users, lookup_errors))
+}
+
+
+/// Fetched records with the related tweets and users
+type FetchResult<R, T, U> = (Vec<R>, Vec<T>, Vec<U>);
+
+/// Concatenates the pages of a fetcher, the records reversed to oldest first
+fn collect_pages<R, T, U>(pages: PageIter<FetchResult<R, T, U>>) -> Result<FetchResult<R, T, U>, Box<dyn Error>> {
+    let mut fetched_list: Vec<R> = Vec::new();
+    let mut related_tweets: Vec<T> = Vec::new();
+    let mut related_users: Vec<U> = Vec::new();
+    for page in pages {
+        let (mut fetched_in_page, mut tweets_in_page, mut users_in_page) = page?.records;
+        fetched_list.append(&mut fetched_in_page);
+        related_tweets.append(&mut tweets_in_page);
+        related_users.append(&mut users_in_page);
+    }
+    fetched_list.reverse();
+    Ok((fetched_list, related_tweets, related_users))
+}
+
+
+/// What a page was parsed into, wherever the paging would have stopped
+fn page_records<P>(page_step: PageStep<P>) -> P {
+    match page_step {
+        ControlFlow::Continue(records) | ControlFlow::Break(records) => records
+    }
+}
+
+
+/// Ends the paging at a page that reached an already stored record
+fn page_step<P>(page: P, reached_known: bool) -> PageStep<P> {
+    match reached_known {
+        true => ControlFlow::Break(page),
+        false => ControlFlow::Continue(page)
+    }
+}
+
+
+/// The author of a fetched tweet from the related users, or a placeholder added to them when the
+/// includes lack it
+fn author_or_placeholder(author_id: &str, related_users: &mut Vec<BasicUserDetail>) -> BasicUserDetail {
+    match query_result::find_by_id(author_id, related_users) {
+        Some(related_user) => related_user.clone(), 
+        None => {
+            let placeholder = BasicUserDetail::placeholder(author_id);
+            related_users.push(placeholder.clone());
+            placeholder
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::checkpoint::{self, FetchCheckpoint};
+    use crate::configuration::Config;
+    use crate::db::{self, init_db};
+    use crate::query_result::{Placement, TweetType};
+    use crate::test_support;
+    use crate::transport::{Interaction, Transport};
+
+    use super::*;
+
+    fn replay_config(cassette_path: &str) -> Config {
+        Config { transport: Transport::replay(cassette_path).unwrap(), ..test_support::config(TaskType::Initializing) }
+    }
+
+    /// A page of liked tweets by one author, newest first
+    fn liked_page(tweet_ids: &[&str], next_token: Option<&str>) -> String {
+        serde_json::json!({
+            "data": tweet_ids.iter().map(|tweet_id| serde_json::json!({"id": tweet_id, "text": format!("tweet {tweet_id}"), "author_id": "2"})).collect::<Vec<serde_json::Value>>(),
+            "includes": {"users": [{"id": "2", "name": "Towa", "username": "tokoyami_towa"}]},
+            "meta": {"result_count": tweet_ids.len(), "next_token": next_token}
+        }).to_string()
+    }
+
+    #[test]
+    fn test_monitor_likes_after_initializing() {
+        let user_id = "1";
+        let likes_url = format!("https://api.twitter.com/2/users/{user_id}/liked_tweets?expansions=author_id&max_results=100&tweet.fields=id%2Ctext%2Centities%2Cnote_tweet&user.fields=id%2Cname%2Cusername");
+        let interactions: Vec<Interaction> = [
+            (likes_url.clone(), liked_page(&["14", "13"], Some("page2"))),
+            (format!("{likes_url}&pagination_token=page2"), liked_page(&["12", "11"], None)),
+            // polled after initializing, the newest like stored is met on the first page
+            (likes_url.clone(), liked_page(&["15", "14", "13"], Some("page2")))
+        ].into_iter().map(|(url, body)| Interaction { method: String::from("GET"), url, status: 200, body }).collect();
+        let cassette_path = env::temp_dir().join(format!("sui_likes_{}.json", std::process::id()));
+        fs::write(&cassette_path, serde_json::to_string(&interactions).unwrap()).unwrap();
+        let conf = replay_config(cassette_path.to_str().unwrap());
+        let (db_path, writer, writer_handle) = test_support::writer_db("likes");
+        let conn = db::open(&db_path).unwrap();
+
+        // written like initializing does, page by page
+        checkpoint::fetch_resumable(&conf, &writer, &StopSignal::new(), FetchCheckpoint::get_record(&conn, user_id, "likes").unwrap(), &LikeFetcher::new(user_id, None), |(liked_tweet_records, liked_tweets, liked_users)| {
+            Ok(Box::new(move |conn: &Connection| {
+                let store = SqliteStore::new(conn);
+                for liked_user in liked_users.iter() {
+                    store.write_user(liked_user)?;
                 }
+                for liked_tweet in liked_tweets.iter() {
+                    store.write_dict_tweet(liked_tweet)?;
+                }
+                for liked_tweet_record in liked_tweet_records.iter() {
+                    store.write_like(liked_tweet_record, Placement::Older)?;
+                }
+                Ok(liked_tweet_records.len())
+            }))
+        }).unwrap();
+        drop(writer);
+        writer_handle.join().unwrap();
 
-                related_tweet_item.hashtags.as_mut().unwrap().push(hashtag.to_owned());
-            }
+        let store = SqliteStore::new(&conn);
+        let newest_like_id = store.newest_like_id(user_id).unwrap();
+        assert_eq!(newest_like_id.as_deref(), Some("14"));
+        let monitoring_conf = Config { task_type: TaskType::Monitoring, ..conf };
+        let ((liked_tweet_records, liked_tweets, _), requests) = LikeFetcher::new(user_id, newest_like_id.as_deref()).fetch(&monitoring_conf, &StopSignal::new()).unwrap();
+        // the paging stopped at the newest stored like
+        assert_eq!(requests, 1);
+        for liked_tweet in liked_tweets.iter() {
+            store.write_dict_tweet(liked_tweet).unwrap();
+        }
+        for liked_tweet_record in liked_tweet_records.iter() {
+            store.write_like(liked_tweet_record, Placement::Newer).unwrap();
         }
+
+        let liked_ids: Vec<String> = store.get_likes(user_id, None, 0).unwrap().into_iter().map(|liked_tweet| liked_tweet.tweet.id).collect();
+        assert_eq!(liked_ids, vec!["15", "14", "13", "12", "11"]);
+        drop(conn);
+        test_support::remove_db(&db_path);
+        fs::remove_file(cassette_path).unwrap();
     }
-    Ok(related_tweet_item)
-}
\ No newline at end of file
+
+    #[test]
+    fn test_replay_fetchers() {
+        let conf = replay_config("tests/fixtures/cassettes/fetchers.json");
+        let user_id = "975275878673408001";
+
+        // two timeline pages, the second one empty
+        let ((tweets, related_tweets, related_users), requests) = TweetFetcher::new(user_id, None).fetch(&conf, &StopSignal::new()).unwrap();
+        assert_eq!(requests, 2);
+        assert_eq!(tweets.iter().map(|tweet| tweet.id.as_str()).collect::<Vec<&str>>(), vec![
+            "1630000000000000001", "1630000000000000003", "1630000000000000004", "1630000000000000005"
+        ]);
+        assert_eq!((related_tweets.len(), related_users.len()), (1, 2));
+
+        let ((liked_tweets, _, _), _) = LikeFetcher::new(user_id, None).fetch(&conf, &StopSignal::new()).unwrap();
+        assert_eq!(liked_tweets.len(), 4);
+        assert_eq!(liked_tweets[3].tweet.id, "1630000000000000005");
+
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let (following, followed_users, _) = FollowingFetcher::new(user_id, None).fetch(&conf, &conn, &StopSignal::new()).unwrap();
+        assert_eq!(following.iter().map(|following_user| following_user.followed_user.username.as_str()).collect::<Vec<&str>>(), vec![
+            "elonmusk", "tokoyami_towa"
+        ]);
+        assert_eq!(followed_users.len(), 2);
+
+        // every recorded response has been served
+        assert!(TweetFetcher::new(user_id, None).fetch(&conf, &StopSignal::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_pages() {
+        let page: Response<Vec<Tweet>> = serde_json::from_str(include_str!("../tests/fixtures/user_tweets.json")).unwrap();
+        let mut related_tweets: Vec<BasicTweet> = Vec::new();
+        let mut related_users: Vec<BasicUserDetail> = Vec::new();
+        let fetched = parse_tweet_page(&page, &mut related_tweets, &mut related_users).unwrap();
+        assert_eq!(fetched.len(), 4);
+        assert_eq!(related_tweets.len(), 1);
+        assert_eq!(related_users.len(), 2);
+
+        let empty_page: Response<Vec<Tweet>> = serde_json::from_str(include_str!("../tests/fixtures/empty_page.json")).unwrap();
+        assert!(parse_tweet_page(&empty_page, &mut related_tweets, &mut related_users).unwrap().is_empty());
+
+        let page: Response<Vec<Tweet>> = serde_json::from_str(include_str!("../tests/fixtures/mentions.json")).unwrap();
+        let mut related_tweets: Vec<BasicTweet> = Vec::new();
+        let mut related_users: Vec<BasicUserDetail> = Vec::new();
+        let mentions = parse_mention_page("975275878673408001", &page, &mut related_tweets, &mut related_users).unwrap();
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].author.username, "tokoyami_towa");
+        assert!(matches!(&mentions[0].tweet_type, TweetType::Reply { tweet, .. } if tweet.id == "1630000000000000003"));
+        // the author of the second mention is missing from the includes
+        assert!(mentions[1].author.is_placeholder());
+        assert_eq!(mentions[1].tweet_type, TweetType::Tweet);
+        assert_eq!(related_tweets.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_tweet_lookup() {
+        let page: Response<Vec<Tweet>> = serde_json::from_str(include_str!("../tests/fixtures/tweet_lookup.json")).unwrap();
+        let (tweets, users, lookup_errors) = parse_tweet_lookup(&page).unwrap();
+        assert_eq!(tweets[0].hashtags, Some(vec!["トワ様".to_string()]));
+        assert_eq!(users[0].username, "tokoyami_towa");
+        assert_eq!(lookup_errors.iter().map(|lookup_error| lookup_error.title.as_str()).collect::<Vec<&str>>(), vec!["Not Found Error", "Authorization Error"]);
+
+        let empty_page: Response<Vec<Tweet>> = serde_json::from_str(include_str!("../tests/fixtures/empty_page.json")).unwrap();
+        assert!(parse_tweet_lookup(&empty_page).is_err());
+    }
+}
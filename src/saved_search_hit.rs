@@ -0,0 +1,146 @@
+use std::error::Error;
+
+use rusqlite::{Connection, named_params, OptionalExtension};
+
+use crate::query_result::{BasicTweet, BasicUserDetail};
+use crate::store::SqliteStore;
+
+/// Common table expression selecting one page of `saved_search_hit` rows of `:search_name`, newest first
+const SAVED_SEARCH_HIT_PAGE: &str = "WITH page AS (
+    SELECT * FROM saved_search_hit WHERE search_name = :search_name
+    ORDER BY length(tweet_id) DESC, tweet_id DESC LIMIT :limit OFFSET :offset
+)";
+
+/// A tweet matching a saved search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSearchHit {
+    pub search_name: String,
+    pub created_at: String,
+    pub tweet: BasicTweet,
+    pub author: BasicUserDetail
+}
+
+impl SavedSearchHit {
+    /// Stores the hit; the tweet and its author go to `tweet_dict` and `user_dict` with the rest
+    /// of the fetch result
+    pub fn write_to_db(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        conn.execute(
+            "INSERT OR IGNORE INTO saved_search_hit (search_name, tweet_id, time, author_id)
+            VALUES (:search_name, :tweet_id, :time, :author_id)",
+            named_params! {
+                ":search_name": &self.search_name,
+                ":tweet_id": &self.tweet.id,
+                ":time": &self.created_at,
+                ":author_id": &self.author.id
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Id of the newest stored hit of `search_name`, the checkpoint to search newer tweets from
+    pub fn newest_id(conn: &Connection, search_name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let newest_id: Option<String> = conn.query_row(
+            "SELECT tweet_id FROM saved_search_hit WHERE search_name = ?
+            ORDER BY length(tweet_id) DESC, tweet_id DESC LIMIT 1",
+            [search_name],
+            |row| row.get(0)
+        ).optional()?;
+        Ok(newest_id)
+    }
+
+    /// Reads a page of `search_name`'s hits, newest first
+    pub fn get_records(conn: &Connection, search_name: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<SavedSearchHit>, Box<dyn Error>> {
+        let page_params = named_params! {
+            ":search_name": search_name,
+            ":limit": max_results.map(i64::from).unwrap_or(-1),
+            ":offset": offset
+        };
+
+        let mut hit_stmt = conn.prepare(&format!(
+            "{SAVED_SEARCH_HIT_PAGE}
+            SELECT page.time, tweet.tweet_id, tweet.author_id, tweet.text, tweet.conversation_id, tweet.replied_to_id,
+                author.user_id, author.username, author.name
+            FROM page
+            JOIN tweet_dict AS tweet ON tweet.tweet_id = page.tweet_id
+            LEFT JOIN user_dict AS author ON author.user_id = page.author_id
+            ORDER BY length(page.tweet_id) DESC, page.tweet_id DESC"
+        ))?;
+        let query_results = hit_stmt.query_map(page_params, |row| {
+            let tweet = BasicTweet {
+                id: row.get(1)?,
+                author_id: row.get(2)?,
+                text: row.get(3)?,
+                hashtags: None,
+                urls: None,
+                conversation_id: row.get(4)?,
+                replied_to_id: row.get(5)?
+            };
+            let author = match (row.get::<_, Option<String>>(6)?, row.get::<_, Option<String>>(7)?, row.get::<_, Option<String>>(8)?) {
+                (Some(id), Some(username), Some(name)) => BasicUserDetail { id, username, name },
+                _ => BasicUserDetail::placeholder(&tweet.author_id)
+            };
+            Ok((row.get::<_, String>(0)?, tweet, author))
+        })?;
+        let mut queried_hits = Vec::new();
+        for query_result in query_results {
+            queried_hits.push(query_result?);
+        }
+
+        let hit_ids: Vec<String> = queried_hits.iter().map(|(_, tweet, _)| tweet.id.clone()).collect();
+        let store = SqliteStore::new(conn);
+        let hashtag_map = store.get_hashtag_map(&hit_ids)?;
+        let url_map = store.get_url_map(&hit_ids)?;
+
+        Ok(queried_hits.into_iter().map(|(created_at, mut tweet, author)| {
+            tweet.hashtags = hashtag_map.get(&tweet.id).cloned();
+            tweet.urls = url_map.get(&tweet.id).cloned();
+            SavedSearchHit {
+                search_name: search_name.to_string(),
+                created_at,
+                tweet,
+                author
+            }
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::store::Store;
+
+    use super::*;
+
+    #[test]
+    fn test_saved_search_hits() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let towa = BasicUserDetail { id: "2".to_string(), username: "tokoyami_towa".to_string(), name: "Towa".to_string() };
+        SqliteStore::new(&conn).write_user(&towa).unwrap();
+        let hits: Vec<SavedSearchHit> = ["9", "10"].iter().map(|tweet_id| SavedSearchHit {
+            search_name: "art".to_string(),
+            created_at: "2023-01-01T00:00:00.000Z".to_string(),
+            tweet: BasicTweet {
+                id: tweet_id.to_string(),
+                author_id: "2".to_string(),
+                text: "fan art #suiseiart".to_string(),
+                hashtags: Some(vec!["suiseiart".to_string()]),
+                urls: None,
+                conversation_id: Some(tweet_id.to_string()),
+                replied_to_id: None
+            },
+            author: towa.clone()
+        }).collect();
+        for hit in hits.iter() {
+            SqliteStore::new(&conn).write_dict_tweet(&hit.tweet).unwrap();
+            hit.write_to_db(&conn).unwrap();
+            hit.write_to_db(&conn).unwrap();
+        }
+
+        assert_eq!(SavedSearchHit::newest_id(&conn, "art").unwrap(), Some("10".to_string()));
+        assert_eq!(SavedSearchHit::newest_id(&conn, "clips").unwrap(), None);
+        assert_eq!(SavedSearchHit::get_records(&conn, "art", None, 0).unwrap(), vec![hits[1].clone(), hits[0].clone()]);
+        assert_eq!(SavedSearchHit::get_records(&conn, "art", Some(1), 1).unwrap(), vec![hits[0].clone()]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::time::Duration;
+
+use chrono::{DateTime, Duration as TimeDelta, Timelike, Utc};
+use rusqlite::{Connection, named_params};
+
+use crate::configuration::StreamPolling;
+
+/// Length of the API's rate limit windows
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Requests per window an app may send to the endpoint of each polled stream
+pub const PROFILE_RATE_LIMIT: u32 = 300;
+pub const TWEETS_RATE_LIMIT: u32 = 1500;
+pub const LIKES_RATE_LIMIT: u32 = 75;
+pub const MENTIONS_RATE_LIMIT: u32 = 450;
+pub const FOLLOWING_RATE_LIMIT: u32 = 15;
+/// Over the three engagement endpoints, 75 each
+pub const ENGAGEMENT_RATE_LIMIT: u32 = 225;
+
+/// How far back activity is counted
+const LOOKBACK_DAYS: i64 = 28;
+
+/// When an account tweeted and liked over the last four weeks, by UTC hour of day
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ActivityProfile {
+    hourly: [u32; 24]
+}
+
+impl ActivityProfile {
+    /// Counts `user_id`'s tweets and recorded likes in the four weeks before `now`. Likes
+    /// without a recorded time, e.g. those fetched when initializing, are not counted.
+    pub fn load(conn: &Connection, user_id: &str, now: DateTime<Utc>) -> Result<ActivityProfile, Box<dyn Error>> {
+        let since = (now - TimeDelta::days(LOOKBACK_DAYS)).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        let mut activity_stmt = conn.prepare(
+            "SELECT CAST(strftime('%H', time) AS INTEGER) AS hour, COUNT(*) FROM (
+                SELECT time FROM user_tweet WHERE author_id = :user_id AND time >= :since
+                UNION ALL
+                SELECT time FROM user_liked WHERE user_id = :user_id AND time >= :since
+            )
+            WHERE hour IS NOT NULL
+            GROUP BY hour"
+        )?;
+        let query_results = activity_stmt.query_map(
+            named_params! { ":user_id": user_id, ":since": &since },
+            |row| Ok((row.get::<_, usize>(0)?, row.get::<_, u32>(1)?))
+        )?;
+
+        let mut activity = ActivityProfile::default();
+        for query_result in query_results {
+            let (hour, count) = query_result?;
+            activity.hourly[hour % 24] = count;
+        }
+        Ok(activity)
+    }
+
+    /// Activity in `hour` relative to the average hour, `None` without any activity
+    fn relative(&self, hour: u32) -> Option<f64> {
+        let total: u32 = self.hourly.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        Some(self.hourly[hour as usize % 24] as f64 * 24.0 / total as f64)
+    }
+}
+
+/// Adapts the interval of a polled stream to the account's activity: shorter during the hours
+/// the account is usually active and right after a poll found something new, longer when idle
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveInterval {
+    interval: Duration,
+    min_interval: Duration,
+    max_interval: Duration,
+    /// Share of the rate limit window one request of the account takes up
+    request_interval: Duration
+}
+
+impl AdaptiveInterval {
+    /// Keeps `polling` within its bounds and the share of `rate_limit` one of `accounts`
+    /// accounts may use. Every poll sends at least one request.
+    pub fn new(polling: &StreamPolling, rate_limit: u32, accounts: usize) -> AdaptiveInterval {
+        let request_interval = RATE_LIMIT_WINDOW * accounts.max(1) as u32 / rate_limit.max(1);
+        let (min_interval, max_interval) = polling.bounds();
+        let min_interval = min_interval.max(request_interval);
+        let max_interval = max_interval.max(min_interval);
+        AdaptiveInterval {
+            interval: polling.interval.clamp(min_interval, max_interval),
+            min_interval,
+            max_interval,
+            request_interval
+        }
+    }
+
+    /// Interval until the next poll at `now`, after a poll that sent `requests` requests and
+    /// found something new or not. A poll paging through several pages waits until all of them
+    /// fit the account's share of the rate limit, even beyond the longest interval.
+    pub fn next(&self, activity: &ActivityProfile, now: DateTime<Utc>, found_new: bool, requests: u16) -> Duration {
+        let interval = if found_new {
+            self.min_interval
+        } else {
+            match activity.relative(now.hour()) {
+                Some(relative) if relative > 0.0 => self.interval.div_f64(relative).clamp(self.min_interval, self.max_interval),
+                _ => self.max_interval
+            }
+        };
+        interval.max(self.request_interval * requests as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::db::init_db;
+    use crate::query_result::FetchedTweet;
+    use crate::store::{SqliteStore, Store};
+
+    use super::*;
+
+    fn polling(interval: u64, min_interval: Option<u64>, max_interval: Option<u64>) -> StreamPolling {
+        StreamPolling {
+            enabled: true,
+            interval: Duration::from_secs(interval),
+            min_interval: min_interval.map(Duration::from_secs),
+            max_interval: max_interval.map(Duration::from_secs),
+            recent_tweets: 0,
+            request_budget: 0
+        }
+    }
+
+    #[test]
+    fn test_activity_profile() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        for (id, created_at) in [("1", "2023-03-01T20:10:00.000Z"), ("2", "2023-03-02T20:40:00.000Z"), ("3", "2023-03-03T08:00:00.000Z"), ("4", "2022-01-01T20:00:00.000Z")] {
+            let mut tweet = FetchedTweet::new();
+            tweet.id = id.to_string();
+            tweet.author_id = "0".to_string();
+            tweet.created_at = created_at.to_string();
+            SqliteStore::new(&conn).write_tweet(&tweet).unwrap();
+        }
+
+        // the tweet of 2022 is too old to count
+        let activity = ActivityProfile::load(&conn, "0", Utc.with_ymd_and_hms(2023, 3, 10, 0, 0, 0).unwrap()).unwrap();
+        assert_eq!(activity.hourly[20], 2);
+        assert_eq!(activity.hourly[8], 1);
+        assert_eq!(activity.hourly.iter().sum::<u32>(), 3);
+        assert_eq!(ActivityProfile::load(&conn, "1", Utc::now()).unwrap(), ActivityProfile::default());
+    }
+
+    #[test]
+    fn test_adaptive_interval() {
+        let mut activity = ActivityProfile::default();
+        activity.hourly[20] = 47;
+        activity.hourly[8] = 1;
+        let at_hour = |hour: u32| Utc.with_ymd_and_hms(2023, 3, 10, hour, 0, 0).unwrap();
+
+        let adaptive = AdaptiveInterval::new(&polling(60, Some(10), Some(600)), TWEETS_RATE_LIMIT, 1);
+        assert_eq!(adaptive.next(&activity, at_hour(20), false, 1), Duration::from_secs(10));
+        // half as active as the average hour
+        assert_eq!(adaptive.next(&activity, at_hour(8), false, 1), Duration::from_secs(120));
+        assert_eq!(adaptive.next(&activity, at_hour(3), false, 1), Duration::from_secs(600));
+        assert_eq!(adaptive.next(&activity, at_hour(3), true, 1), Duration::from_secs(10));
+        assert_eq!(adaptive.next(&ActivityProfile::default(), at_hour(20), false, 1), Duration::from_secs(600));
+
+        // without bounds the interval stays fixed
+        let fixed = AdaptiveInterval::new(&polling(60, None, None), TWEETS_RATE_LIMIT, 1);
+        assert_eq!(fixed.next(&activity, at_hour(20), true, 1), Duration::from_secs(60));
+        assert_eq!(fixed.next(&activity, at_hour(3), false, 1), Duration::from_secs(60));
+
+        // 4 accounts share 15 following requests per 15 minutes
+        let budgeted = AdaptiveInterval::new(&polling(180, Some(30), None), FOLLOWING_RATE_LIMIT, 4);
+        assert_eq!(budgeted.next(&activity, at_hour(20), true, 1), Duration::from_secs(240));
+        assert_eq!(budgeted.next(&activity, at_hour(3), false, 1), Duration::from_secs(240));
+        // a poll paging through 3 pages waits for all of them, beyond the longest interval
+        assert_eq!(budgeted.next(&activity, at_hour(20), true, 3), Duration::from_secs(720));
+        assert_eq!(adaptive.next(&activity, at_hour(20), true, 30), Duration::from_secs(18));
+    }
+}
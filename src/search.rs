@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use rusqlite::{Connection, named_params, ToSql};
+
+use crate::query_result::{FetchedTweet, BasicTweet};
+use crate::store::SqliteStore;
+
+/// Marker inserted before a matched term in snippets
+pub const SNIPPET_OPEN: &str = "[";
+/// Marker inserted after a matched term in snippets
+pub const SNIPPET_CLOSE: &str = "]";
+/// Marker for text cut off from snippets
+pub const SNIPPET_ELLIPSIS: &str = "…";
+/// Approximate number of characters kept in a snippet
+const SNIPPET_LEN: usize = 32;
+/// The trigram tokenizer only matches terms of at least this many characters
+const MIN_MATCH_LEN: usize = 3;
+
+/// A search result with its highlighted snippet.
+///
+/// `rank` is the FTS5 bm25 rank (lower is better); results of queries answered by substring scan
+/// (terms shorter than three characters) all have rank `0.0` and are ordered newest first.
+#[derive(Debug, PartialEq)]
+pub struct SearchHit<T> {
+    pub item: T,
+    pub snippet: String,
+    pub rank: f64
+}
+
+/// How a query string is answered
+#[derive(Debug, PartialEq)]
+enum SearchQuery {
+    /// FTS5 query of quoted phrases, all terms long enough for the trigram index
+    Match(String),
+    /// Terms matched by `LIKE` substring scan
+    Substring(Vec<String>)
+}
+
+impl SearchQuery {
+    /// Splits `query` on whitespace into terms which all have to match. Returns `None` for a
+    /// blank query.
+    fn parse(query: &str) -> Option<SearchQuery> {
+        let terms: Vec<String> = query.split_whitespace().map(|term| term.to_string()).collect();
+        if terms.is_empty() {
+            return None;
+        }
+
+        if terms.iter().all(|term| term.chars().count() >= MIN_MATCH_LEN) {
+            let phrases: Vec<String> = terms.iter().map(|term| format!("\"{}\"", term.replace('"', "\"\""))).collect();
+            Some(SearchQuery::Match(phrases.join(" ")))
+        } else {
+            Some(SearchQuery::Substring(terms))
+        }
+    }
+
+    /// `LIKE` conditions on `column` for every term, and the patterns bound to them
+    fn like_clause(terms: &[String], column: &str) -> (String, Vec<(String, String)>) {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut patterns: Vec<(String, String)> = Vec::new();
+        for (idx, term) in terms.iter().enumerate() {
+            let escaped = term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            conditions.push(format!("{column} LIKE :term{idx} ESCAPE '\\'"));
+            patterns.push((format!(":term{idx}"), format!("%{escaped}%")));
+        }
+        (conditions.join(" AND "), patterns)
+    }
+}
+
+/// Adds a new `user_tweet` row to the full-text index
+pub(crate) fn index_user_tweet(conn: &Connection, rowid: i64, text: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO user_tweet_fts (rowid, tweet_text) VALUES (:rowid, :text)",
+        named_params! {":rowid": rowid, ":text": text}
+    )?;
+    Ok(())
+}
+
+/// Adds a new `tweet_dict` row to the full-text index
+pub(crate) fn index_dict_tweet(conn: &Connection, rowid: i64, text: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO tweet_dict_fts (rowid, text) VALUES (:rowid, :text)",
+        named_params! {":rowid": rowid, ":text": text}
+    )?;
+    Ok(())
+}
+
+/// Replaces the indexed text of a `user_tweet` row whose text changed
+pub(crate) fn reindex_user_tweet(conn: &Connection, rowid: i64, old_text: &str, new_text: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO user_tweet_fts (user_tweet_fts, rowid, tweet_text) VALUES ('delete', :rowid, :text)",
+        named_params! {":rowid": rowid, ":text": old_text}
+    )?;
+    index_user_tweet(conn, rowid, new_text)
+}
+
+/// Replaces the indexed text of a `tweet_dict` row whose text changed
+pub(crate) fn reindex_dict_tweet(conn: &Connection, rowid: i64, old_text: &str, new_text: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO tweet_dict_fts (tweet_dict_fts, rowid, text) VALUES ('delete', :rowid, :text)",
+        named_params! {":rowid": rowid, ":text": old_text}
+    )?;
+    index_dict_tweet(conn, rowid, new_text)
+}
+
+/// Searches the text of stored timeline tweets (`user_tweet`), optionally only those of
+/// `author_id`, returning at most `max_results` hits, best first.
+pub fn search_tweets(conn: &Connection, query: &str, author_id: Option<&str>, max_results: u16) -> Result<Vec<SearchHit<FetchedTweet>>, Box<dyn Error>> {
+    let search_query = match SearchQuery::parse(query) {
+        Some(search_query) => search_query,
+        None => { return Ok(Vec::new()); }
+    };
+
+    let mut hit_rows: Vec<(String, String, f64)> = Vec::new();
+    match &search_query {
+        SearchQuery::Match(match_query) => {
+            let mut search_stmt = conn.prepare(&format!(
+                "SELECT user_tweet.tweet_id,
+                    snippet(user_tweet_fts, 0, '{SNIPPET_OPEN}', '{SNIPPET_CLOSE}', '{SNIPPET_ELLIPSIS}', {SNIPPET_LEN}),
+                    user_tweet_fts.rank
+                FROM user_tweet_fts
+                JOIN user_tweet ON user_tweet.id = user_tweet_fts.rowid
+                WHERE user_tweet_fts MATCH :query AND (:author_id IS NULL OR user_tweet.author_id = :author_id)
+                ORDER BY user_tweet_fts.rank LIMIT :limit"
+            ))?;
+            let query_results = search_stmt.query_map(
+                named_params! {":query": match_query, ":author_id": author_id, ":limit": max_results},
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            )?;
+            for query_result in query_results {
+                hit_rows.push(query_result?);
+            }
+        }
+        SearchQuery::Substring(terms) => {
+            let (condition, patterns) = SearchQuery::like_clause(terms, "tweet_text");
+            let mut search_stmt = conn.prepare(&format!(
+                "SELECT tweet_id, tweet_text FROM user_tweet
+                WHERE {condition} AND (:author_id IS NULL OR author_id = :author_id)
+                ORDER BY id DESC LIMIT :limit"
+            ))?;
+            let mut search_params: Vec<(&str, &dyn ToSql)> = vec![(":author_id", &author_id), (":limit", &max_results)];
+            for (name, pattern) in patterns.iter() {
+                search_params.push((name.as_str(), pattern));
+            }
+            let query_results = search_stmt.query_map(search_params.as_slice(), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for query_result in query_results {
+                let (tweet_id, text) = query_result?;
+                hit_rows.push((tweet_id, highlight(&text, terms), 0.0));
+            }
+        }
+    }
+
+    let tweet_ids: Vec<String> = hit_rows.iter().map(|(tweet_id, _, _)| tweet_id.clone()).collect();
+    let mut tweet_map: HashMap<String, FetchedTweet> = SqliteStore::new(conn).get_tweets_by_ids(&tweet_ids)?
+        .into_iter()
+        .map(|tweet| (tweet.id.clone(), tweet))
+        .collect();
+
+    let mut hits: Vec<SearchHit<FetchedTweet>> = Vec::new();
+    for (tweet_id, snippet, rank) in hit_rows.into_iter() {
+        if let Some(tweet) = tweet_map.remove(&tweet_id) {
+            hits.push(SearchHit { item: tweet, snippet, rank });
+        }
+    }
+    Ok(hits)
+}
+
+/// Searches the text of referenced and liked tweets (`tweet_dict`), returning at most
+/// `max_results` hits, best first.
+pub fn search_dict_tweets(conn: &Connection, query: &str, max_results: u16) -> Result<Vec<SearchHit<BasicTweet>>, Box<dyn Error>> {
+    let search_query = match SearchQuery::parse(query) {
+        Some(search_query) => search_query,
+        None => { return Ok(Vec::new()); }
+    };
+
+    let tweet_mapper = |row: &rusqlite::Row| -> rusqlite::Result<(BasicTweet, String, f64)> {
+        Ok((
+            BasicTweet {
+                id: row.get(0)?,
+                author_id: row.get(1)?,
+                text: row.get(2)?,
+                hashtags: None,
+                urls: None,
+                conversation_id: row.get(5)?,
+                replied_to_id: row.get(6)?
+            },
+            row.get(3)?,
+            row.get(4)?
+        ))
+    };
+
+    let mut hits: Vec<SearchHit<BasicTweet>> = Vec::new();
+    match &search_query {
+        SearchQuery::Match(match_query) => {
+            let mut search_stmt = conn.prepare(&format!(
+                "SELECT tweet_dict.tweet_id, tweet_dict.author_id, tweet_dict.text,
+                    snippet(tweet_dict_fts, 0, '{SNIPPET_OPEN}', '{SNIPPET_CLOSE}', '{SNIPPET_ELLIPSIS}', {SNIPPET_LEN}),
+                    tweet_dict_fts.rank, tweet_dict.conversation_id, tweet_dict.replied_to_id
+                FROM tweet_dict_fts
+                JOIN tweet_dict ON tweet_dict.id = tweet_dict_fts.rowid
+                WHERE tweet_dict_fts MATCH :query
+                ORDER BY tweet_dict_fts.rank LIMIT :limit"
+            ))?;
+            let query_results = search_stmt.query_map(
+                named_params! {":query": match_query, ":limit": max_results},
+                tweet_mapper
+            )?;
+            for query_result in query_results {
+                let (item, snippet, rank) = query_result?;
+                hits.push(SearchHit { item, snippet, rank });
+            }
+        }
+        SearchQuery::Substring(terms) => {
+            let (condition, patterns) = SearchQuery::like_clause(terms, "text");
+            let mut search_stmt = conn.prepare(&format!(
+                "SELECT tweet_id, author_id, text, '', 0.0, conversation_id, replied_to_id FROM tweet_dict
+                WHERE {condition}
+                ORDER BY id DESC LIMIT :limit"
+            ))?;
+            let mut search_params: Vec<(&str, &dyn ToSql)> = vec![(":limit", &max_results)];
+            for (name, pattern) in patterns.iter() {
+                search_params.push((name.as_str(), pattern));
+            }
+            let query_results = search_stmt.query_map(search_params.as_slice(), tweet_mapper)?;
+            for query_result in query_results {
+                let (item, _, rank) = query_result?;
+                let snippet = highlight(&item.text, terms);
+                hits.push(SearchHit { item, snippet, rank });
+            }
+        }
+    }
+
+    let tweet_ids: Vec<String> = hits.iter().map(|hit| hit.item.id.clone()).collect();
+    let store = SqliteStore::new(conn);
+    let hashtag_map = store.get_hashtag_map(&tweet_ids)?;
+    let url_map = store.get_url_map(&tweet_ids)?;
+    for hit in hits.iter_mut() {
+        hit.item.hashtags = hashtag_map.get(&hit.item.id).cloned();
+        hit.item.urls = url_map.get(&hit.item.id).cloned();
+    }
+
+    Ok(hits)
+}
+
+/// Builds a snippet of `text` around the first occurrence of any of `terms`, marking every
+/// occurrence inside it the way FTS5 `snippet()` does. ASCII letters match case-insensitively,
+/// like `LIKE`.
+fn highlight(text: &str, terms: &[String]) -> String {
+    let text_chars: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = text_chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let term_chars: Vec<Vec<char>> = terms.iter().map(|term| term.chars().map(|c| c.to_ascii_lowercase()).collect()).collect();
+
+    let mut matches: Vec<(usize, usize)> = Vec::new();
+    let mut idx = 0;
+    while idx < lowered.len() {
+        let matched_len = term_chars.iter()
+            .filter(|term| !term.is_empty() && lowered[idx..].starts_with(term))
+            .map(|term| term.len())
+            .max();
+        match matched_len {
+            Some(len) => {
+                matches.push((idx, idx + len));
+                idx += len;
+            }
+            None => { idx += 1; }
+        }
+    }
+
+    let first_match = matches.first().map(|(start, _)| *start).unwrap_or(0);
+    let start = first_match.saturating_sub(SNIPPET_LEN / 4);
+    let end = text_chars.len().min(start + SNIPPET_LEN);
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str(SNIPPET_ELLIPSIS);
+    }
+    for (char_idx, c) in text_chars.iter().enumerate().take(end).skip(start) {
+        if matches.iter().any(|(match_start, _)| *match_start == char_idx) {
+            snippet.push_str(SNIPPET_OPEN);
+        }
+        snippet.push(*c);
+        if matches.iter().any(|(_, match_end)| *match_end == char_idx + 1 || (char_idx + 1 == end && *match_end > end)) {
+            snippet.push_str(SNIPPET_CLOSE);
+        }
+    }
+    if end < text_chars.len() {
+        snippet.push_str(SNIPPET_ELLIPSIS);
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{init_db, rebuild_search_index};
+    use crate::query_result::{BasicUserDetail, TweetType};
+    use crate::store::Store;
+
+    use super::*;
+
+    fn write_tweet(conn: &Connection, id: &str, author_id: &str, text: &str) {
+        SqliteStore::new(conn).write_tweet(&FetchedTweet {
+            id: id.to_string(),
+            text: text.to_string(),
+            created_at: "2023-01-01T00:00:00.000Z".to_string(),
+            author_id: author_id.to_string(),
+            tweet_type: TweetType::Tweet,
+            hashtags: None,
+            mentions: None,
+            media: None,
+            urls: None,
+            conversation_id: Some(id.to_string()),
+            in_reply_to_user_id: None,
+            replied_to_id: None,
+            is_note_tweet: false,
+            edit_controls: None,
+            versions: None
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(SearchQuery::parse("  "), None);
+        assert_eq!(
+            SearchQuery::parse("歌枠配信 \"karaoke\""),
+            Some(SearchQuery::Match("\"歌枠配信\" \"\"\"karaoke\"\"\"".to_string()))
+        );
+        assert_eq!(
+            SearchQuery::parse("歌枠 stream"),
+            Some(SearchQuery::Substring(vec!["歌枠".to_string(), "stream".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(highlight("今日は歌枠です", &["歌枠".to_string()]), "今日は[歌枠]です");
+        assert_eq!(highlight("Stream at 9", &["st".to_string()]), "[St]ream at 9");
+    }
+
+    #[test]
+    fn test_search_tweets() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        write_tweet(&conn, "1", "0", "今日は21時から歌枠配信します！");
+        write_tweet(&conn, "2", "0", "雑談配信ありがとう〜");
+        write_tweet(&conn, "3", "9", "歌枠配信たのしみ");
+
+        let hits = search_tweets(&conn, "歌枠配信", None, 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.snippet.contains("[歌枠配信]")));
+
+        let hits = search_tweets(&conn, "歌枠配信", Some("0"), 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].item.id, "1");
+
+        let hits = search_tweets(&conn, "配信", Some("0"), 10).unwrap();
+        let hit_ids: Vec<&str> = hits.iter().map(|hit| hit.item.id.as_str()).collect();
+        assert_eq!(hit_ids, vec!["2", "1"]);
+        assert_eq!(hits[0].snippet, "雑談[配信]ありがとう〜");
+
+        assert!(search_tweets(&conn, "100%", None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_dict_tweets() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let store = SqliteStore::new(&conn);
+        store.write_user(&BasicUserDetail {
+            id: "2".to_string(),
+            username: "tokoyami_towa".to_string(),
+            name: "Tokoyami Towa".to_string()
+        }).unwrap();
+        store.write_dict_tweet(&BasicTweet {
+            id: "002".to_string(),
+            author_id: "2".to_string(),
+            text: "Suisei gomi! #hoshimachi".to_string(),
+            hashtags: Some(vec!["hoshimachi".to_string()]),
+            urls: None,
+            conversation_id: None,
+            replied_to_id: None
+        }).unwrap();
+
+        let hits = search_dict_tweets(&conn, "gomi", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].item.hashtags, Some(vec!["hoshimachi".to_string()]));
+        assert_eq!(hits[0].snippet, "Suisei [gomi]! #hoshimachi");
+
+        rebuild_search_index(&conn).unwrap();
+        assert_eq!(search_dict_tweets(&conn, "SUISEI", 10).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+use std::error::Error;
+
+use rusqlite::{Connection, OptionalExtension, named_params};
+
+use crate::query_result;
+
+/// One run of the monitor, from its start to its shutdown
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorSession {
+    pub id: i64,
+    pub started_at: String,
+    /// `None` while running, and for a run that ended without shutting down, e.g. killed
+    pub stopped_at: Option<String>,
+    /// Whether every pending write was flushed before the shutdown
+    pub clean_shutdown: bool
+}
+
+impl MonitorSession {
+    /// Records the start of a run
+    pub fn start(conn: &Connection) -> Result<MonitorSession, Box<dyn Error>> {
+        ensure_table(conn)?;
+        let started_at = query_result::now();
+        conn.execute(
+            "INSERT INTO monitor_session (started_at) VALUES (:started_at)",
+            named_params! { ":started_at": &started_at }
+        )?;
+        Ok(MonitorSession {
+            id: conn.last_insert_rowid(),
+            started_at,
+            stopped_at: None,
+            clean_shutdown: false
+        })
+    }
+
+    /// The run started last, `None` before the first one
+    pub fn last(conn: &Connection) -> Result<Option<MonitorSession>, Box<dyn Error>> {
+        ensure_table(conn)?;
+        let session = conn.query_row(
+            "SELECT id, started_at, stopped_at, clean_shutdown FROM monitor_session ORDER BY id DESC LIMIT 1",
+            [],
+            |row| Ok(MonitorSession {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                stopped_at: row.get(2)?,
+                clean_shutdown: row.get(3)?
+            })
+        ).optional()?;
+        Ok(session)
+    }
+
+    /// Records the shutdown of the run
+    pub fn finish(&mut self, conn: &Connection, clean_shutdown: bool) -> Result<(), Box<dyn Error>> {
+        let stopped_at = query_result::now();
+        conn.execute(
+            "UPDATE monitor_session SET stopped_at = :stopped_at, clean_shutdown = :clean_shutdown WHERE id = :id",
+            named_params! { ":stopped_at": &stopped_at, ":clean_shutdown": clean_shutdown, ":id": self.id }
+        )?;
+        self.stopped_at = Some(stopped_at);
+        self.clean_shutdown = clean_shutdown;
+        Ok(())
+    }
+}
+
+/// [`crate::db::init_db`] creates the table, databases initialized by older versions lack it
+fn ensure_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS monitor_session (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            started_at TEXT NOT NULL,
+            stopped_at TEXT,
+            clean_shutdown INTEGER NOT NULL DEFAULT 0
+        );"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(MonitorSession::last(&conn).unwrap(), None);
+
+        let mut session = MonitorSession::start(&conn).unwrap();
+        assert_eq!(MonitorSession::last(&conn).unwrap(), Some(session.clone()));
+        session.finish(&conn, true).unwrap();
+        let last_session = MonitorSession::last(&conn).unwrap().unwrap();
+        assert!(last_session.clean_shutdown);
+        assert!(last_session.stopped_at.is_some());
+
+        // killed without shutting down
+        let killed_session = MonitorSession::start(&conn).unwrap();
+        let last_session = MonitorSession::last(&conn).unwrap().unwrap();
+        assert_eq!(last_session.id, killed_session.id);
+        assert!(!last_session.clean_shutdown && last_session.stopped_at.is_none());
+
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_db(&conn).unwrap();
+        let sessions: i64 = conn.query_row("SELECT COUNT(*) FROM monitor_session", [], |row| row.get(0)).unwrap();
+        assert_eq!(sessions, 0);
+    }
+}
@@ -0,0 +1,143 @@
+use std::error::Error;
+
+use rusqlite::{Connection, named_params};
+
+/// Length of the periods statistics are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year
+}
+
+impl Period {
+    /// `strftime` format turning a tweet time into the label of its period
+    fn strftime_format(&self) -> &'static str {
+        match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Week => "%Y-W%W",
+            Period::Month => "%Y-%m",
+            Period::Year => "%Y"
+        }
+    }
+}
+
+/// How often a domain was linked in one period
+#[derive(Debug, PartialEq)]
+pub struct DomainCount {
+    /// `2023-01-31`, `2023-W05`, `2023-01` or `2023`, depending on the [`Period`]
+    pub period: String,
+    pub domain: String,
+    /// Number of links to the domain
+    pub link_count: i64,
+    /// Number of tweets linking to the domain
+    pub tweet_count: i64
+}
+
+/// Returns the `max_per_period` domains `author_id` linked most in every period, for tweets
+/// created within `[since, until)` (ISO 8601 times, either bound optional). Periods are in
+/// ascending order, domains by descending link count.
+pub fn top_linked_domains(
+    conn: &Connection,
+    author_id: &str,
+    period: Period,
+    since: Option<&str>,
+    until: Option<&str>,
+    max_per_period: u16
+) -> Result<Vec<DomainCount>, Box<dyn Error>> {
+    let mut domain_stmt = conn.prepare(
+        "WITH counted AS (
+            SELECT strftime(:format, user_tweet.time) AS period, url_dict.domain AS domain,
+                COUNT(*) AS link_count, COUNT(DISTINCT user_tweet.tweet_id) AS tweet_count
+            FROM user_tweet
+            JOIN url_dict ON url_dict.tweet_id = user_tweet.tweet_id
+            WHERE user_tweet.author_id = :author_id AND url_dict.domain IS NOT NULL
+                AND (:since IS NULL OR user_tweet.time >= :since)
+                AND (:until IS NULL OR user_tweet.time < :until)
+            GROUP BY period, domain
+        ), ranked AS (
+            SELECT *, row_number() OVER (PARTITION BY period ORDER BY link_count DESC, domain) AS domain_rank
+            FROM counted
+        )
+        SELECT period, domain, link_count, tweet_count FROM ranked
+        WHERE domain_rank <= :limit
+        ORDER BY period, domain_rank"
+    )?;
+    let query_results = domain_stmt.query_map(
+        named_params! {
+            ":format": period.strftime_format(),
+            ":author_id": author_id,
+            ":since": since,
+            ":until": until,
+            ":limit": max_per_period
+        },
+        |row| Ok(DomainCount {
+            period: row.get(0)?,
+            domain: row.get(1)?,
+            link_count: row.get(2)?,
+            tweet_count: row.get(3)?
+        })
+    )?;
+
+    let mut domain_counts: Vec<DomainCount> = Vec::new();
+    for query_result in query_results {
+        domain_counts.push(query_result?);
+    }
+    Ok(domain_counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::query_result::{FetchedTweet, UrlEntity};
+    use crate::store::{SqliteStore, Store};
+
+    use super::*;
+
+    fn write_linking_tweet(conn: &Connection, id: &str, created_at: &str, linked_urls: &[&str]) {
+        let mut tweet = FetchedTweet::new();
+        tweet.id = id.to_string();
+        tweet.author_id = "0".to_string();
+        tweet.created_at = created_at.to_string();
+        tweet.text = linked_urls.join(" ");
+        tweet.urls = Some(linked_urls.iter().map(|linked_url| UrlEntity {
+            url: "https://t.co/x".to_string(),
+            expanded_url: Some(linked_url.to_string()),
+            display_url: None,
+            unwound_url: None,
+            domain: UrlEntity::domain_of(linked_url),
+            title: None
+        }).collect());
+        SqliteStore::new(conn).write_tweet(&tweet).unwrap();
+    }
+
+    #[test]
+    fn test_domain_of() {
+        assert_eq!(UrlEntity::domain_of("https://www.YouTube.com/watch?v=1"), Some("youtube.com".to_string()));
+        assert_eq!(UrlEntity::domain_of("https://user@twitch.tv:443/suisei"), Some("twitch.tv".to_string()));
+        assert_eq!(UrlEntity::domain_of("not a url"), None);
+    }
+
+    #[test]
+    fn test_top_linked_domains() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        write_linking_tweet(&conn, "1", "2023-01-01T20:00:00.000Z", &["https://youtu.be/a", "https://youtu.be/b"]);
+        write_linking_tweet(&conn, "2", "2023-01-02T20:00:00.000Z", &["https://www.twitch.tv/x"]);
+        write_linking_tweet(&conn, "3", "2023-01-02T21:00:00.000Z", &["https://www.twitch.tv/y"]);
+        write_linking_tweet(&conn, "4", "2023-02-01T20:00:00.000Z", &["https://booth.pm/z"]);
+
+        let monthly = top_linked_domains(&conn, "0", Period::Month, None, None, 1).unwrap();
+        assert_eq!(monthly, vec![
+            DomainCount { period: "2023-01".to_string(), domain: "twitch.tv".to_string(), link_count: 2, tweet_count: 2 },
+            DomainCount { period: "2023-02".to_string(), domain: "booth.pm".to_string(), link_count: 1, tweet_count: 1 },
+        ]);
+
+        let daily = top_linked_domains(&conn, "0", Period::Day, Some("2023-01-01T00:00:00.000Z"), Some("2023-01-02T00:00:00.000Z"), 5).unwrap();
+        assert_eq!(daily, vec![
+            DomainCount { period: "2023-01-01".to_string(), domain: "youtu.be".to_string(), link_count: 2, tweet_count: 1 },
+        ]);
+    }
+}
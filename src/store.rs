@@ -0,0 +1,1524 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+
+use rusqlite::{Connection, named_params, OptionalExtension, ToSql};
+
+use crate::configuration::TaskType;
+use crate::profile::ProfileChange;
+use crate::query_result::{Availability, BasicTweet, BasicUserDetail, EditControls, FetchedTweet, FetchedUser, FollowingAction, FollowingUser, LikedTweet, Media, Placement, TweetType, TweetVersion, UrlEntity, UserDetail};
+use crate::search;
+
+/// Where the profiles, tweets, likes and follows of the monitored users and the user and tweet
+/// dictionaries are kept. Reads return the newest records first, skipping `offset` records and
+/// returning at most `max_results` of them, every one when `None`.
+///
+/// Only these records are pluggable. Mentions, engagements, search hits, stream announcements,
+/// profile changes, archived media files and fetch checkpoints are SQLite only and written by
+/// their own modules, e.g. [`crate::mention::Mention::write_to_db`]. Search, threads, stats and
+/// reprocessing query the SQLite tables directly.
+pub trait Store {
+    /// Stores a fetched profile. While monitoring, a profile equal to the latest stored one of
+    /// the user is skipped.
+    fn write_profile(&self, fetched_user: &FetchedUser, task_type: &TaskType) -> Result<(), Box<dyn Error>>;
+    fn get_profiles(&self, username: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FetchedUser>, Box<dyn Error>>;
+
+    /// Stores a tweet of a monitored user; a stored tweet is brought up to date with its edits
+    fn write_tweet(&self, tweet: &FetchedTweet) -> Result<(), Box<dyn Error>>;
+    fn get_tweets(&self, author_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FetchedTweet>, Box<dyn Error>>;
+    /// Newest id of `author_id`'s stored tweets and their versions
+    fn newest_tweet_id(&self, author_id: &str) -> Result<Option<String>, Box<dyn Error>>;
+
+    /// Stores a like before or after the stored likes of the user
+    fn write_like(&self, liked_tweet: &LikedTweet, placement: Placement) -> Result<(), Box<dyn Error>>;
+    fn get_likes(&self, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<LikedTweet>, Box<dyn Error>>;
+    /// Liked tweet id of the newest stored like
+    fn newest_like_id(&self, user_id: &str) -> Result<Option<String>, Box<dyn Error>>;
+
+    /// Stores a follow before or after the stored follows of the user, or removes an unfollowed
+    /// user from the current following
+    fn write_following(&self, following_user: &FollowingUser, placement: Placement) -> Result<(), Box<dyn Error>>;
+    /// Recorded follows
+    fn get_following(&self, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FollowingUser>, Box<dyn Error>>;
+    /// Ids of the users `user_id` currently follows, the latest followed first
+    fn current_following_ids(&self, user_id: &str) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Stores a user in the user dictionary; a placeholder never replaces a stored user
+    fn write_user(&self, user: &BasicUserDetail) -> Result<(), Box<dyn Error>>;
+    fn get_user(&self, user_id: &str) -> Result<Option<BasicUserDetail>, Box<dyn Error>>;
+
+    /// Stores a tweet in the tweet dictionary; a stored placeholder is replaced by the tweet
+    fn write_dict_tweet(&self, tweet: &BasicTweet) -> Result<(), Box<dyn Error>>;
+    fn get_dict_tweet(&self, tweet_id: &str) -> Result<Option<BasicTweet>, Box<dyn Error>>;
+}
+
+/// Common table expression selecting one page of `user_tweet` rows of `:author_id`, newest first
+/// by tweet id, as pages fetched while initializing are written newest page first. A negative
+/// `:limit` selects every row.
+const USER_TWEET_PAGE: &str = "WITH page AS (
+    SELECT *, ROW_NUMBER() OVER (ORDER BY length(tweet_id) DESC, tweet_id DESC) AS page_order
+    FROM user_tweet WHERE author_id = :author_id
+    ORDER BY length(tweet_id) DESC, tweet_id DESC LIMIT :limit OFFSET :offset
+)";
+
+/// Common table expression selecting the `user_tweet` rows whose `tweet_id` is listed in the JSON
+/// array `:tweet_ids`, in the order of the array.
+const USER_TWEET_BY_IDS: &str = "WITH page AS (
+    SELECT user_tweet.*, ids.key AS page_order
+    FROM json_each(:tweet_ids) AS ids
+    JOIN user_tweet ON user_tweet.tweet_id = ids.value
+)";
+
+/// Common table expression selecting one page of `user_liked` rows of `:user_id`, newest first.
+const USER_LIKED_PAGE: &str = "WITH page AS (
+    SELECT * FROM user_liked WHERE user_id = :user_id ORDER BY fetch_order DESC LIMIT :limit OFFSET :offset
+)";
+
+/// The SQLite database created by [`crate::db::init_db`]. Besides the [`Store`] records it keeps
+/// the full-text index of the stored tweets and, while monitoring, a [`ProfileChange`] for every
+/// field of a profile that changed.
+pub struct SqliteStore<'a> {
+    conn: &'a Connection
+}
+
+impl<'a> SqliteStore<'a> {
+    pub fn new(conn: &'a Connection) -> SqliteStore<'a> {
+        SqliteStore { conn }
+    }
+
+    /// Reads the stored tweets with the given ids, in the order of `tweet_ids`. Ids not found in
+    /// `user_tweet` are skipped.
+    pub fn get_tweets_by_ids(&self, tweet_ids: &[String]) -> Result<Vec<FetchedTweet>, Box<dyn Error>> {
+        self.get_tweet_page(USER_TWEET_BY_IDS, named_params! {
+            ":tweet_ids": serde_json::to_string(tweet_ids)?
+        })
+    }
+
+    /// Whether `liked_tweet` is stored as a like of its user
+    pub(crate) fn has_like(&self, liked_tweet: &LikedTweet) -> Result<bool, Box<dyn Error>> {
+        let stored: bool = self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM user_liked WHERE user_id = :user_id AND ref_tweet_id = :ref_tweet_id)",
+            named_params! { ":user_id": &liked_tweet.user_id, ":ref_tweet_id": &liked_tweet.tweet.id },
+            |row| row.get(0)
+        )?;
+        Ok(stored)
+    }
+
+    /// Records where the file of `media_key` was archived
+    pub fn set_media_archived(&self, media_key: &str, content_hash: &str, local_path: &str) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "UPDATE media_dict SET content_hash = :content_hash, local_path = :local_path WHERE media_key = :media_key",
+            named_params! {
+                ":content_hash": content_hash,
+                ":local_path": local_path,
+                ":media_key": media_key
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Reads the hashtags of the tweets in `tweet_ids`, grouped by tweet id
+    pub(crate) fn get_hashtag_map(&self, tweet_ids: &[String]) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+        let mut hashtag_stmt = self.conn.prepare(
+            "SELECT tweet_id, hashtag FROM hashtag_dict
+            WHERE tweet_id IN (SELECT value FROM json_each(:tweet_ids))
+            ORDER BY id"
+        )?;
+        let hashtag_rows = hashtag_stmt.query_map(named_params! {":tweet_ids": serde_json::to_string(tweet_ids)?}, |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut hashtag_map: HashMap<String, Vec<String>> = HashMap::new();
+        for hashtag_row in hashtag_rows {
+            let (tweet_id, hashtag) = hashtag_row?;
+            hashtag_map.entry(tweet_id).or_default().push(hashtag);
+        }
+        Ok(hashtag_map)
+    }
+
+    /// Reads the urls of the tweets in `tweet_ids`, grouped by tweet id
+    pub(crate) fn get_url_map(&self, tweet_ids: &[String]) -> Result<HashMap<String, Vec<UrlEntity>>, Box<dyn Error>> {
+        let mut url_stmt = self.conn.prepare(
+            "SELECT tweet_id, url, expanded_url, display_url, unwound_url, domain, title
+            FROM url_dict
+            WHERE tweet_id IN (SELECT value FROM json_each(:tweet_ids))
+            ORDER BY id"
+        )?;
+        let url_rows = url_stmt.query_map(named_params! {":tweet_ids": serde_json::to_string(tweet_ids)?}, |row| {
+            Ok((row.get::<_, String>(0)?, UrlEntity {
+                url: row.get(1)?,
+                expanded_url: row.get(2)?,
+                display_url: row.get(3)?,
+                unwound_url: row.get(4)?,
+                domain: row.get(5)?,
+                title: row.get(6)?
+            }))
+        })?;
+
+        let mut url_map: HashMap<String, Vec<UrlEntity>> = HashMap::new();
+        for url_row in url_rows {
+            let (tweet_id, url) = url_row?;
+            url_map.entry(tweet_id).or_default().push(url);
+        }
+        Ok(url_map)
+    }
+
+    fn get_latest_profile_by_id(&self, user_id: &str) -> Result<Option<FetchedUser>, rusqlite::Error> {
+        self.conn.query_row(
+            "SELECT * FROM user_profile WHERE user_id = ? ORDER BY id DESC LIMIT 1",
+            [user_id],
+            profile_from_row
+        ).optional()
+    }
+
+    /// Reads the tweets selected by `page`, a common table expression defining a `page` table with
+    /// the columns of `user_tweet` plus a `page_order` column the result is sorted by.
+    ///
+    /// The page, its referenced tweets and their authors come from one joined query; hashtags,
+    /// mentions, media, urls and versions of the whole page are then loaded with one batched query
+    /// each over the ids of the page, so the page is selected once and the number of statements
+    /// does not grow with the page size.
+    fn get_tweet_page(&self, page: &str, page_params: &[(&str, &dyn ToSql)]) -> Result<Vec<FetchedTweet>, Box<dyn Error>> {
+        let mut user_tweet_stmt = self.conn.prepare(&format!(
+            "{page}
+            SELECT page.tweet_id, page.tweet_text, page.time, page.author_id, page.tweet_type, page.ref_tweet_id,
+                ref_tweet.author_id, ref_tweet.text, ref_user.username, ref_user.name,
+                page.conversation_id, page.in_reply_to_user_id, page.replied_to_id,
+                ref_tweet.conversation_id, ref_tweet.replied_to_id,
+                page.is_note_tweet, page.edits_remaining, page.is_edit_eligible, page.editable_until
+            FROM page
+            LEFT JOIN tweet_dict AS ref_tweet ON ref_tweet.tweet_id = page.ref_tweet_id
+            LEFT JOIN user_dict AS ref_user ON ref_user.user_id = ref_tweet.author_id
+            ORDER BY page.page_order"
+        ))?;
+        let query_results = user_tweet_stmt.query_map(page_params, |row| {
+            let mut latest_tweet = FetchedTweet::new();
+            latest_tweet.id = row.get(0)?;
+            latest_tweet.text = row.get(1)?;
+            latest_tweet.created_at = row.get(2)?;
+            latest_tweet.author_id = row.get(3)?;
+            latest_tweet.conversation_id = row.get(10)?;
+            latest_tweet.in_reply_to_user_id = row.get(11)?;
+            latest_tweet.replied_to_id = row.get(12)?;
+            latest_tweet.is_note_tweet = row.get(15)?;
+            latest_tweet.edit_controls = match (row.get::<_, Option<i64>>(16)?, row.get::<_, Option<bool>>(17)?, row.get::<_, Option<String>>(18)?) {
+                (Some(edits_remaining), Some(is_edit_eligible), Some(editable_until)) => Some(EditControls {
+                    edits_remaining,
+                    is_edit_eligible,
+                    editable_until
+                }),
+                _ => None
+            };
+            let tweet_type_str: String = row.get(4)?;
+            let ref_tweet_id: Option<String> = row.get(5)?;
+            let ref_tweet = match (ref_tweet_id, row.get::<_, Option<String>>(6)?, row.get::<_, Option<String>>(7)?) {
+                (Some(id), Some(author_id), Some(text)) => Some(BasicTweet {
+                    id,
+                    author_id,
+                    text,
+                    hashtags: None,
+                    urls: None,
+                    conversation_id: row.get(13)?,
+                    replied_to_id: row.get(14)?
+                }),
+                _ => None
+            };
+            let ref_user = match (row.get::<_, Option<String>>(8)?, row.get::<_, Option<String>>(9)?) {
+                (Some(username), Some(name)) => ref_tweet.as_ref().map(|tweet| BasicUserDetail {
+                    id: tweet.author_id.clone(),
+                    username,
+                    name
+                }),
+                _ => None
+            };
+            Ok((latest_tweet, tweet_type_str, ref_tweet, ref_user))
+        })?;
+        let mut user_tweet_query_results = Vec::new();
+        for query_result in query_results {
+            user_tweet_query_results.push(query_result?);
+        }
+
+        let page_ids: Vec<String> = user_tweet_query_results.iter().map(|(tweet, ..)| tweet.id.clone()).collect();
+        let mut entity_ids = page_ids.clone();
+        entity_ids.extend(user_tweet_query_results.iter().filter_map(|(_, _, ref_tweet, _)| ref_tweet.as_ref().map(|tweet| tweet.id.clone())));
+        let page_ids = serde_json::to_string(&page_ids)?;
+
+        let hashtag_map = self.get_hashtag_map(&entity_ids)?;
+
+        let mut mention_map: HashMap<String, Vec<BasicUserDetail>> = HashMap::new();
+        let mut mention_stmt = self.conn.prepare(
+            "SELECT mention_dict.tweet_id, user_dict.user_id, user_dict.username, user_dict.name
+            FROM mention_dict
+            JOIN user_dict ON user_dict.user_id = mention_dict.ref_user_id
+            WHERE mention_dict.tweet_id IN (SELECT value FROM json_each(:tweet_ids))
+            ORDER BY mention_dict.id"
+        )?;
+        let mention_rows = mention_stmt.query_map(named_params! {":tweet_ids": &page_ids}, |row| {
+            Ok((row.get::<_, String>(0)?, BasicUserDetail {
+                id: row.get(1)?,
+                username: row.get(2)?,
+                name: row.get(3)?
+            }))
+        })?;
+        for mention_row in mention_rows {
+            let (tweet_id, mentioned_user) = mention_row?;
+            mention_map.entry(tweet_id).or_default().push(mentioned_user);
+        }
+
+        let mut media_map: HashMap<String, Vec<Media>> = HashMap::new();
+        let mut media_stmt = self.conn.prepare(
+            "SELECT tweet_id, media_key, media_type, url, preview_image_url, width, height, alt_text, duration_ms, content_hash, local_path
+            FROM media_dict
+            WHERE tweet_id IN (SELECT value FROM json_each(:tweet_ids))
+            ORDER BY id"
+        )?;
+        let media_rows = media_stmt.query_map(named_params! {":tweet_ids": &page_ids}, |row| {
+            Ok((row.get::<_, String>(0)?, Media {
+                media_key: row.get(1)?,
+                media_type: row.get(2)?,
+                url: row.get(3)?,
+                preview_image_url: row.get(4)?,
+                width: row.get(5)?,
+                height: row.get(6)?,
+                alt_text: row.get(7)?,
+                duration_ms: row.get(8)?,
+                content_hash: row.get(9)?,
+                local_path: row.get(10)?
+            }))
+        })?;
+        for media_row in media_rows {
+            let (tweet_id, media) = media_row?;
+            media_map.entry(tweet_id).or_default().push(media);
+        }
+
+        let url_map = self.get_url_map(&entity_ids)?;
+
+        let mut version_map: HashMap<String, Vec<TweetVersion>> = HashMap::new();
+        let mut version_stmt = self.conn.prepare(
+            "SELECT original_tweet_id, tweet_id, text
+            FROM tweet_version
+            WHERE original_tweet_id IN (SELECT value FROM json_each(:tweet_ids))
+            ORDER BY length(tweet_id), tweet_id"
+        )?;
+        let version_rows = version_stmt.query_map(named_params! {":tweet_ids": &page_ids}, |row| {
+            Ok((row.get::<_, String>(0)?, TweetVersion { tweet_id: row.get(1)?, text: row.get(2)? }))
+        })?;
+        for version_row in version_rows {
+            let (original_tweet_id, version) = version_row?;
+            version_map.entry(original_tweet_id).or_default().push(version);
+        }
+
+        let mut fetched_tweet_list: Vec<FetchedTweet> = Vec::new();
+        for (mut latest_tweet, tweet_type_str, ref_tweet, ref_user) in user_tweet_query_results.into_iter() {
+            latest_tweet.hashtags = hashtag_map.get(&latest_tweet.id).cloned();
+            latest_tweet.mentions = mention_map.remove(&latest_tweet.id);
+            latest_tweet.media = media_map.remove(&latest_tweet.id);
+            latest_tweet.urls = url_map.get(&latest_tweet.id).cloned();
+            latest_tweet.versions = version_map.remove(&latest_tweet.id).filter(|versions| versions.len() > 1);
+
+            if &tweet_type_str == "tweet" {
+                latest_tweet.tweet_type = TweetType::Tweet;
+            } else if (&tweet_type_str == "retweet") || (&tweet_type_str == "reply") {
+                let (mut ref_tweet, ref_user) = match (ref_tweet, ref_user) {
+                    (Some(ref_tweet), Some(ref_user)) => (ref_tweet, ref_user),
+                    // authors of placeholder tweets are unknown
+                    (Some(ref_tweet), None) => {
+                        let ref_user = BasicUserDetail::placeholder(&ref_tweet.author_id);
+                        (ref_tweet, ref_user)
+                    }
+                    _ => { return Err(Box::new(rusqlite::Error::QueryReturnedNoRows)); }
+                };
+                ref_tweet.hashtags = hashtag_map.get(&ref_tweet.id).cloned();
+                ref_tweet.urls = url_map.get(&ref_tweet.id).cloned();
+
+                if &tweet_type_str == "retweet" {
+                    latest_tweet.tweet_type = TweetType::Retweet { tweet: ref_tweet, author: ref_user };
+                } else {
+                    latest_tweet.tweet_type = TweetType::Reply { tweet: ref_tweet, author: ref_user };
+                }
+            } else {
+                panic!("Unacceptable tweet type in table user_tweet!");
+            }
+
+            fetched_tweet_list.push(latest_tweet);
+        }
+
+        Ok(fetched_tweet_list)
+    }
+
+    /// Brings a stored tweet up to date with this version: the text and entities are replaced when
+    /// they changed, the edit controls always.
+    fn update_tweet_version(&self, tweet: &FetchedTweet, rowid: i64, stored_text: &str) -> Result<(), Box<dyn Error>> {
+        self.write_tweet_versions(tweet)?;
+        self.conn.execute(
+            "UPDATE user_tweet
+            SET edits_remaining = :edits_remaining, is_edit_eligible = :is_edit_eligible, editable_until = :editable_until
+            WHERE id = :rowid",
+            named_params! {
+                ":edits_remaining": tweet.edit_controls.as_ref().map(|edit_controls| edit_controls.edits_remaining),
+                ":is_edit_eligible": tweet.edit_controls.as_ref().map(|edit_controls| edit_controls.is_edit_eligible),
+                ":editable_until": tweet.edit_controls.as_ref().map(|edit_controls| &edit_controls.editable_until),
+                ":rowid": rowid
+            }
+        )?;
+        if stored_text == tweet.text {
+            return Ok(());
+        }
+
+        self.conn.execute(
+            "UPDATE user_tweet SET tweet_text = :tweet_text, is_note_tweet = :is_note_tweet WHERE id = :rowid",
+            named_params! {
+                ":tweet_text": &tweet.text,
+                ":is_note_tweet": tweet.is_note_tweet,
+                ":rowid": rowid
+            }
+        )?;
+        search::reindex_user_tweet(self.conn, rowid, stored_text, &tweet.text)?;
+        for entity_table in ["hashtag_dict", "mention_dict", "url_dict"] {
+            self.conn.execute(&format!("DELETE FROM {entity_table} WHERE tweet_id = ?"), [&tweet.id])?;
+        }
+        self.write_entities(&tweet.id, tweet.hashtags.as_deref(), tweet.mentions.as_deref(), tweet.urls.as_deref())?;
+        // media stay stored with their archived files, only media added by the edit are new
+        if let Some(media_vec) = &tweet.media {
+            for media in media_vec {
+                let stored_media: Option<i64> = self.conn.query_row(
+                    "SELECT id FROM media_dict WHERE tweet_id = :tweet_id AND media_key = :media_key",
+                    named_params! { ":tweet_id": &tweet.id, ":media_key": &media.media_key },
+                    |row| row.get(0)
+                ).optional()?;
+                if stored_media.is_none() {
+                    self.write_media(media, &tweet.id)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the known versions of the tweet. Every tweet has at least its current version.
+    fn write_tweet_versions(&self, tweet: &FetchedTweet) -> Result<(), Box<dyn Error>> {
+        let current_version = [TweetVersion { tweet_id: tweet.id.clone(), text: Some(tweet.text.clone()) }];
+        let versions = tweet.versions.as_deref().unwrap_or(&current_version);
+        let mut version_stmt = self.conn.prepare(
+            "INSERT INTO tweet_version
+            (original_tweet_id, tweet_id, text)
+            VALUES (:original_tweet_id, :tweet_id, :text)
+            ON CONFLICT (tweet_id) DO UPDATE
+            SET text = COALESCE(text, excluded.text)"
+        )?;
+        for version in versions {
+            version_stmt.execute(
+                named_params! {
+                    ":original_tweet_id": &tweet.id,
+                    ":tweet_id": &version.tweet_id,
+                    ":text": &version.text
+                }
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Records the hashtags, mentioned users and urls of `tweet_id`
+    fn write_entities(&self, tweet_id: &str, hashtags: Option<&[String]>, mentions: Option<&[BasicUserDetail]>, urls: Option<&[UrlEntity]>) -> Result<(), Box<dyn Error>> {
+        let mut hashtag_dict_stmt = self.conn.prepare(
+            "INSERT INTO hashtag_dict
+            (hashtag, tweet_id)
+            VALUES (:hashtag, :tweet_id)"
+        )?;
+        for hashtag in hashtags.unwrap_or_default() {
+            hashtag_dict_stmt.execute(
+                named_params! {
+                    ":hashtag": hashtag,
+                    ":tweet_id": tweet_id
+                }
+            )?;
+        }
+
+        let mut mention_dict_stmt = self.conn.prepare(
+            "INSERT INTO mention_dict
+            (ref_user_id, tweet_id)
+            VALUES (:ref_user_id, :tweet_id)"
+        )?;
+        for mentioned_user in mentions.unwrap_or_default() {
+            mention_dict_stmt.execute(
+                named_params! {
+                    ":ref_user_id": &mentioned_user.id,
+                    ":tweet_id": tweet_id
+                }
+            )?;
+        }
+
+        let mut url_dict_stmt = self.conn.prepare(
+            "INSERT INTO url_dict
+            (tweet_id, url, expanded_url, display_url, unwound_url, domain, title)
+            VALUES (:tweet_id, :url, :expanded_url, :display_url, :unwound_url, :domain, :title)"
+        )?;
+        for url in urls.unwrap_or_default() {
+            url_dict_stmt.execute(
+                named_params! {
+                    ":tweet_id": tweet_id,
+                    ":url": &url.url,
+                    ":expanded_url": &url.expanded_url,
+                    ":display_url": &url.display_url,
+                    ":unwound_url": &url.unwound_url,
+                    ":domain": &url.domain,
+                    ":title": &url.title
+                }
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn write_media(&self, media: &Media, tweet_id: &str) -> Result<(), Box<dyn Error>> {
+        let mut media_dict_stmt = self.conn.prepare(
+            "INSERT INTO media_dict
+            (media_key, tweet_id, media_type, url, preview_image_url, width, height, alt_text, duration_ms, content_hash, local_path)
+            VALUES (:media_key, :tweet_id, :media_type, :url, :preview_image_url, :width, :height, :alt_text, :duration_ms, :content_hash, :local_path)"
+        )?;
+        media_dict_stmt.execute(
+            named_params! {
+                ":media_key": &media.media_key,
+                ":tweet_id": tweet_id,
+                ":media_type": &media.media_type,
+                ":url": &media.url,
+                ":preview_image_url": &media.preview_image_url,
+                ":width": &media.width,
+                ":height": &media.height,
+                ":alt_text": &media.alt_text,
+                ":duration_ms": &media.duration_ms,
+                ":content_hash": &media.content_hash,
+                ":local_path": &media.local_path
+            }
+        )?;
+
+        Ok(())
+    }
+
+    /// `fetch_order` of a record placed among the rows of `user_id` in `table`
+    fn fetch_order(&self, placement: Placement, table: &str, user_id: &str) -> rusqlite::Result<i64> {
+        let (bound, step) = match placement {
+            Placement::Older => ("MIN", -1),
+            Placement::Newer => ("MAX", 1)
+        };
+        self.conn.query_row(
+            &format!("SELECT COALESCE({bound}(fetch_order), 0) + :step FROM {table} WHERE user_id = :user_id"),
+            named_params! { ":step": step, ":user_id": user_id },
+            |row| row.get(0)
+        )
+    }
+}
+
+impl Store for SqliteStore<'_> {
+    fn write_profile(&self, fetched_user: &FetchedUser, task_type: &TaskType) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO user_profile
+            (time, user_id, username, name, location, description, url, profile_image_url, pinned_tweet_id, protected, verified, profile_banner_url)
+            VALUES (:time, :user_id, :username, :name, :location, :description, :url, :profile_image_url, :pinned_tweet_id, :protected, :verified, :profile_banner_url)"
+        )?;
+
+        let mut changes: Vec<ProfileChange> = Vec::new();
+        if let TaskType::Monitoring = task_type {
+            // looked up by id, the username itself may have changed
+            if let Some(latest_record) = self.get_latest_profile_by_id(&fetched_user.user.id)? {
+                if latest_record.user == fetched_user.user {
+                    return Ok(());
+                }
+                changes = ProfileChange::diff(&latest_record.user, fetched_user);
+            }
+        }
+
+        stmt.execute(named_params! {
+            ":time": &fetched_user.recorded_time,
+            ":user_id": &fetched_user.user.id,
+            ":name": &fetched_user.user.name,
+            ":username": &fetched_user.user.username,
+            ":location": &fetched_user.user.location,
+            ":description": &fetched_user.user.description,
+            ":url": &fetched_user.user.url,
+            ":profile_image_url": &fetched_user.user.profile_image_url,
+            ":pinned_tweet_id": &fetched_user.user.pinned_tweet_id,
+            ":protected": &fetched_user.user.protected,
+            ":verified": &fetched_user.user.verified,
+            ":profile_banner_url": &fetched_user.user.profile_banner_url,
+        })?;
+        for change in changes.iter() {
+            change.write_to_db(self.conn)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_profiles(&self, username: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FetchedUser>, Box<dyn Error>> {
+        let mut user_profile_stmt = self.conn.prepare("SELECT * FROM user_profile WHERE username = :username ORDER BY id DESC LIMIT :limit OFFSET :offset")?;
+        let query_results = user_profile_stmt.query_map(
+            named_params! {
+                ":username": username,
+                ":limit": max_results.map(i64::from).unwrap_or(-1),
+                ":offset": offset
+            },
+            profile_from_row
+        )?;
+
+        let mut user_vec: Vec<FetchedUser> = Vec::new();
+        for fetched_user in query_results {
+            user_vec.push(fetched_user?);
+        }
+        Ok(user_vec)
+    }
+
+    fn write_tweet(&self, tweet: &FetchedTweet) -> Result<(), Box<dyn Error>> {
+        let stored: Option<(i64, String)> = self.conn.query_row(
+            "SELECT id, tweet_text FROM user_tweet WHERE tweet_id = ?",
+            [&tweet.id],
+            |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?;
+        if let Some((rowid, stored_text)) = stored {
+            return self.update_tweet_version(tweet, rowid, &stored_text);
+        }
+
+        let mut user_tweet_stmt = self.conn.prepare(
+            "INSERT INTO user_tweet
+            (tweet_id, tweet_text, time, author_id, tweet_type, ref_tweet_id, conversation_id, in_reply_to_user_id, replied_to_id,
+                is_note_tweet, edits_remaining, is_edit_eligible, editable_until)
+            VALUES (:tweet_id, :tweet_text, :time, :author_id, :tweet_type, :ref_tweet_id, :conversation_id, :in_reply_to_user_id, :replied_to_id,
+                :is_note_tweet, :edits_remaining, :is_edit_eligible, :editable_until)"
+        )?;
+
+        let (tweet_type, ref_tweet_id) = match &tweet.tweet_type {
+            TweetType::Tweet => ("tweet", None),
+            TweetType::Reply { tweet, author: _ } => ("reply", Some(tweet.id.as_str())),
+            TweetType::Retweet { tweet, author: _ } => ("retweet", Some(tweet.id.as_str()))
+        };
+
+        user_tweet_stmt.execute(
+            named_params! {
+                ":tweet_id": &tweet.id,
+                ":tweet_text": &tweet.text,
+                ":time": &tweet.created_at,
+                ":author_id": &tweet.author_id,
+                ":tweet_type": tweet_type,
+                ":ref_tweet_id": ref_tweet_id,
+                ":conversation_id": &tweet.conversation_id,
+                ":in_reply_to_user_id": &tweet.in_reply_to_user_id,
+                ":replied_to_id": &tweet.replied_to_id,
+                ":is_note_tweet": tweet.is_note_tweet,
+                ":edits_remaining": tweet.edit_controls.as_ref().map(|edit_controls| edit_controls.edits_remaining),
+                ":is_edit_eligible": tweet.edit_controls.as_ref().map(|edit_controls| edit_controls.is_edit_eligible),
+                ":editable_until": tweet.edit_controls.as_ref().map(|edit_controls| &edit_controls.editable_until)
+            }
+        )?;
+        search::index_user_tweet(self.conn, self.conn.last_insert_rowid(), &tweet.text)?;
+        self.write_tweet_versions(tweet)?;
+        self.write_entities(&tweet.id, tweet.hashtags.as_deref(), tweet.mentions.as_deref(), tweet.urls.as_deref())?;
+
+        if let Some(media_vec) = &tweet.media {
+            for media in media_vec {
+                self.write_media(media, &tweet.id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_tweets(&self, author_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FetchedTweet>, Box<dyn Error>> {
+        self.get_tweet_page(USER_TWEET_PAGE, named_params! {
+            ":author_id": author_id,
+            ":limit": max_results.map(i64::from).unwrap_or(-1),
+            ":offset": offset
+        })
+    }
+
+    fn newest_tweet_id(&self, author_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let newest_id: Option<String> = self.conn.query_row(
+            "SELECT version_id FROM (
+                SELECT tweet_id AS version_id FROM user_tweet WHERE author_id = :author_id
+                UNION ALL
+                SELECT tweet_version.tweet_id FROM tweet_version
+                JOIN user_tweet ON user_tweet.tweet_id = tweet_version.original_tweet_id
+                WHERE user_tweet.author_id = :author_id
+            )
+            ORDER BY length(version_id) DESC, version_id DESC LIMIT 1",
+            named_params! { ":author_id": author_id },
+            |row| row.get(0)
+        ).optional()?;
+        Ok(newest_id)
+    }
+
+    fn write_like(&self, liked_tweet: &LikedTweet, placement: Placement) -> Result<(), Box<dyn Error>> {
+        let fetch_order = self.fetch_order(placement, "user_liked", &liked_tweet.user_id)?;
+        self.conn.execute(
+            "INSERT INTO user_liked
+            (time, user_id, author_id, ref_tweet_id, fetch_order)
+            VALUES (:time, :user_id, :author_id, :ref_tweet_id, :fetch_order)",
+            named_params! {
+                ":time": &liked_tweet.recorded_time,
+                ":user_id": &liked_tweet.user_id,
+                ":author_id": &liked_tweet.author.id,
+                ":ref_tweet_id": &liked_tweet.tweet.id,
+                ":fetch_order": fetch_order
+            }
+        )?;
+
+        Ok(())
+    }
+
+    fn get_likes(&self, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<LikedTweet>, Box<dyn Error>> {
+        let page_params = named_params! {
+            ":user_id": user_id,
+            ":limit": max_results.map(i64::from).unwrap_or(-1),
+            ":offset": offset
+        };
+
+        let mut user_liked_stmt = self.conn.prepare(&format!(
+            "{USER_LIKED_PAGE}
+            SELECT page.time, tweet_dict.tweet_id, tweet_dict.author_id, tweet_dict.text,
+                user_dict.user_id, user_dict.username, user_dict.name,
+                tweet_dict.conversation_id, tweet_dict.replied_to_id
+            FROM page
+            LEFT JOIN tweet_dict ON tweet_dict.tweet_id = page.ref_tweet_id
+            LEFT JOIN user_dict ON user_dict.user_id = page.author_id
+            ORDER BY page.fetch_order DESC"
+        ))?;
+        let query_results = user_liked_stmt.query_map(page_params, |row| {
+            let tweet = match (row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?) {
+                (Some(id), Some(author_id), Some(text)) => Some(BasicTweet {
+                    id,
+                    author_id,
+                    text,
+                    hashtags: None,
+                    urls: None,
+                    conversation_id: row.get(7)?,
+                    replied_to_id: row.get(8)?
+                }),
+                _ => None
+            };
+            let author = match (row.get::<_, Option<String>>(4)?, row.get::<_, Option<String>>(5)?, row.get::<_, Option<String>>(6)?) {
+                (Some(id), Some(username), Some(name)) => Some(BasicUserDetail { id, username, name }),
+                _ => None
+            };
+            Ok((row.get::<_, Option<String>>(0)?, tweet, author))
+        })?;
+        let mut queried_liked_vec = Vec::new();
+        for query_result in query_results {
+            queried_liked_vec.push(query_result?);
+        }
+
+        let liked_ids: Vec<String> = queried_liked_vec.iter().filter_map(|(_, tweet, _)| tweet.as_ref().map(|tweet| tweet.id.clone())).collect();
+        let hashtag_map = self.get_hashtag_map(&liked_ids)?;
+        let url_map = self.get_url_map(&liked_ids)?;
+
+        let mut liked_tweet_vec: Vec<LikedTweet> = Vec::new();
+        for (recorded_time, tweet_detail, author_detail) in queried_liked_vec.into_iter() {
+            let (mut tweet_detail, author_detail) = match (tweet_detail, author_detail) {
+                (Some(tweet_detail), Some(author_detail)) => (tweet_detail, author_detail),
+                _ => { return Err(Box::new(rusqlite::Error::QueryReturnedNoRows)); }
+            };
+            tweet_detail.hashtags = hashtag_map.get(&tweet_detail.id).cloned();
+            tweet_detail.urls = url_map.get(&tweet_detail.id).cloned();
+            liked_tweet_vec.push(
+                LikedTweet {
+                    recorded_time,
+                    user_id: user_id.to_string(),
+                    tweet: tweet_detail,
+                    author: author_detail
+                }
+            );
+        }
+
+        Ok(liked_tweet_vec)
+    }
+
+    fn newest_like_id(&self, user_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let newest_id: Option<String> = self.conn.query_row(
+            "SELECT ref_tweet_id FROM user_liked WHERE user_id = ? ORDER BY fetch_order DESC LIMIT 1",
+            [user_id],
+            |row| row.get(0)
+        ).optional()?;
+        Ok(newest_id)
+    }
+
+    fn write_following(&self, following_user: &FollowingUser, placement: Placement) -> Result<(), Box<dyn Error>> {
+        match &following_user.action {
+            FollowingAction::Follow => {
+                let fetch_order = self.fetch_order(placement, "user_following", &following_user.user_id)?;
+                for following_table in ["user_following", "user_current_following"] {
+                    self.conn.execute(
+                        &format!("INSERT INTO {following_table}
+                        (time, user_id, following_user_id, action, fetch_order)
+                        VALUES (:time, :user_id, :following_user_id, 'follow', :fetch_order)"),
+                        named_params! {
+                            ":time": &following_user.recorded_time,
+                            ":user_id": &following_user.user_id,
+                            ":following_user_id": &following_user.followed_user.id,
+                            ":fetch_order": fetch_order
+                        }
+                    )?;
+                }
+            }
+            FollowingAction::Unfollow => {
+                self.conn.execute(
+                    "DELETE FROM user_current_following WHERE user_id = :user_id AND following_user_id = :following_user_id",
+                    named_params! {
+                        ":user_id": &following_user.user_id,
+                        ":following_user_id": &following_user.followed_user.id
+                    }
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_following(&self, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FollowingUser>, Box<dyn Error>> {
+        let mut user_following_stmt = self.conn.prepare(
+            "SELECT user_following.time, user_following.action, user_dict.user_id, user_dict.username, user_dict.name
+            FROM user_following
+            LEFT JOIN user_dict ON user_dict.user_id = user_following.following_user_id
+            WHERE user_following.user_id = :user_id
+            ORDER BY user_following.fetch_order DESC LIMIT :limit OFFSET :offset"
+        )?;
+        let query_results = user_following_stmt.query_map(
+            named_params! {
+                ":user_id": user_id,
+                ":limit": max_results.map(i64::from).unwrap_or(-1),
+                ":offset": offset
+            },
+            |row| {
+                let followed_user = match (row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?, row.get::<_, Option<String>>(4)?) {
+                    (Some(id), Some(username), Some(name)) => Some(BasicUserDetail { id, username, name }),
+                    _ => None
+                };
+                Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?, followed_user))
+            }
+        )?;
+
+        let mut following_vec: Vec<FollowingUser> = Vec::new();
+        for query_result in query_results {
+            let (recorded_time, action_str, followed_user) = query_result?;
+            let following_action = if action_str.as_str() == "follow" {
+                FollowingAction::Follow
+            } else if action_str.as_str() == "unfollow" {
+                FollowingAction::Unfollow
+            } else {
+                panic!("Unacceptable following type string");
+            };
+
+            let followed_user = followed_user.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            following_vec.push(
+                FollowingUser {
+                    recorded_time,
+                    user_id: user_id.to_string(),
+                    followed_user,
+                    action: following_action
+                }
+            );
+        }
+        Ok(following_vec)
+    }
+
+    fn current_following_ids(&self, user_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut user_following_stmt = self.conn.prepare("SELECT following_user_id FROM user_current_following WHERE user_id = ? ORDER BY fetch_order DESC")?;
+        let query_results = user_following_stmt.query_map([user_id], |row| row.get(0))?;
+
+        let mut queried_following_vec: Vec<String> = Vec::new();
+        for query_result in query_results {
+            queried_following_vec.push(query_result?);
+        }
+        Ok(queried_following_vec)
+    }
+
+    fn write_user(&self, user: &BasicUserDetail) -> Result<(), Box<dyn Error>> {
+        if user.is_placeholder() {
+            // never hide what is known about the user behind a placeholder
+            self.conn.execute(
+                "INSERT INTO user_dict
+                (user_id, username, name, availability)
+                VALUES (:user_id, :username, :name, :availability)
+                ON CONFLICT (user_id) DO NOTHING",
+                named_params! {
+                    ":user_id": &user.id,
+                    ":username": &user.username,
+                    ":name": &user.name,
+                    ":availability": Availability::Placeholder.as_str()
+                }
+            )?;
+            return Ok(());
+        }
+
+        self.conn.execute(
+            "INSERT INTO user_dict
+            (user_id, username, name)
+            VALUES (:user_id, :username, :name)
+            ON CONFLICT (user_id) DO UPDATE
+            SET username = excluded.username, name = excluded.name, availability = 'available', unavailable_reason = NULL",
+            named_params! {
+                ":user_id": &user.id,
+                ":username": &user.username,
+                ":name": &user.name
+            }
+        )?;
+
+        Ok(())
+    }
+
+    fn get_user(&self, user_id: &str) -> Result<Option<BasicUserDetail>, Box<dyn Error>> {
+        let user: Option<BasicUserDetail> = self.conn.query_row(
+            "SELECT user_id, username, name FROM user_dict WHERE user_id = ?",
+            [user_id],
+            |row| {
+                Ok(BasicUserDetail {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    name: row.get(2)?
+                })
+            }
+        ).optional()?;
+        Ok(user)
+    }
+
+    fn write_dict_tweet(&self, tweet: &BasicTweet) -> Result<(), Box<dyn Error>> {
+        let check_exist: Option<(i64, String, String)> = self.conn.query_row(
+            "SELECT id, availability, text FROM tweet_dict WHERE tweet_id = ?",
+            [&tweet.id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        ).optional()?;
+
+        match check_exist {
+            Some((rowid, availability, old_text)) if availability != Availability::Available.as_str() && !tweet.is_placeholder() => {
+                self.conn.execute(
+                    "UPDATE tweet_dict
+                    SET author_id = :author_id, text = :text, conversation_id = :conversation_id,
+                    replied_to_id = :replied_to_id, availability = :availability, unavailable_reason = NULL
+                    WHERE id = :rowid",
+                    named_params! {
+                        ":author_id": &tweet.author_id,
+                        ":text": &tweet.text,
+                        ":conversation_id": &tweet.conversation_id,
+                        ":replied_to_id": &tweet.replied_to_id,
+                        ":availability": Availability::Available.as_str(),
+                        ":rowid": rowid
+                    }
+                )?;
+                search::reindex_dict_tweet(self.conn, rowid, &old_text, &tweet.text)?;
+                self.write_entities(&tweet.id, tweet.hashtags.as_deref(), None, tweet.urls.as_deref())?;
+            }
+            Some(_) => {
+                // tweets first seen without conversation details, e.g. as liked tweets, get them
+                // once they are fetched with them
+                self.conn.execute(
+                    "UPDATE tweet_dict
+                    SET conversation_id = COALESCE(conversation_id, :conversation_id),
+                    replied_to_id = COALESCE(replied_to_id, :replied_to_id)
+                    WHERE tweet_id = :tweet_id",
+                    named_params! {
+                        ":conversation_id": &tweet.conversation_id,
+                        ":replied_to_id": &tweet.replied_to_id,
+                        ":tweet_id": &tweet.id
+                    }
+                )?;
+            }
+            None => {
+                let availability = if tweet.is_placeholder() { Availability::Placeholder } else { Availability::Available };
+                self.conn.execute(
+                    "INSERT INTO tweet_dict
+                    (tweet_id, author_id, text, conversation_id, replied_to_id, availability)
+                    VALUES (:tweet_id, :author_id, :text, :conversation_id, :replied_to_id, :availability)",
+                    named_params! {
+                        ":tweet_id": &tweet.id,
+                        ":author_id": &tweet.author_id,
+                        ":text": &tweet.text,
+                        ":conversation_id": &tweet.conversation_id,
+                        ":replied_to_id": &tweet.replied_to_id,
+                        ":availability": availability.as_str(),
+                    }
+                )?;
+                search::index_dict_tweet(self.conn, self.conn.last_insert_rowid(), &tweet.text)?;
+                self.write_entities(&tweet.id, tweet.hashtags.as_deref(), None, tweet.urls.as_deref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_dict_tweet(&self, tweet_id: &str) -> Result<Option<BasicTweet>, Box<dyn Error>> {
+        let tweet: Option<BasicTweet> = self.conn.query_row(
+            "SELECT tweet_id, author_id, text, conversation_id, replied_to_id FROM tweet_dict WHERE tweet_id = ?",
+            [tweet_id],
+            |row| {
+                Ok(BasicTweet {
+                    id: row.get(0)?,
+                    author_id: row.get(1)?,
+                    text: row.get(2)?,
+                    hashtags: None,
+                    urls: None,
+                    conversation_id: row.get(3)?,
+                    replied_to_id: row.get(4)?
+                })
+            }
+        ).optional()?;
+
+        let tweet_ids = Vec::from_iter(tweet.iter().map(|tweet| tweet.id.clone()));
+        let mut hashtag_map = self.get_hashtag_map(&tweet_ids)?;
+        let mut url_map = self.get_url_map(&tweet_ids)?;
+        Ok(tweet.map(|mut tweet| {
+            tweet.hashtags = hashtag_map.remove(&tweet.id);
+            tweet.urls = url_map.remove(&tweet.id);
+            tweet
+        }))
+    }
+}
+
+fn profile_from_row(row: &rusqlite::Row) -> rusqlite::Result<FetchedUser> {
+    Ok(FetchedUser {
+        recorded_time: row.get(1)?,
+        user: UserDetail {
+            id: row.get(2)?,
+            username: row.get(3)?,
+            name: row.get(4)?,
+            location: row.get(5)?,
+            description: row.get(6)?,
+            url: row.get(7)?,
+            profile_image_url: row.get(8)?,
+            pinned_tweet_id: row.get(9)?,
+            protected: row.get(10)?,
+            verified: row.get(11)?,
+            profile_banner_url: row.get(12)?
+        }
+    })
+}
+
+#[derive(Debug, Default)]
+struct MemoryTables {
+    /// Records oldest first
+    profiles: Vec<FetchedUser>,
+    tweets: Vec<FetchedTweet>,
+    likes: Vec<LikedTweet>,
+    following: Vec<FollowingUser>,
+    /// Monitored user id and followed user id
+    current_following: Vec<(String, String)>,
+    users: HashMap<String, BasicUserDetail>,
+    dict_tweets: HashMap<String, BasicTweet>
+}
+
+/// Keeps the records in memory, e.g. for tests or when embedding the fetchers without a database
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    tables: Mutex<MemoryTables>
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    fn tables(&self) -> std::sync::MutexGuard<'_, MemoryTables> {
+        self.tables.lock().expect("the store lock should not be poisoned")
+    }
+}
+
+impl Store for MemoryStore {
+    fn write_profile(&self, fetched_user: &FetchedUser, task_type: &TaskType) -> Result<(), Box<dyn Error>> {
+        let mut tables = self.tables();
+        if let TaskType::Monitoring = task_type {
+            let latest_record = tables.profiles.iter().rev().find(|profile| profile.user.id == fetched_user.user.id);
+            if latest_record.is_some_and(|latest_record| latest_record.user == fetched_user.user) {
+                return Ok(());
+            }
+        }
+        tables.profiles.push(fetched_user.clone());
+        Ok(())
+    }
+
+    fn get_profiles(&self, username: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FetchedUser>, Box<dyn Error>> {
+        let tables = self.tables();
+        Ok(newest_page(tables.profiles.iter().filter(|profile| profile.user.username == username), max_results, offset))
+    }
+
+    fn write_tweet(&self, tweet: &FetchedTweet) -> Result<(), Box<dyn Error>> {
+        let mut tables = self.tables();
+        let stored_tweet = match tables.tweets.iter_mut().find(|stored_tweet| stored_tweet.id == tweet.id) {
+            Some(stored_tweet) => stored_tweet,
+            None => {
+                tables.tweets.push(tweet.clone());
+                return Ok(());
+            }
+        };
+
+        stored_tweet.edit_controls = tweet.edit_controls.clone();
+        for version in tweet.versions.iter().flatten() {
+            let stored_versions = stored_tweet.versions.get_or_insert_with(Vec::new);
+            match stored_versions.iter_mut().find(|stored_version| stored_version.tweet_id == version.tweet_id) {
+                Some(stored_version) => { stored_version.text = stored_version.text.take().or_else(|| version.text.clone()); }
+                None => { stored_versions.push(TweetVersion { tweet_id: version.tweet_id.clone(), text: version.text.clone() }); }
+            }
+        }
+        if stored_tweet.text == tweet.text {
+            return Ok(());
+        }
+
+        stored_tweet.text = tweet.text.clone();
+        stored_tweet.is_note_tweet = tweet.is_note_tweet;
+        stored_tweet.hashtags = tweet.hashtags.clone();
+        stored_tweet.mentions = tweet.mentions.clone();
+        stored_tweet.urls = tweet.urls.clone();
+        // media stay stored, only media added by the edit are new
+        for media in tweet.media.iter().flatten() {
+            let stored_media = stored_tweet.media.get_or_insert_with(Vec::new);
+            if !stored_media.iter().any(|stored| stored.media_key == media.media_key) {
+                stored_media.push(media.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn get_tweets(&self, author_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FetchedTweet>, Box<dyn Error>> {
+        let tables = self.tables();
+        let mut tweets: Vec<&FetchedTweet> = tables.tweets.iter().filter(|tweet| tweet.author_id == author_id).collect();
+        // ordered by tweet id like `USER_TWEET_PAGE`, whatever order the pages were written in
+        tweets.sort_by_key(|tweet| (tweet.id.len(), tweet.id.as_str()));
+        Ok(newest_page(tweets.into_iter(), max_results, offset))
+    }
+
+    fn newest_tweet_id(&self, author_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let tables = self.tables();
+        let newest_id = tables.tweets.iter()
+            .filter(|tweet| tweet.author_id == author_id)
+            .flat_map(|tweet| std::iter::once(&tweet.id).chain(tweet.versions.iter().flatten().map(|version| &version.tweet_id)))
+            .max_by_key(|tweet_id| (tweet_id.len(), tweet_id.as_str()))
+            .cloned();
+        Ok(newest_id)
+    }
+
+    fn write_like(&self, liked_tweet: &LikedTweet, placement: Placement) -> Result<(), Box<dyn Error>> {
+        place(&mut self.tables().likes, liked_tweet.clone(), placement);
+        Ok(())
+    }
+
+    fn get_likes(&self, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<LikedTweet>, Box<dyn Error>> {
+        let tables = self.tables();
+        Ok(newest_page(tables.likes.iter().filter(|liked_tweet| liked_tweet.user_id == user_id), max_results, offset))
+    }
+
+    fn newest_like_id(&self, user_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let tables = self.tables();
+        Ok(tables.likes.iter().rev().find(|liked_tweet| liked_tweet.user_id == user_id).map(|liked_tweet| liked_tweet.tweet.id.clone()))
+    }
+
+    fn write_following(&self, following_user: &FollowingUser, placement: Placement) -> Result<(), Box<dyn Error>> {
+        let mut tables = self.tables();
+        let following_pair = (following_user.user_id.clone(), following_user.followed_user.id.clone());
+        match following_user.action {
+            FollowingAction::Follow => {
+                place(&mut tables.following, following_user.clone(), placement);
+                place(&mut tables.current_following, following_pair, placement);
+            }
+            FollowingAction::Unfollow => {
+                tables.current_following.retain(|current_pair| *current_pair != following_pair);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_following(&self, user_id: &str, max_results: Option<u16>, offset: u16) -> Result<Vec<FollowingUser>, Box<dyn Error>> {
+        let tables = self.tables();
+        Ok(newest_page(tables.following.iter().filter(|following_user| following_user.user_id == user_id), max_results, offset))
+    }
+
+    fn current_following_ids(&self, user_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let tables = self.tables();
+        Ok(tables.current_following.iter().rev()
+            .filter(|(following_user_id, _)| following_user_id == user_id)
+            .map(|(_, followed_id)| followed_id.clone())
+            .collect())
+    }
+
+    fn write_user(&self, user: &BasicUserDetail) -> Result<(), Box<dyn Error>> {
+        let mut tables = self.tables();
+        if user.is_placeholder() && tables.users.contains_key(&user.id) {
+            return Ok(());
+        }
+        tables.users.insert(user.id.clone(), user.clone());
+        Ok(())
+    }
+
+    fn get_user(&self, user_id: &str) -> Result<Option<BasicUserDetail>, Box<dyn Error>> {
+        Ok(self.tables().users.get(user_id).cloned())
+    }
+
+    fn write_dict_tweet(&self, tweet: &BasicTweet) -> Result<(), Box<dyn Error>> {
+        let mut tables = self.tables();
+        match tables.dict_tweets.get_mut(&tweet.id) {
+            Some(stored_tweet) if stored_tweet.is_placeholder() && !tweet.is_placeholder() => {
+                *stored_tweet = tweet.clone();
+            }
+            // tweets first seen without conversation details get them once fetched with them
+            Some(stored_tweet) => {
+                stored_tweet.conversation_id = stored_tweet.conversation_id.take().or_else(|| tweet.conversation_id.clone());
+                stored_tweet.replied_to_id = stored_tweet.replied_to_id.take().or_else(|| tweet.replied_to_id.clone());
+            }
+            None => {
+                tables.dict_tweets.insert(tweet.id.clone(), tweet.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn get_dict_tweet(&self, tweet_id: &str) -> Result<Option<BasicTweet>, Box<dyn Error>> {
+        Ok(self.tables().dict_tweets.get(tweet_id).cloned())
+    }
+}
+
+/// Adds `record` to `records`, kept oldest first
+fn place<T>(records: &mut Vec<T>, record: T, placement: Placement) {
+    match placement {
+        Placement::Older => records.insert(0, record),
+        Placement::Newer => records.push(record)
+    }
+}
+
+/// A page of `records`, given oldest first, newest first
+fn newest_page<'a, T: Clone + 'a>(records: impl DoubleEndedIterator<Item = &'a T>, max_results: Option<u16>, offset: u16) -> Vec<T> {
+    records.rev()
+        .skip(offset.into())
+        .take(max_results.map(usize::from).unwrap_or(usize::MAX))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::query_result::{EditControls, Media, TweetType, UrlEntity, UserDetail};
+
+    use super::*;
+
+    fn user(id: &str, username: &str) -> BasicUserDetail {
+        BasicUserDetail { id: id.to_string(), username: username.to_string(), name: username.to_uppercase() }
+    }
+
+    fn dict_tweet(id: &str, author_id: &str, text: &str) -> BasicTweet {
+        BasicTweet {
+            text: text.to_string(),
+            id: id.to_string(),
+            author_id: author_id.to_string(),
+            hashtags: None,
+            urls: None,
+            conversation_id: None,
+            replied_to_id: None
+        }
+    }
+
+    /// Writes the same records to `store` and checks what is read back
+    fn check_store(store: &dyn Store) {
+        let suisei = user("1", "suisei");
+        let towa = user("2", "towa");
+
+        store.write_user(&towa).unwrap();
+        store.write_user(&BasicUserDetail::placeholder("2")).unwrap();
+        store.write_user(&BasicUserDetail::placeholder("3")).unwrap();
+        assert_eq!(store.get_user("2").unwrap(), Some(towa.clone()));
+        assert!(store.get_user("3").unwrap().unwrap().is_placeholder());
+        assert_eq!(store.get_user("4").unwrap(), None);
+
+        store.write_dict_tweet(&BasicTweet::placeholder("20")).unwrap();
+        store.write_dict_tweet(&dict_tweet("20", "2", "towa tweet")).unwrap();
+        assert_eq!(store.get_dict_tweet("20").unwrap().unwrap().text, "towa tweet");
+        assert_eq!(store.get_dict_tweet("21").unwrap(), None);
+
+        let mut profile = FetchedUser::record(&TaskType::Monitoring);
+        profile.user.id = suisei.id.clone();
+        profile.user.username = suisei.username.clone();
+        profile.user.name = suisei.name.clone();
+        store.write_profile(&profile, &TaskType::Monitoring).unwrap();
+        store.write_profile(&profile, &TaskType::Monitoring).unwrap();
+        profile.user.name = String::from("Suichan");
+        store.write_profile(&profile, &TaskType::Monitoring).unwrap();
+        let profiles = store.get_profiles("suisei", None, 0).unwrap();
+        assert_eq!(profiles.iter().map(|profile| profile.user.name.as_str()).collect::<Vec<&str>>(), vec!["Suichan", "SUISEI"]);
+
+        for (tweet_id, text) in [("10", "first"), ("11", "second"), ("10", "first")] {
+            let mut tweet = FetchedTweet::new();
+            tweet.id = tweet_id.to_string();
+            tweet.text = text.to_string();
+            tweet.author_id = suisei.id.clone();
+            tweet.created_at = String::from("2023-01-01T00:00:00.000Z");
+            store.write_tweet(&tweet).unwrap();
+        }
+        let tweets = store.get_tweets("1", Some(1), 1).unwrap();
+        assert_eq!(tweets.iter().map(|tweet| tweet.id.as_str()).collect::<Vec<&str>>(), vec!["10"]);
+        assert_eq!(store.get_tweets("1", None, 0).unwrap().len(), 2);
+        assert_eq!(store.newest_tweet_id("1").unwrap(), Some(String::from("11")));
+        assert_eq!(store.newest_tweet_id("2").unwrap(), None);
+        // a page of older tweets written after a newer page, each page oldest first
+        for tweet_id in ["13", "14", "9", "12"] {
+            let mut tweet = FetchedTweet::new();
+            tweet.id = tweet_id.to_string();
+            tweet.text = format!("tweet {tweet_id}");
+            tweet.author_id = suisei.id.clone();
+            tweet.created_at = String::from("2023-01-01T00:00:00.000Z");
+            store.write_tweet(&tweet).unwrap();
+        }
+        let tweets = store.get_tweets("1", None, 0).unwrap();
+        assert_eq!(tweets.iter().map(|tweet| tweet.id.as_str()).collect::<Vec<&str>>(), vec!["14", "13", "12", "11", "10", "9"]);
+        let tweets = store.get_tweets("1", Some(2), 2).unwrap();
+        assert_eq!(tweets.iter().map(|tweet| tweet.id.as_str()).collect::<Vec<&str>>(), vec!["12", "11"]);
+
+        let mut liked_tweet = LikedTweet::record(&TaskType::Monitoring, "1");
+        liked_tweet.tweet = dict_tweet("20", "2", "towa tweet");
+        liked_tweet.author = towa.clone();
+        store.write_like(&liked_tweet, Placement::Newer).unwrap();
+        assert_eq!(store.get_likes("1", None, 0).unwrap()[0].tweet.id, "20");
+        assert_eq!(store.newest_like_id("1").unwrap(), Some(String::from("20")));
+        // history paged in later stays behind the newest like
+        let mut older_like = liked_tweet.clone();
+        older_like.tweet = dict_tweet("21", "2", "older towa tweet");
+        store.write_dict_tweet(&older_like.tweet).unwrap();
+        store.write_like(&older_like, Placement::Older).unwrap();
+        let likes = store.get_likes("1", None, 0).unwrap();
+        assert_eq!(likes.iter().map(|like| like.tweet.id.as_str()).collect::<Vec<&str>>(), vec!["20", "21"]);
+        assert_eq!(store.newest_like_id("1").unwrap(), Some(String::from("20")));
+
+        let mut following = FollowingUser::record(&TaskType::Monitoring, "1");
+        following.followed_user = towa.clone();
+        store.write_following(&following, Placement::Newer).unwrap();
+        let mut older_following = following.clone();
+        older_following.followed_user = suisei.clone();
+        store.write_user(&suisei).unwrap();
+        store.write_following(&older_following, Placement::Older).unwrap();
+        assert_eq!(store.current_following_ids("1").unwrap(), vec![String::from("2"), String::from("1")]);
+        following.action = FollowingAction::Unfollow;
+        store.write_following(&following, Placement::Newer).unwrap();
+        assert_eq!(store.current_following_ids("1").unwrap(), vec![String::from("1")]);
+        assert_eq!(store.get_following("1", None, 0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_db_write_and_get() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let store = SqliteStore::new(&conn);
+        let hsmtss_profile_0 = FetchedUser {
+            recorded_time: None,
+            user: UserDetail {
+                id: "0".to_string(),
+                name: "Hoshimachi Suisei".to_string(),
+                username: "hoshimatisuisei".to_string(),
+                location: Some("Tokyo".to_string()),
+                description: Some("Inui Toko Daisuki!".to_string()),
+                url: None,
+                profile_image_url: None,
+                profile_banner_url: None,
+                pinned_tweet_id: None,
+                protected: Some(false),
+                verified: Some(false)
+            }
+        };
+
+        let hsmtss_profile_1 = FetchedUser {
+            recorded_time: Some("2022-12-31T00:00:00Z".to_string()),
+            user: UserDetail {
+                id: "0".to_string(),
+                name: "Hoshimachi Suisei".to_string(),
+                username: "hoshimatisuisei".to_string(),
+                location: Some("Komoro".to_string()),
+                description: Some("Inui Toko Daisuki!".to_string()),
+                url: None,
+                profile_image_url: None,
+                profile_banner_url: None,
+                pinned_tweet_id: None,
+                protected: Some(false),
+                verified: Some(false)
+            }
+        };
+
+        let inui_toko_profile_0 = BasicUserDetail {
+            id: "1".to_string(),
+            username: "inui_toko".to_string(),
+            name: "Inui Toko".to_string()
+        };
+
+        let inui_toko_profile_1 = BasicUserDetail {
+            id: "1".to_string(),
+            username: "inui_toko".to_string(),
+            name: "Inui Toko!".to_string()
+        };
+
+        let hsmt_tweet_0 = FetchedTweet {
+            id: "001".to_string(),
+            author_id: "0".to_string(),
+            text: "Toko-chan chuu #inui_toko_daisuki @inui_toko".to_string(),
+            created_at: "2022-01-01T00:00:00Z".to_string(),
+            tweet_type: TweetType::Tweet,
+            mentions: Some(vec![inui_toko_profile_0.clone()]),
+            hashtags: Some(vec!["inui_toko_daisuki".to_string()]),
+            media: Some(vec![Media {
+                media_key: "3_001".to_string(),
+                media_type: "photo".to_string(),
+                url: Some("https://pbs.twimg.com/media/001.jpg".to_string()),
+                preview_image_url: None,
+                width: Some(1200),
+                height: Some(675),
+                alt_text: Some("Toko-chan".to_string()),
+                duration_ms: None,
+                content_hash: None,
+                local_path: None
+            }]),
+            urls: Some(vec![UrlEntity {
+                url: "https://t.co/abc".to_string(),
+                expanded_url: Some("https://www.youtube.com/watch?v=abc".to_string()),
+                display_url: Some("youtube.com/watch?v=abc".to_string()),
+                unwound_url: None,
+                domain: Some("youtube.com".to_string()),
+                title: None
+            }]),
+            conversation_id: Some("001".to_string()),
+            in_reply_to_user_id: None,
+            replied_to_id: None,
+            is_note_tweet: false,
+            edit_controls: Some(EditControls {
+                edits_remaining: 5,
+                is_edit_eligible: true,
+                editable_until: "2022-01-01T01:00:00.000Z".to_string()
+            }),
+            versions: None
+        };
+
+        let tkymtw_profile = BasicUserDetail {
+            id: "2".to_string(),
+            username: "tokoyami_towa".to_string(),
+            name: "Tokoyami Towa".to_string()
+        };
+
+        let tkymtw_tweet = BasicTweet {
+            id: "002".to_string(),
+            author_id: "2".to_string(),
+            text: "Suisei gomi! #hoshimachi".to_string(),
+            hashtags: Some(vec!["hoshimachi".to_string()]),
+            urls: None,
+            conversation_id: Some("002".to_string()),
+            replied_to_id: None
+        };
+
+        let hsmt_follow_0 = FollowingUser {
+            recorded_time: None,
+            user_id: "0".to_string(),
+            followed_user: inui_toko_profile_0.clone(),
+            action: FollowingAction::Follow
+        };
+
+        let hsmt_follow_1 = FollowingUser {
+            recorded_time: Some("2021-01-01T00:00:00Z".to_string()),
+            user_id: "0".to_string(),
+            followed_user: tkymtw_profile.clone(),
+            action: FollowingAction::Follow
+        };
+
+        let hsmt_like_0 = LikedTweet {
+            recorded_time: None,
+            user_id: "0".to_string(),
+            tweet: tkymtw_tweet.clone(),
+            author: tkymtw_profile.clone()
+        };
+
+        let hsmt_tweet_1 = FetchedTweet {
+            id: "003".to_string(),
+            author_id: "0".to_string(),
+            text: "a... @inui_toko".to_string(),
+            created_at: "2022-01-01T00:00:00Z".to_string(),
+            tweet_type: TweetType::Reply { tweet: tkymtw_tweet.clone(), author: tkymtw_profile.clone() },
+            mentions: Some(vec![tkymtw_profile.clone(), inui_toko_profile_1.clone()]),
+            hashtags: None,
+            media: None,
+            urls: None,
+            conversation_id: Some("002".to_string()),
+            in_reply_to_user_id: Some("2".to_string()),
+            replied_to_id: Some("002".to_string()),
+            is_note_tweet: false,
+            edit_controls: None,
+            versions: None
+        };
+
+        store.write_profile(&hsmtss_profile_0, &TaskType::Initializing).unwrap();
+        let gotten_hsmtss_profile_0 = store.get_profiles("hoshimatisuisei", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_profile_0, hsmtss_profile_0);
+
+        store.write_user(&inui_toko_profile_0).unwrap();
+        store.write_tweet(&hsmt_tweet_0).unwrap();
+        let gotten_hsmtss_tweet_0 = store.get_tweets("0", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_tweet_0, hsmt_tweet_0);
+
+        store.write_user(&tkymtw_profile).unwrap();
+        store.write_dict_tweet(&tkymtw_tweet).unwrap();
+        store.write_like(&hsmt_like_0, Placement::Newer).unwrap();
+        let gotten_hsmtss_like_0 = store.get_likes("0", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_like_0, hsmt_like_0);
+
+        store.write_following(&hsmt_follow_0, Placement::Newer).unwrap();
+        let gotten_hsmtss_follow_0 = store.get_following("0", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_follow_0, hsmt_follow_0);
+
+        store.write_profile(&hsmtss_profile_1, &TaskType::Monitoring).unwrap();
+        let gotten_hsmtss_profile_1 = store.get_profiles("hoshimatisuisei", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_profile_1, hsmtss_profile_1);
+
+        store.write_following(&hsmt_follow_1, Placement::Newer).unwrap();
+        let gotten_hsmtss_follow_1 = store.get_following("0", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_follow_1, hsmt_follow_1);
+
+        store.write_user(&inui_toko_profile_1).unwrap();
+        store.write_tweet(&hsmt_tweet_1).unwrap();
+        let gotten_hsmtss_tweet_1 = store.get_tweets("0", Some(1), 0).unwrap().into_iter().next().unwrap();
+        assert_eq!(gotten_hsmtss_tweet_1, hsmt_tweet_1);
+    }
+
+    #[test]
+    fn test_tweet_edit() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let store = SqliteStore::new(&conn);
+
+        let mut original = FetchedTweet::new();
+        original.id = "100".to_string();
+        original.author_id = "0".to_string();
+        original.created_at = "2023-01-01T00:00:00.000Z".to_string();
+        original.text = "Stellar Stellar #hoshimachi".to_string();
+        original.hashtags = Some(vec!["hoshimachi".to_string()]);
+        store.write_tweet(&original).unwrap();
+
+        let mut edited = FetchedTweet::new();
+        edited.id = "100".to_string();
+        edited.author_id = "0".to_string();
+        edited.created_at = "2023-01-01T00:10:00.000Z".to_string();
+        edited.text = "Stellar Stellar #suisei".to_string();
+        edited.hashtags = Some(vec!["suisei".to_string()]);
+        edited.versions = Some(vec![
+            TweetVersion { tweet_id: "100".to_string(), text: None },
+            TweetVersion { tweet_id: "105".to_string(), text: Some(edited.text.clone()) }
+        ]);
+        store.write_tweet(&edited).unwrap();
+        store.write_tweet(&edited).unwrap();
+
+        let stored = store.get_tweets("0", None, 0).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].text, "Stellar Stellar #suisei");
+        assert_eq!(stored[0].created_at, "2023-01-01T00:00:00.000Z");
+        assert_eq!(stored[0].hashtags, Some(vec!["suisei".to_string()]));
+        assert_eq!(stored[0].versions, Some(vec![
+            TweetVersion { tweet_id: "100".to_string(), text: Some("Stellar Stellar #hoshimachi".to_string()) },
+            TweetVersion { tweet_id: "105".to_string(), text: Some("Stellar Stellar #suisei".to_string()) }
+        ]));
+        assert_eq!(store.newest_tweet_id("0").unwrap(), Some("105".to_string()));
+        assert_eq!(search::search_tweets(&conn, "suisei", None, 10).unwrap().len(), 1);
+        assert!(search::search_tweets(&conn, "hoshimachi", None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stores() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        check_store(&SqliteStore::new(&conn));
+
+        check_store(&MemoryStore::new());
+    }
+}
@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::api_response::{Response, Tweet};
+use crate::configuration;
+use crate::raw_archive;
+use crate::errors::StreamError;
+use crate::request_builder::{self, TweetFetchResult, TWEET_EXPANSIONS, TWEET_FIELDS, TWEET_MEDIA_FIELDS};
+use crate::worker::StopSignal;
+
+/// Tags of the rules this tool manages start with it, other rules of the app are left alone
+const RULE_TAG_PREFIX: &str = "sui_twitter_db:";
+
+/// A filtered stream rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamRule {
+    pub value: String,
+    pub tag: String
+}
+
+/// A rule already set on the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExistingRule {
+    pub id: String,
+    pub rule: StreamRule
+}
+
+/// A rule as listed by the rules endpoint
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct RuleObject {
+    id: String,
+    value: String,
+    tag: Option<String>
+}
+
+/// One rule per monitored account, matching the tweets it posts
+pub fn monitoring_rules(usernames: &[String]) -> Vec<StreamRule> {
+    usernames.iter().map(|username| {
+        let username = username.trim_start_matches('@');
+        StreamRule {
+            value: format!("from:{username}"),
+            tag: format!("{RULE_TAG_PREFIX}{username}")
+        }
+    }).collect()
+}
+
+/// Ids of the managed rules to delete and the rules to add so the managed rules become `rules`
+pub fn rule_changes(existing_rules: &[ExistingRule], rules: &[StreamRule]) -> (Vec<String>, Vec<StreamRule>) {
+    let delete_ids = existing_rules.iter()
+        .filter(|existing_rule| existing_rule.rule.tag.starts_with(RULE_TAG_PREFIX) && !rules.contains(&existing_rule.rule))
+        .map(|existing_rule| existing_rule.id.clone())
+        .collect();
+    let add_rules = rules.iter()
+        .filter(|rule| !existing_rules.iter().any(|existing_rule| &existing_rule.rule == *rule))
+        .cloned()
+        .collect();
+    (delete_ids, add_rules)
+}
+
+/// Exponential reconnection delays
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Backoff {
+        Backoff { initial, max, current: initial }
+    }
+
+    /// The delay before the next attempt; each call doubles the following one up to `max`
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// Client of the v2 filtered stream
+pub struct FilteredStream {
+    base_url: String,
+    client: Client
+}
+
+impl Default for FilteredStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FilteredStream {
+    pub fn new() -> FilteredStream {
+        FilteredStream::with_base_url("https://api.twitter.com")
+    }
+
+    /// Connects to another host, e.g. a local stand-in
+    pub fn with_base_url(base_url: &str) -> FilteredStream {
+        FilteredStream {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            // the connection stays open for as long as the stream runs
+            client: Client::builder().timeout(None).build().expect("error in client builder")
+        }
+    }
+
+    /// Makes the managed rules of the stream match `rules`
+    pub fn sync_rules(&self, conf: &configuration::Config, rules: &[StreamRule]) -> Result<(), Box<dyn Error>> {
+        let rules_url = format!("{}/2/tweets/search/stream/rules", &self.base_url);
+        let response = self.client.get(&rules_url)
+            .header("Authorization", format!("Bearer {}", &conf.bearer_token))
+            .send()?.text()?;
+        let response_parsed: Response<Vec<RuleObject>> = serde_json::from_str(&response)?;
+
+        let existing_rules: Vec<ExistingRule> = response_parsed.data.unwrap_or_default().into_iter().map(|rule_raw| ExistingRule {
+            id: rule_raw.id,
+            rule: StreamRule { value: rule_raw.value, tag: rule_raw.tag.unwrap_or_default() }
+        }).collect();
+
+        let (delete_ids, add_rules) = rule_changes(&existing_rules, rules);
+        if !delete_ids.is_empty() {
+            self.post_rules(conf, &rules_url, json!({ "delete": { "ids": delete_ids } }))?;
+        }
+        if !add_rules.is_empty() {
+            let add_list: Vec<Value> = add_rules.iter().map(|rule| json!({ "value": &rule.value, "tag": &rule.tag })).collect();
+            self.post_rules(conf, &rules_url, json!({ "add": add_list }))?;
+        }
+        Ok(())
+    }
+
+    fn post_rules(&self, conf: &configuration::Config, rules_url: &str, body: Value) -> Result<(), Box<dyn Error>> {
+        let response = self.client.post(rules_url)
+            .header("Authorization", format!("Bearer {}", &conf.bearer_token))
+            .json(&body)
+            .send()?.text()?;
+        let response_parsed: Response<Value> = serde_json::from_str(&response)?;
+        if let Some(first_error) = response_parsed.errors.first() {
+            return Err(Box::new(StreamError::new(&format!("rule update rejected: {}", first_error.message()))));
+        }
+        Ok(())
+    }
+
+    /// Connects and hands every streamed tweet to `on_tweet` until the server ends the
+    /// connection or `stop` is given, which is noticed at the next line as the server sends a
+    /// keep-alive newline every 20 seconds. Returns whether anything, keep-alive newlines
+    /// included, was received.
+    pub fn consume(&self, conf: &configuration::Config, stop: &StopSignal, on_tweet: &mut dyn FnMut(TweetFetchResult)) -> Result<bool, Box<dyn Error>> {
+        let response = self.client.get(format!("{}/2/tweets/search/stream", &self.base_url))
+            .query(&[
+                ("expansions", TWEET_EXPANSIONS),
+                ("tweet.fields", TWEET_FIELDS),
+                ("user.fields", "id,name,username"),
+                ("media.fields", TWEET_MEDIA_FIELDS)
+            ])
+            .header("Authorization", format!("Bearer {}", &conf.bearer_token))
+            .send()?;
+        if !response.status().is_success() {
+            return Err(Box::new(StreamError::new(&format!("connection refused with {}", response.status()))));
+        }
+
+        let mut received = false;
+        for line in BufReader::new(response).lines() {
+            let line = line?;
+            received = true;
+            if stop.is_stopped() {
+                break;
+            }
+            // keep-alive
+            if line.trim().is_empty() {
+                continue;
+            }
+            raw_archive::archive_response(conf, raw_archive::STREAM, "filtered", &line);
+            let payload: Response<Tweet> = serde_json::from_str(&line)?;
+            if payload.data.is_some() {
+                on_tweet(request_builder::parse_tweet_payload(&payload)?);
+            } else if let Some(first_error) = payload.errors.first() {
+                // e.g. an operational disconnect
+                return Err(Box::new(StreamError::new(&format!("disconnected: {}", first_error.message()))));
+            }
+        }
+        Ok(received)
+    }
+
+    /// Keeps consuming the stream, calling `on_connect` before every connection so the caller
+    /// can catch up on what was missed in between. Connections that fail or end without
+    /// receiving anything are retried after `backoff` delays; after `max_failures` of them in a
+    /// row the last error is returned. Returns once `stop` is given.
+    pub fn run(
+        &self,
+        conf: &configuration::Config,
+        backoff: &mut Backoff,
+        max_failures: u32,
+        stop: &StopSignal,
+        on_connect: &mut dyn FnMut(),
+        on_tweet: &mut dyn FnMut(TweetFetchResult)
+    ) -> Result<(), Box<dyn Error>> {
+        let mut failures = 0;
+        loop {
+            on_connect();
+            let consumed = self.consume(conf, stop, on_tweet);
+            if stop.is_stopped() {
+                return Ok(());
+            }
+            let last_error: Box<dyn Error> = match consumed {
+                Ok(true) => {
+                    failures = 0;
+                    backoff.reset();
+                    continue;
+                }
+                Ok(false) => Box::new(StreamError::new("connection closed without data")),
+                Err(e) => e
+            };
+
+            failures += 1;
+            if failures >= max_failures {
+                return Err(last_error);
+            }
+            let delay = backoff.next_delay();
+            log::warn!("filtered stream: {}, reconnecting in {:?}", last_error, delay);
+            if stop.wait(delay) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use crate::configuration::{Config, IngestionMode, TaskType};
+    use crate::test_support::{self, read_request_head};
+
+    use super::*;
+
+    fn test_config() -> Config {
+        Config { ingestion: IngestionMode::Stream, ..test_support::config(TaskType::Monitoring) }
+    }
+
+    /// Answers one connection with `chunks` as a chunked stream
+    fn serve_chunks(stream: &mut TcpStream, chunks: &[&str]) {
+        read_request_head(stream);
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n").unwrap();
+        for chunk in chunks {
+            write!(stream, "{:x}\r\n{}\r\n", chunk.len(), chunk).unwrap();
+            stream.flush().unwrap();
+        }
+        stream.write_all(b"0\r\n\r\n").unwrap();
+    }
+
+    const TWEET_LINE: &str = r#"{"data":{"id":"100","text":"Stellar Stellar #hoshimachi","author_id":"1","created_at":"2023-01-01T00:00:00.000Z","conversation_id":"100","entities":{"hashtags":[{"tag":"hoshimachi"}]},"referenced_tweets":[{"type":"quoted","id":"90"}]},"includes":{"users":[{"id":"2","name":"Towa","username":"tokoyami_towa"}],"tweets":[{"id":"90","text":"quoted","author_id":"2"}]},"matching_rules":[{"id":"1","tag":"sui_twitter_db:suisei"}]}"#;
+
+    #[test]
+    fn test_consume() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // a tweet split over chunks between keep-alives
+            let (head, tail) = TWEET_LINE.split_at(40);
+            serve_chunks(&mut stream, &["\r\n", head, &format!("{tail}\r\n"), "\r\n"]);
+        });
+
+        let mut fetched_tweets = Vec::new();
+        let received = FilteredStream::with_base_url(&base_url)
+            .consume(&test_config(), &StopSignal::new(), &mut |(tweets, ref_tweets, ref_users)| {
+                assert_eq!(ref_tweets[0].id, "90");
+                assert_eq!(ref_users[0].username, "tokoyami_towa");
+                fetched_tweets.extend(tweets);
+            })
+            .unwrap();
+        server.join().unwrap();
+
+        assert!(received);
+        assert_eq!(fetched_tweets.len(), 1);
+        assert_eq!(fetched_tweets[0].id, "100");
+        assert_eq!(fetched_tweets[0].hashtags, Some(vec!["hoshimachi".to_string()]));
+        assert_eq!(fetched_tweets[0].conversation_id, Some("100".to_string()));
+    }
+
+    #[test]
+    fn test_run_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut refused, _) = listener.accept().unwrap();
+            read_request_head(&mut refused);
+            refused.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            drop(refused);
+
+            let (mut stream, _) = listener.accept().unwrap();
+            serve_chunks(&mut stream, &[&format!("{TWEET_LINE}\r\n")]);
+            // later connections are refused once the listener is gone
+        });
+
+        let mut connections = 0;
+        let mut fetched_tweets = 0;
+        let result = FilteredStream::with_base_url(&base_url).run(
+            &test_config(),
+            &mut Backoff::new(Duration::from_millis(10), Duration::from_millis(40)),
+            3,
+            &StopSignal::new(),
+            &mut || { connections += 1; },
+            &mut |(tweets, _, _)| { fetched_tweets += tweets.len(); }
+        );
+        server.join().unwrap();
+
+        assert!(result.is_err());
+        // refused, streamed, then three failures in a row
+        assert_eq!(connections, 5);
+        assert_eq!(fetched_tweets, 1);
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = monitoring_rules(&[String::from("@suisei_hosimati"), String::from("tokoyami_towa")]);
+        assert_eq!(rules[0], StreamRule { value: "from:suisei_hosimati".to_string(), tag: "sui_twitter_db:suisei_hosimati".to_string() });
+
+        let existing_rules = vec![
+            ExistingRule { id: "1".to_string(), rule: rules[0].clone() },
+            ExistingRule { id: "2".to_string(), rule: monitoring_rules(&[String::from("sakuramiko35")]).remove(0) },
+            ExistingRule { id: "3".to_string(), rule: StreamRule { value: "cat".to_string(), tag: "other".to_string() } }
+        ];
+        assert_eq!(rule_changes(&existing_rules, &rules), (vec!["2".to_string()], vec![rules[1].clone()]));
+
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(3));
+        assert_eq!(
+            [backoff.next_delay(), backoff.next_delay(), backoff.next_delay()],
+            [Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(3)]
+        );
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+use crate::configuration::{Config, IngestionMode, Polling, TaskType};
+use crate::db;
+use crate::transport::Transport;
+use crate::writer::DbWriter;
+
+/// A page of two users pointing at [`LAST_PAGE`]
+pub const FIRST_PAGE: &str = r#"{"data":[{"id":"3","name":"C","username":"c"},{"id":"2","name":"B","username":"b"}],"meta":{"result_count":2,"next_token":"page2"}}"#;
+pub const LAST_PAGE: &str = r#"{"data":[{"id":"1","name":"A","username":"a"}],"meta":{"result_count":1}}"#;
+
+/// A polling configuration running `task_type` over HTTP; tests change the rest with `..config(task_type)`
+pub fn config(task_type: TaskType) -> Config {
+    Config {
+        conf_path: String::new(),
+        bearer_token: String::from("aaaabbbb"),
+        monitoring_username: vec![String::from("@suisei_hosimati")],
+        db_path: String::new(),
+        media_archive_dir: None,
+        raw_archive_dir: None,
+        saved_searches: Vec::new(),
+        ingestion: IngestionMode::Polling,
+        transport: Transport::Http,
+        polling: Polling::default(),
+        verbose: false,
+        task_type
+    }
+}
+
+/// An initialized database in the temp directory, named after `name`, with a writer on it.
+/// Returns its path, the writer and the writer thread.
+pub fn writer_db(name: &str) -> (String, DbWriter, JoinHandle<()>) {
+    let db_path = std::env::temp_dir().join(format!("sui_{}_{}.db", name, std::process::id()));
+    let db_path = db_path.to_str().unwrap().to_string();
+    remove_db(&db_path);
+    db::init_db(&db::open(&db_path).unwrap()).unwrap();
+    let (writer, writer_handle) = DbWriter::start(&db_path).unwrap();
+    (db_path, writer, writer_handle)
+}
+
+/// Deletes a database file with its write-ahead log
+pub fn remove_db(db_path: &str) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{db_path}{suffix}"));
+    }
+}
+
+/// Reads the head of a request, returning its request line
+pub fn read_request_head(stream: &mut TcpStream) -> String {
+    let mut head: Vec<u8> = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap().lines().next().unwrap().to_string()
+}
+
+/// Answers one request per page, then refuses connections. Returns the base url to request and
+/// the server, joining to the request lines received.
+pub fn serve_pages(pages: &'static [&'static str]) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let mut request_lines = Vec::new();
+        for page in pages {
+            let (mut stream, _) = listener.accept().unwrap();
+            request_lines.push(read_request_head(&mut stream));
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", page.len(), page).unwrap();
+        }
+        request_lines
+    });
+    (base_url, server)
+}
+
+/// Query string of a request line, empty without one
+pub fn query_of(request_line: &str) -> String {
+    request_line.split(' ').nth(1)
+        .and_then(|target| target.split_once('?'))
+        .map(|(_, query)| query.to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use rusqlite::{Connection, named_params};
+
+use crate::configuration;
+use crate::query_result::{BasicTweet, BasicUserDetail};
+use crate::request_builder::TweetLookupFetcher;
+use crate::store::{SqliteStore, Store};
+
+/// A tweet of a conversation with the replies to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadNode {
+    /// Hashtags and urls are not loaded
+    pub tweet: BasicTweet,
+    /// `None` when the author was never stored
+    pub author: Option<BasicUserDetail>,
+    /// Replies in posting order
+    pub replies: Vec<ThreadNode>
+}
+
+/// The stored part of a conversation, as a reply tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thread {
+    pub conversation_id: String,
+    /// The conversation's first tweet, followed by the replies whose parent is not stored,
+    /// in posting order
+    pub roots: Vec<ThreadNode>,
+    /// Ids of tweets replied to within the conversation that are not stored
+    pub missing_parent_ids: Vec<String>
+}
+
+/// Builds the reply tree of `conversation_id` from tweets in `user_tweet` and `tweet_dict`
+pub fn get_thread(conn: &Connection, conversation_id: &str) -> Result<Thread, Box<dyn Error>> {
+    let mut thread_stmt = conn.prepare(
+        "WITH conversation AS (
+            SELECT tweet_id, author_id, tweet_text AS text, conversation_id, replied_to_id, 0 AS source
+            FROM user_tweet WHERE conversation_id = :conversation_id OR tweet_id = :conversation_id
+            UNION ALL
+            SELECT tweet_id, author_id, text, conversation_id, replied_to_id, 1 AS source
+            FROM tweet_dict WHERE conversation_id = :conversation_id OR tweet_id = :conversation_id
+        )
+        SELECT conversation.tweet_id, conversation.author_id, conversation.text, conversation.conversation_id,
+            conversation.replied_to_id,
+            COALESCE(user_dict.username, (SELECT username FROM user_profile
+                WHERE user_profile.user_id = conversation.author_id ORDER BY id DESC LIMIT 1)),
+            COALESCE(user_dict.name, (SELECT name FROM user_profile
+                WHERE user_profile.user_id = conversation.author_id ORDER BY id DESC LIMIT 1))
+        FROM conversation
+        LEFT JOIN user_dict ON user_dict.user_id = conversation.author_id
+        ORDER BY conversation.source"
+    )?;
+    let query_results = thread_stmt.query_map(
+        named_params! { ":conversation_id": conversation_id },
+        |row| {
+            let tweet = BasicTweet {
+                id: row.get(0)?,
+                author_id: row.get(1)?,
+                text: row.get(2)?,
+                hashtags: None,
+                urls: None,
+                conversation_id: row.get(3)?,
+                replied_to_id: row.get(4)?
+            };
+            let author = match (row.get::<_, Option<String>>(5)?, row.get::<_, Option<String>>(6)?) {
+                (Some(username), Some(name)) => Some(BasicUserDetail { id: tweet.author_id.clone(), username, name }),
+                _ => None
+            };
+            Ok((tweet, author))
+        }
+    )?;
+
+    // a tweet can be in both `user_tweet` and `tweet_dict`; the `user_tweet` row wins
+    let mut tweets: HashMap<String, (BasicTweet, Option<BasicUserDetail>)> = HashMap::new();
+    for query_result in query_results {
+        let (tweet, author) = query_result?;
+        tweets.entry(tweet.id.clone()).or_insert((tweet, author));
+    }
+
+    Ok(build_thread(conversation_id, tweets))
+}
+
+/// Looks up the missing parents of `conversation_id` and stores them in `tweet_dict` and
+/// `user_dict`, repeating for parents of the fetched parents for up to `max_rounds` lookups.
+/// Returns the thread with whatever is still missing, e.g. deleted tweets.
+pub fn hydrate_missing_parents(
+    conf: &configuration::Config,
+    conn: &Connection,
+    conversation_id: &str,
+    max_rounds: u8
+) -> Result<Thread, Box<dyn Error>> {
+    let mut thread = get_thread(conn, conversation_id)?;
+    let mut requested_ids: HashSet<String> = HashSet::new();
+
+    for _ in 0..max_rounds {
+        let lookup_ids: Vec<String> = thread.missing_parent_ids.iter()
+            .filter(|tweet_id| !requested_ids.contains(*tweet_id))
+            .cloned()
+            .collect();
+        if lookup_ids.is_empty() {
+            break;
+        }
+        requested_ids.extend(lookup_ids.iter().cloned());
+
+        let (fetched_tweets, related_users, _) = TweetLookupFetcher::new(&lookup_ids).fetch(conf)?;
+        let store = SqliteStore::new(conn);
+        for user in related_users.iter() {
+            store.write_user(user)?;
+        }
+        for tweet in fetched_tweets.iter() {
+            store.write_dict_tweet(tweet)?;
+        }
+        thread = get_thread(conn, conversation_id)?;
+    }
+
+    Ok(thread)
+}
+
+fn build_thread(conversation_id: &str, mut tweets: HashMap<String, (BasicTweet, Option<BasicUserDetail>)>) -> Thread {
+    let mut root_ids: Vec<String> = Vec::new();
+    let mut reply_ids: HashMap<String, Vec<String>> = HashMap::new();
+    let mut missing_parent_ids: HashSet<String> = HashSet::new();
+    if !tweets.contains_key(conversation_id) {
+        missing_parent_ids.insert(conversation_id.to_string());
+    }
+
+    for (tweet_id, (tweet, _)) in tweets.iter() {
+        match &tweet.replied_to_id {
+            Some(parent_id) if tweets.contains_key(parent_id) => {
+                reply_ids.entry(parent_id.clone()).or_default().push(tweet_id.clone());
+            }
+            Some(parent_id) => {
+                missing_parent_ids.insert(parent_id.clone());
+                root_ids.push(tweet_id.clone());
+            }
+            None => { root_ids.push(tweet_id.clone()); }
+        }
+    }
+
+    sort_by_posting_order(&mut root_ids);
+    // the conversation's first tweet goes first even if a stray reply was posted before it
+    // according to its id, which happens with tweets fetched from a different conversation
+    if let Some(position) = root_ids.iter().position(|tweet_id| tweet_id == conversation_id) {
+        let first_tweet_id = root_ids.remove(position);
+        root_ids.insert(0, first_tweet_id);
+    }
+    let roots = root_ids.iter()
+        .filter_map(|tweet_id| build_node(tweet_id, &mut tweets, &mut reply_ids))
+        .collect();
+
+    let mut missing_parent_ids: Vec<String> = missing_parent_ids.into_iter().collect();
+    sort_by_posting_order(&mut missing_parent_ids);
+    Thread {
+        conversation_id: conversation_id.to_string(),
+        roots,
+        missing_parent_ids
+    }
+}
+
+fn build_node(
+    tweet_id: &str,
+    tweets: &mut HashMap<String, (BasicTweet, Option<BasicUserDetail>)>,
+    reply_ids: &mut HashMap<String, Vec<String>>
+) -> Option<ThreadNode> {
+    // removing visited tweets keeps a malformed reply cycle from recursing forever
+    let (tweet, author) = tweets.remove(tweet_id)?;
+    let mut child_ids = reply_ids.remove(tweet_id).unwrap_or_default();
+    sort_by_posting_order(&mut child_ids);
+    let replies = child_ids.iter()
+        .filter_map(|child_id| build_node(child_id, tweets, reply_ids))
+        .collect();
+    Some(ThreadNode { tweet, author, replies })
+}
+
+/// Tweet ids grow over time, so shorter ids are older and equally long ones compare as strings
+fn sort_by_posting_order(tweet_ids: &mut [String]) {
+    tweet_ids.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::init_db;
+    use crate::query_result::FetchedTweet;
+
+    use super::*;
+
+    fn dict_tweet(id: &str, author_id: &str, replied_to_id: Option<&str>) -> BasicTweet {
+        BasicTweet {
+            id: id.to_string(),
+            author_id: author_id.to_string(),
+            text: format!("tweet {id}"),
+            hashtags: None,
+            urls: None,
+            conversation_id: Some("100".to_string()),
+            replied_to_id: replied_to_id.map(|id| id.to_string())
+        }
+    }
+
+    fn node_ids(nodes: &[ThreadNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.tweet.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_get_thread() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let store = SqliteStore::new(&conn);
+        store.write_user(&BasicUserDetail { id: "1".to_string(), username: "suisei_hosimati".to_string(), name: "Suisei".to_string() }).unwrap();
+
+        store.write_dict_tweet(&dict_tweet("100", "1", None)).unwrap();
+        store.write_dict_tweet(&dict_tweet("1000", "2", Some("100"))).unwrap();
+        store.write_dict_tweet(&dict_tweet("999", "3", Some("100"))).unwrap();
+        store.write_dict_tweet(&dict_tweet("1002", "1", Some("1001"))).unwrap();
+
+        let mut own_reply = FetchedTweet::new();
+        own_reply.id = "1005".to_string();
+        own_reply.author_id = "1".to_string();
+        own_reply.created_at = "2023-01-01T20:00:00.000Z".to_string();
+        own_reply.text = "reply".to_string();
+        own_reply.conversation_id = Some("100".to_string());
+        own_reply.replied_to_id = Some("999".to_string());
+        store.write_tweet(&own_reply).unwrap();
+
+        let thread = get_thread(&conn, "100").unwrap();
+        assert_eq!(node_ids(&thread.roots), vec!["100", "1002"]);
+        assert_eq!(thread.missing_parent_ids, vec!["1001".to_string()]);
+
+        let first_tweet = &thread.roots[0];
+        assert_eq!(first_tweet.author.as_ref().unwrap().username, "suisei_hosimati");
+        assert_eq!(node_ids(&first_tweet.replies), vec!["999", "1000"]);
+        assert_eq!(node_ids(&first_tweet.replies[0].replies), vec!["1005"]);
+        assert_eq!(first_tweet.replies[0].replies[0].tweet.text, "reply");
+        assert_eq!(first_tweet.replies[1].author, None);
+    }
+
+    #[test]
+    fn test_missing_first_tweet() {
+        let mut tweets = HashMap::new();
+        tweets.insert("101".to_string(), (dict_tweet("101", "2", Some("100")), None));
+        let thread = build_thread("100", tweets);
+        assert_eq!(node_ids(&thread.roots), vec!["101"]);
+        assert_eq!(thread.missing_parent_ids, vec!["100".to_string()]);
+    }
+}
@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use reqwest::blocking::RequestBuilder;
+use serde::{Serialize, Deserialize};
+
+use crate::errors::CassetteError;
+
+/// A request with the response it got. Headers, and with them the bearer token, are not kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    /// Full URL with the query string
+    pub url: String,
+    pub status: u16,
+    pub body: String
+}
+
+/// Interactions kept in a JSON file
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>
+}
+
+impl Cassette {
+    /// Records `interaction`, saving the whole cassette so an interrupted session keeps what it
+    /// recorded
+    fn push(&self, interaction: Interaction) -> Result<(), Box<dyn Error>> {
+        let mut interactions = self.interactions.lock().expect("the cassette lock should not be poisoned");
+        interactions.push(interaction);
+        fs::write(&self.path, serde_json::to_string_pretty(&*interactions)?)?;
+        Ok(())
+    }
+
+    /// Takes the first interaction not served yet with the same method and URL, so repeated
+    /// requests get the responses in the order they were recorded
+    fn take(&self, method: &str, url: &str) -> Result<Interaction, Box<dyn Error>> {
+        let mut interactions = self.interactions.lock().expect("the cassette lock should not be poisoned");
+        match interactions.iter().position(|interaction| interaction.method == method && interaction.url == url) {
+            Some(interaction_index) => Ok(interactions.remove(interaction_index)),
+            None => Err(Box::new(CassetteError::new(&format!("no recorded response for {} {}", method, url))))
+        }
+    }
+}
+
+/// How fetchers send their requests: over HTTP, over HTTP while recording to a cassette, or
+/// served from a recorded cassette without network access
+#[derive(Debug, Clone, Default)]
+pub enum Transport {
+    #[default]
+    Http,
+    Record(Arc<Cassette>),
+    Replay(Arc<Cassette>)
+}
+
+impl Transport {
+    /// Records to `path`, replacing what it held
+    pub fn record(path: &str) -> Result<Transport, Box<dyn Error>> {
+        fs::write(path, "[]")?;
+        Ok(Transport::Record(Arc::new(Cassette {
+            path: PathBuf::from(path),
+            interactions: Mutex::new(Vec::new())
+        })))
+    }
+
+    /// Serves the interactions recorded in `path`
+    pub fn replay(path: &str) -> Result<Transport, Box<dyn Error>> {
+        let interactions: Vec<Interaction> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Transport::Replay(Arc::new(Cassette {
+            path: PathBuf::from(path),
+            interactions: Mutex::new(interactions)
+        })))
+    }
+
+    /// Sends `request`, returning the response body whatever the status, as error payloads are
+    /// parsed by the fetchers
+    pub fn send(&self, request: RequestBuilder) -> Result<String, Box<dyn Error>> {
+        match self {
+            Transport::Http => Ok(request.send()?.text()?),
+            Transport::Record(cassette) => {
+                let (method, url) = request_key(&request)?;
+                let response = request.send()?;
+                let status = response.status().as_u16();
+                let body = response.text()?;
+                cassette.push(Interaction { method, url, status, body: body.clone() })?;
+                Ok(body)
+            }
+            Transport::Replay(cassette) => {
+                let (method, url) = request_key(&request)?;
+                Ok(cassette.take(&method, &url)?.body)
+            }
+        }
+    }
+}
+
+/// Transports are equal when they send the same way to the same cassette
+impl PartialEq for Transport {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Transport::Http, Transport::Http) => true,
+            (Transport::Record(cassette), Transport::Record(other_cassette))
+            | (Transport::Replay(cassette), Transport::Replay(other_cassette)) => cassette.path == other_cassette.path,
+            _ => false
+        }
+    }
+}
+
+/// Method and URL a request is recorded and looked up under
+fn request_key(request: &RequestBuilder) -> Result<(String, String), Box<dyn Error>> {
+    let request = request.try_clone().expect("the request should be cloned").build()?;
+    Ok((request.method().to_string(), request.url().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use reqwest::blocking::Client;
+
+    use crate::test_support::serve_pages;
+
+    use super::*;
+
+    #[test]
+    fn test_record_replay() {
+        let cassette_path = env::temp_dir().join(format!("sui_cassette_{}.json", std::process::id()));
+        let cassette_path = cassette_path.to_str().unwrap();
+
+        // answers a single request
+        let (base_url, server) = serve_pages(&[r#"{"data":[]}"#]);
+        let request = || Client::new().get(format!("{base_url}/2/users/1/tweets"))
+            .query(&[("max_results", "100")])
+            .header("Authorization", "Bearer aaaabbbb");
+
+        let recording = Transport::record(cassette_path).unwrap();
+        assert_eq!(recording.send(request()).unwrap(), r#"{"data":[]}"#);
+        server.join().unwrap();
+        let recorded = fs::read_to_string(cassette_path).unwrap();
+        assert!(recorded.contains("/2/users/1/tweets?max_results=100"));
+        assert!(!recorded.contains("aaaabbbb"));
+
+        // the server is gone, the response comes from the cassette once
+        let replaying = Transport::replay(cassette_path).unwrap();
+        assert_eq!(replaying.send(request()).unwrap(), r#"{"data":[]}"#);
+        assert!(replaying.send(request()).is_err());
+        assert!(replaying.send(Client::new().get(format!("{base_url}/2/users/1/liked_tweets"))).is_err());
+        assert_eq!(replaying, Transport::replay(cassette_path).unwrap());
+        assert_ne!(replaying, Transport::Http);
+
+        fs::remove_file(cassette_path).unwrap();
+    }
+}
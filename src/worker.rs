@@ -0,0 +1,153 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Tells a worker to stop, waking it up from its waits
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal {
+    stopped: Arc<(Mutex<bool>, Condvar)>
+}
+
+impl StopSignal {
+    pub fn new() -> StopSignal {
+        StopSignal::default()
+    }
+
+    pub fn stop(&self) {
+        let (stopped, stop_changed) = &*self.stopped;
+        *stopped.lock().expect("the stop lock should not be poisoned") = true;
+        stop_changed.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.0.lock().expect("the stop lock should not be poisoned")
+    }
+
+    /// Sleeps for `duration` unless stopped before; returns whether it was stopped
+    pub fn wait(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let (stopped, stop_changed) = &*self.stopped;
+        let mut is_stopped = stopped.lock().expect("the stop lock should not be poisoned");
+        while !*is_stopped {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            is_stopped = stop_changed.wait_timeout(is_stopped, deadline - now).expect("the stop lock should not be poisoned").0;
+        }
+        *is_stopped
+    }
+}
+
+/// A named thread running until its [`StopSignal`] is given
+#[derive(Debug)]
+pub struct Worker {
+    name: String,
+    stop: StopSignal,
+    handle: JoinHandle<()>
+}
+
+impl Worker {
+    /// Runs `work` on a new thread named `name`, handing it the signal to stop at
+    pub fn spawn(name: &str, work: impl FnOnce(StopSignal) + Send + 'static) -> Worker {
+        let stop = StopSignal::new();
+        let worker_stop = stop.clone();
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || work(worker_stop))
+            .expect("the worker thread should be spawned");
+        Worker { name: name.to_string(), stop, handle }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Signals the worker to stop without waiting for it
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+
+    /// Whether the worker's thread has returned, stopped or not
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the worker to finish what it is doing; a worker that panicked is logged
+    pub fn join(self) {
+        if self.handle.join().is_err() {
+            log::warn!("worker {} had panicked", &self.name);
+        }
+    }
+}
+
+/// Signals every worker to stop, then waits for each to finish what it is doing. Workers that
+/// panicked are logged.
+pub fn stop_all(workers: Vec<Worker>) {
+    for worker in workers.iter() {
+        worker.stop();
+    }
+    for worker in workers.into_iter() {
+        worker.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_stop_signal() {
+        let stop = StopSignal::new();
+        assert!(!stop.wait(Duration::from_millis(10)));
+
+        let waiting_stop = stop.clone();
+        let waiting = thread::spawn(move || {
+            let started = Instant::now();
+            (waiting_stop.wait(Duration::from_secs(60)), started.elapsed())
+        });
+        thread::sleep(Duration::from_millis(20));
+        stop.stop();
+        let (stopped, waited) = waiting.join().unwrap();
+        assert!(stopped);
+        assert!(waited < Duration::from_secs(60));
+        assert!(stop.is_stopped());
+        assert!(stop.wait(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_stop_all() {
+        let rounds = Arc::new(AtomicUsize::new(0));
+        let workers: Vec<Worker> = (0..3).map(|worker_index| {
+            let rounds = Arc::clone(&rounds);
+            Worker::spawn(&format!("worker-{worker_index}"), move |stop| loop {
+                rounds.fetch_add(1, Ordering::SeqCst);
+                if stop.wait(Duration::from_secs(60)) {
+                    break;
+                }
+            })
+        }).collect();
+        assert_eq!(workers[1].name(), "worker-1");
+        let panicking = Worker::spawn("panicking", |_| panic!("worker failure"));
+
+        stop_all(workers);
+        assert_eq!(rounds.load(Ordering::SeqCst), 3);
+        stop_all(vec![panicking]);
+
+        // a stopped worker is left to finish on its own
+        let (started_sender, started) = std::sync::mpsc::channel();
+        let slow = Worker::spawn("slow", move |_| {
+            started_sender.send(()).unwrap();
+            thread::sleep(Duration::from_millis(200));
+        });
+        started.recv().unwrap();
+        slow.stop();
+        assert!(!slow.is_finished());
+        while !slow.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        slow.join();
+    }
+}
@@ -0,0 +1,122 @@
+use std::error::Error;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+use rusqlite::Connection;
+
+use crate::db;
+use crate::errors::WriterError;
+
+type WriteJob = Box<dyn FnOnce(&Connection) + Send>;
+
+/// Funnels the writes of every thread through one connection on a writer thread, so writers
+/// never contend for the database lock. Threads read on their own connections from
+/// [`db::open`]; in WAL mode those reads do not block the writer.
+#[derive(Clone)]
+pub struct DbWriter {
+    sender: Sender<WriteJob>
+}
+
+impl DbWriter {
+    /// Opens `db_path` for the writer thread, which runs until every `DbWriter` clone is dropped
+    pub fn start(db_path: &str) -> Result<(DbWriter, JoinHandle<()>), Box<dyn Error>> {
+        let conn = db::open(db_path)?;
+        let (sender, receiver) = mpsc::channel::<WriteJob>();
+        let handle = thread::Builder::new().name(String::from("db-writer")).spawn(move || {
+            for job in receiver {
+                job(&conn);
+            }
+        })?;
+        Ok((DbWriter { sender }, handle))
+    }
+
+    /// Runs `write` in one transaction on the writer thread once the writes queued before it are
+    /// done, and returns its result. The transaction is rolled back when `write` fails.
+    pub fn write<T: Send + 'static>(
+        &self,
+        write: impl FnOnce(&Connection) -> Result<T, Box<dyn Error>> + Send + 'static
+    ) -> Result<T, Box<dyn Error>> {
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job: WriteJob = Box::new(move |conn| {
+            let result = conn.unchecked_transaction().map_err(|e| e.to_string()).and_then(|transaction| {
+                let written = write(&transaction).map_err(|e| e.to_string())?;
+                transaction.commit().map_err(|e| e.to_string())?;
+                Ok(written)
+            });
+            // the caller may have given up waiting
+            let _ = result_sender.send(result);
+        });
+
+        self.sender.send(job).map_err(|_| WriterError::new("the writer thread has stopped"))?;
+        match result_receiver.recv() {
+            Ok(result) => result.map_err(|e| -> Box<dyn Error> { Box::new(WriterError::new(&e)) }),
+            Err(_) => Err(Box::new(WriterError::new("the writer thread stopped before the write")))
+        }
+    }
+
+    /// Waits until every write queued so far is done
+    pub fn flush(&self) -> Result<(), Box<dyn Error>> {
+        self.write(|_| Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::query_result::BasicUserDetail;
+    use crate::store::{SqliteStore, Store};
+    use crate::test_support;
+
+    use super::*;
+
+    #[test]
+    fn test_concurrent_writers() {
+        let db_path = env::temp_dir().join(format!("sui_writer_{}.db", std::process::id()));
+        let db_path = db_path.to_str().unwrap().to_string();
+        db::init_db(&db::open(&db_path).unwrap()).unwrap();
+
+        let (writer, writer_handle) = DbWriter::start(&db_path).unwrap();
+        let workers: Vec<JoinHandle<()>> = (0..8).map(|worker| {
+            let writer = writer.clone();
+            let db_path = db_path.clone();
+            thread::spawn(move || {
+                // every worker reads on its own connection while the others write
+                let conn = db::open(&db_path).unwrap();
+                for record in 0..50 {
+                    let user = BasicUserDetail {
+                        id: format!("{worker}-{record}"),
+                        username: format!("user{worker}_{record}"),
+                        name: String::from("worker")
+                    };
+                    writer.write(move |conn| SqliteStore::new(conn).write_user(&user)).unwrap();
+                    let written: i64 = conn.query_row("SELECT COUNT(*) FROM user_dict", [], |row| row.get(0)).unwrap();
+                    assert!(written > record);
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        writer.flush().unwrap();
+
+        // a failed write leaves nothing behind
+        let failed = writer.write(|conn| {
+            SqliteStore::new(conn).write_user(&BasicUserDetail { id: String::from("x"), username: String::from("x"), name: String::from("x") })?;
+            conn.execute("INSERT INTO missing_table VALUES (1)", [])?;
+            Ok(())
+        });
+        assert!(failed.is_err());
+
+        let conn = db::open(&db_path).unwrap();
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+        let written: i64 = conn.query_row("SELECT COUNT(*) FROM user_dict", [], |row| row.get(0)).unwrap();
+        assert_eq!(written, 400);
+
+        drop(writer);
+        writer_handle.join().unwrap();
+        drop(conn);
+        test_support::remove_db(&db_path);
+    }
+}